@@ -1,2 +1,60 @@
+use std::time::{Duration, Instant};
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+///
+/// The open chunk: entries accepted by the writer that are held uncompressed
+/// in memory until the chunk is sealed into the store.
+///
 pub struct Cache {
-}
\ No newline at end of file
+    pub entries: Vec<ChunkEntry>,
+    pub entries_bytes_length: usize,
+    pub opened_at: Option<Instant>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache::new()
+    }
+}
+
+impl Cache {
+    pub fn new() -> Cache {
+        Cache {
+            entries: Vec::new(),
+            entries_bytes_length: 0,
+            opened_at: None,
+        }
+    }
+    pub fn push(&mut self, entry: ChunkEntry) {
+        if self.opened_at.is_none() {
+            self.opened_at = Some(Instant::now());
+        }
+        // Timestamp and action plus the null terminators of target and message
+        self.entries_bytes_length += 11 + entry.target.len() + entry.message.len();
+        self.entries.push(entry);
+    }
+    ///
+    /// Reopen a previously sealed chunk so that further entries extend it rather
+    /// than starting a new one. The open chunk's age counts from the resume.
+    ///
+    pub fn resume(&mut self, entries: Vec<ChunkEntry>) {
+        for entry in entries.into_iter() {
+            self.push(entry);
+        }
+        self.opened_at = Some(Instant::now());
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn age(&self) -> Duration {
+        return match self.opened_at {
+            Some(opened_at) => opened_at.elapsed(),
+            None => Duration::ZERO,
+        };
+    }
+    pub fn take_entries(&mut self) -> Vec<ChunkEntry> {
+        self.entries_bytes_length = 0;
+        self.opened_at = None;
+        return std::mem::take(&mut self.entries);
+    }
+}
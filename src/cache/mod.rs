@@ -1,2 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod cache;
-pub mod entry;
\ No newline at end of file
+pub mod entry;
+pub mod write_ahead_log;
\ No newline at end of file
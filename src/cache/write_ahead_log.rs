@@ -0,0 +1,285 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+use flate2::Crc;
+use crate::{byte_layout, reify};
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsyncPolicy {
+    EVERY_ENTRY,
+    INTERVAL(Duration),
+    OS_MANAGED,
+}
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct WriteAheadLogRecord {
+        #[byte_size=8]
+        pub sequence: u64,
+        #[byte_size=4]
+        pub length: u32,
+        #[byte_size=4]
+        pub checksum: u32,
+        pub entry: Vec<u8>,
+    }
+}
+
+byte_layout!{
+    WriteAheadLogRecord
+    value [sequence, u64, Big]
+    value [length, u32, Big]
+    value [checksum, u32, Big]
+    bytes_vec [entry, length]
+}
+
+impl WriteAheadLogRecord {
+    pub fn from_entry(sequence: u64, entry: &ChunkEntry) -> WriteAheadLogRecord {
+        let entry_bytes: Vec<u8> = entry.into_bytes();
+        WriteAheadLogRecord {
+            sequence,
+            length: entry_bytes.len() as u32,
+            checksum: Self::checksum_of(sequence, &entry_bytes),
+            entry: entry_bytes,
+        }
+    }
+    pub fn checksum_of(sequence: u64, bytes: &[u8]) -> u32 {
+        let mut crc: Crc = Crc::new();
+        crc.update(&sequence.to_be_bytes());
+        crc.update(bytes);
+        return crc.sum();
+    }
+    pub fn is_valid(&self) -> bool {
+        self.entry.len() == self.length as usize && Self::checksum_of(self.sequence, &self.entry) == self.checksum
+    }
+}
+
+///
+/// Append-only log of entries accepted into the open chunk but not yet sealed
+/// into the store. Every record is framed with its length and a CRC32 so a torn
+/// write at the tail is detected and discarded on replay. Records are numbered
+/// in append order; the store records the number of the last one sealed with
+/// each chunk, so records the store already holds are skipped on replay if the
+/// log was not truncated after the seal.
+///
+pub struct WriteAheadLog {
+    pub path: String,
+    pub policy: FsyncPolicy,
+    file: File,
+    last_sync: Instant,
+    dirty: bool,
+    next_sequence: u64,
+}
+
+impl WriteAheadLog {
+    pub fn path_for_store(store_path: &str) -> String {
+        format!("{}.wal", store_path)
+    }
+    pub fn open(path: &str, policy: FsyncPolicy) -> Result<WriteAheadLog, io::Error> {
+        let file: File = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        return Ok(WriteAheadLog {
+            path: String::from(path),
+            policy,
+            file,
+            last_sync: Instant::now(),
+            dirty: false,
+            next_sequence: 1,
+        });
+    }
+    ///
+    /// Record an entry in the log, syncing according to the configured policy.
+    /// The entry must not be acknowledged to the caller until this returns `Ok`.
+    ///
+    /// # Arguments
+    /// * entry: Entry about to be buffered in the open chunk
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result
+    ///
+    pub fn append(&mut self, entry: &ChunkEntry) -> Result<(), io::Error> {
+        let record: WriteAheadLogRecord = WriteAheadLogRecord::from_entry(self.next_sequence, entry);
+        self.file.write_all(record.into_bytes().as_slice())?;
+        self.next_sequence += 1;
+        self.dirty = true;
+        return match self.policy {
+            FsyncPolicy::EVERY_ENTRY => self.sync(),
+            FsyncPolicy::INTERVAL(_) => self.sync_if_due(),
+            FsyncPolicy::OS_MANAGED => Ok(()),
+        };
+    }
+    pub fn sync_if_due(&mut self) -> Result<(), io::Error> {
+        if let FsyncPolicy::INTERVAL(interval) = self.policy {
+            if self.dirty && self.last_sync.elapsed() >= interval {
+                return self.sync();
+            }
+        }
+        return Ok(());
+    }
+    pub fn sync(&mut self) -> Result<(), io::Error> {
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        self.dirty = false;
+        return Ok(());
+    }
+    ///
+    /// Sequence number of the last record appended, or of the last record
+    /// sealed into the store if none have been appended since.
+    ///
+    pub fn last_sequence(&self) -> u64 {
+        self.next_sequence - 1
+    }
+    ///
+    /// Read back every intact record in the log that has not been sealed into
+    /// the store. Reading stops at the first truncated or corrupt record, and
+    /// the log is cut back to the last intact record so subsequent appends do
+    /// not follow garbage. Records appended afterwards are numbered on from the
+    /// newest record read or sealed.
+    ///
+    /// # Arguments
+    /// * sealed_sequence: Sequence number of the last record the store holds
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkEntry>>`: Unsealed entries in the order they were appended
+    ///
+    pub fn replay(&mut self, sealed_sequence: u64) -> Result<Vec<ChunkEntry>, io::Error> {
+        let mut bytes: Vec<u8> = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut bytes)?;
+        let mut entries: Vec<ChunkEntry> = Vec::new();
+        let mut tail: &[u8] = bytes.as_slice();
+        let mut valid_length: usize = 0;
+        let mut last_sequence: u64 = sealed_sequence;
+        while !tail.is_empty() {
+            let mut record: WriteAheadLogRecord = WriteAheadLogRecord::default();
+            tail = match record.parse_bytes::<&'_ [u8], nom::error::Error<_>>(tail) {
+                Ok(t) => t,
+                Err(_) => break,
+            };
+            if !record.is_valid() {
+                break;
+            }
+            let mut entry: ChunkEntry = ChunkEntry::default();
+            if entry.parse_bytes::<&'_ [u8], nom::error::Error<_>>(record.entry.as_slice()).is_err() {
+                break;
+            }
+            if record.sequence > sealed_sequence {
+                entries.push(entry);
+            }
+            last_sequence = last_sequence.max(record.sequence);
+            valid_length = bytes.len() - tail.len();
+        }
+        self.next_sequence = last_sequence + 1;
+        if valid_length < bytes.len() {
+            warn!(
+                crate::LOGGER,
+                "Discarding {} trailing bytes of incomplete records in write-ahead log {}",
+                bytes.len() - valid_length,
+                self.path
+            );
+            self.file.set_len(valid_length as u64)?;
+            self.sync()?;
+        }
+        return Ok(entries);
+    }
+    ///
+    /// Discard all records. Only call this once the entries they describe are
+    /// durably written into the chunk store.
+    ///
+    pub fn truncate(&mut self) -> Result<(), io::Error> {
+        self.file.set_len(0)?;
+        return self.sync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::{temp_store_path, entry_at};
+
+    fn open_log(name: &str, policy: FsyncPolicy) -> WriteAheadLog {
+        let path: String = WriteAheadLog::path_for_store(temp_store_path(name).as_str());
+        return WriteAheadLog::open(path.as_str(), policy).unwrap();
+    }
+
+    #[test]
+    fn replays_only_records_after_sealed_sequence() {
+        let mut wal: WriteAheadLog = open_log("replays_only_records_after_sealed_sequence", FsyncPolicy::OS_MANAGED);
+        for timestamp in 0..3 {
+            wal.append(&entry_at(timestamp)).unwrap();
+        }
+        let path: String = wal.path.clone();
+        drop(wal);
+        let mut wal: WriteAheadLog = WriteAheadLog::open(path.as_str(), FsyncPolicy::OS_MANAGED).unwrap();
+        let replayed: Vec<ChunkEntry> = wal.replay(1).unwrap();
+        assert_eq!(replayed.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>(), vec![1, 2]);
+        // Numbering carries on from the newest record rather than restarting
+        assert_eq!(wal.last_sequence(), 3);
+        wal.truncate().unwrap();
+        assert!(wal.replay(7).unwrap().is_empty());
+        assert_eq!(wal.last_sequence(), 7);
+    }
+
+    #[test]
+    fn truncates_torn_tail() {
+        let mut wal: WriteAheadLog = open_log("truncates_torn_tail", FsyncPolicy::EVERY_ENTRY);
+        for timestamp in 0..3 {
+            wal.append(&entry_at(timestamp)).unwrap();
+        }
+        let record_length: u64 = WriteAheadLogRecord::from_entry(1, &entry_at(0)).into_bytes().len() as u64;
+        let path: String = wal.path.clone();
+        drop(wal);
+        // A crash part way through writing the third record
+        let file: File = OpenOptions::new().write(true).open(path.as_str()).unwrap();
+        file.set_len(3 * record_length - 5).unwrap();
+        drop(file);
+        let mut wal: WriteAheadLog = WriteAheadLog::open(path.as_str(), FsyncPolicy::EVERY_ENTRY).unwrap();
+        assert_eq!(wal.replay(0).unwrap().len(), 2);
+        assert_eq!(std::fs::metadata(path.as_str()).unwrap().len(), 2 * record_length);
+        // Appends after the cut follow the intact records
+        wal.append(&entry_at(3)).unwrap();
+        drop(wal);
+        let mut wal: WriteAheadLog = WriteAheadLog::open(path.as_str(), FsyncPolicy::EVERY_ENTRY).unwrap();
+        let replayed: Vec<ChunkEntry> = wal.replay(0).unwrap();
+        assert_eq!(replayed.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>(), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn discards_corrupt_record() {
+        let mut wal: WriteAheadLog = open_log("discards_corrupt_record", FsyncPolicy::EVERY_ENTRY);
+        wal.append(&entry_at(0)).unwrap();
+        wal.append(&entry_at(1)).unwrap();
+        let path: String = wal.path.clone();
+        drop(wal);
+        let mut bytes: Vec<u8> = std::fs::read(path.as_str()).unwrap();
+        let last: usize = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        std::fs::write(path.as_str(), bytes).unwrap();
+        let mut wal: WriteAheadLog = WriteAheadLog::open(path.as_str(), FsyncPolicy::EVERY_ENTRY).unwrap();
+        assert_eq!(wal.replay(0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn syncs_once_interval_elapses() {
+        let interval: Duration = Duration::from_millis(50);
+        let mut wal: WriteAheadLog = open_log("syncs_once_interval_elapses", FsyncPolicy::INTERVAL(interval));
+        wal.replay(0).unwrap();
+        wal.append(&entry_at(0)).unwrap();
+        assert!(wal.dirty);
+        wal.sync_if_due().unwrap();
+        assert!(wal.dirty);
+        std::thread::sleep(interval);
+        wal.sync_if_due().unwrap();
+        assert!(!wal.dirty);
+        // An append once the interval has passed syncs without waiting for a tick
+        std::thread::sleep(interval);
+        wal.append(&entry_at(1)).unwrap();
+        assert!(!wal.dirty);
+        wal.append(&entry_at(2)).unwrap();
+        assert!(wal.dirty);
+    }
+}
@@ -1,12 +1,63 @@
 use std::io::{Read, Write};
 use flate2::bufread::ZlibDecoder;
 use flate2::Compression;
-use flate2::write::ZlibEncoder;
+use flate2::write::{GzEncoder, ZlibEncoder};
 use flate2::read::GzDecoder;
 use crate::compression::exception::compressor_exceptions;
 
 type Byte = u8;
 
+///
+/// Compression scheme of a chunk's entries. The id is persisted in each chunk,
+/// so existing ids must never be renumbered.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Default)]
+pub enum CompressionCodec {
+    #[default]
+    ZLIB_DEFAULT,
+    ZLIB_FAST,
+    ZLIB_BEST,
+    GZIP_BEST,
+}
+
+
+impl CompressionCodec {
+    pub fn id(&self) -> u8 {
+        match self {
+            CompressionCodec::ZLIB_DEFAULT => 0,
+            CompressionCodec::ZLIB_FAST => 1,
+            CompressionCodec::ZLIB_BEST => 2,
+            CompressionCodec::GZIP_BEST => 3,
+        }
+    }
+    pub fn from_id(id: u8) -> Option<CompressionCodec> {
+        match id {
+            0 => Some(CompressionCodec::ZLIB_DEFAULT),
+            1 => Some(CompressionCodec::ZLIB_FAST),
+            2 => Some(CompressionCodec::ZLIB_BEST),
+            3 => Some(CompressionCodec::GZIP_BEST),
+            _ => None,
+        }
+    }
+    pub fn from_name(name: &str) -> Option<CompressionCodec> {
+        match name.trim().to_lowercase().as_str() {
+            "zlib" | "zlib_default" => Some(CompressionCodec::ZLIB_DEFAULT),
+            "zlib_fast" => Some(CompressionCodec::ZLIB_FAST),
+            "zlib_best" => Some(CompressionCodec::ZLIB_BEST),
+            "gzip_best" => Some(CompressionCodec::GZIP_BEST),
+            _ => None,
+        }
+    }
+    fn level(&self) -> Compression {
+        match self {
+            CompressionCodec::ZLIB_DEFAULT => Compression::default(),
+            CompressionCodec::ZLIB_FAST => Compression::fast(),
+            CompressionCodec::ZLIB_BEST | CompressionCodec::GZIP_BEST => Compression::best(),
+        }
+    }
+}
+
 pub enum CompressionAction {
     COMPRESS,
     DECOMPRESS,
@@ -15,6 +66,7 @@ pub enum CompressionAction {
 
 pub struct Compressor {
     pub action: CompressionAction,
+    pub codec: CompressionCodec,
 }
 
 pub trait CompressionHandler {
@@ -25,14 +77,17 @@ pub trait CompressionHandler {
 impl CompressionHandler for Compressor {
     fn compress_slice(&mut self, data: &[Byte]) -> Result<Vec<Byte>, compressor_exceptions::CompressionError> {
         self.action = CompressionAction::COMPRESS;
-        let mut encoder: ZlibEncoder<Vec<Byte>> = ZlibEncoder::new(Vec::new(), Compression::default());
-        match encoder.write_all(data) {
-            Err(e) => return Err(compressor_exceptions::CompressionError{
-                message: e.to_string()
-            }),
-            Ok(()) => (),
+        let result: std::io::Result<Vec<Byte>> = match self.codec {
+            CompressionCodec::GZIP_BEST => {
+                let mut encoder: GzEncoder<Vec<Byte>> = GzEncoder::new(Vec::new(), self.codec.level());
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            },
+            _ => {
+                let mut encoder: ZlibEncoder<Vec<Byte>> = ZlibEncoder::new(Vec::new(), self.codec.level());
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            },
         };
-        return match encoder.finish() {
+        return match result {
             Err(e) => Err(compressor_exceptions::CompressionError{
                 message: e.to_string()
             }),
@@ -44,9 +99,12 @@ impl CompressionHandler for Compressor {
     }
     fn decompress_slice(&mut self, data: &[Byte]) -> Result<Vec<Byte>, compressor_exceptions::DecompressionError> {
         self.action = CompressionAction::DECOMPRESS;
-        let mut decoder: ZlibDecoder<&[Byte]> = ZlibDecoder::new(data);
         let mut buffer: Vec<Byte> = Vec::new();
-        return match decoder.read_to_end(&mut buffer) {
+        let result: std::io::Result<usize> = match self.codec {
+            CompressionCodec::GZIP_BEST => GzDecoder::new(data).read_to_end(&mut buffer),
+            _ => ZlibDecoder::new(data).read_to_end(&mut buffer),
+        };
+        return match result {
             Err(e) => Err(compressor_exceptions::DecompressionError{
                 message: e.to_string()
             }),
@@ -58,10 +116,20 @@ impl CompressionHandler for Compressor {
     }
 }
 
+impl Default for Compressor {
+    fn default() -> Self {
+        Compressor::new()
+    }
+}
+
 impl Compressor {
     pub fn new() -> Compressor {
+        Compressor::with_codec(CompressionCodec::default())
+    }
+    pub fn with_codec(codec: CompressionCodec) -> Compressor {
         Compressor{
             action: CompressionAction::IDLE,
+            codec,
         }
     }
     pub fn compress_vec(&mut self, data: &Vec<Byte>) -> Result<Vec<Byte>, compressor_exceptions::CompressionError> {
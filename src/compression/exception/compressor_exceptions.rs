@@ -5,6 +5,15 @@ pub enum CompressorError {
     DecompressionError(DecompressionError)
 }
 
+impl fmt::Display for CompressorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressorError::CompressionError(e) => e.fmt(f),
+            CompressorError::DecompressionError(e) => e.fmt(f),
+        }
+    }
+}
+
 pub struct CompressionError {
     pub message: String
 }
@@ -6,6 +6,7 @@ use crate::try_except_return_default;
 
 use crate::configuration::exceptions;
 use std::path::Path;
+use std::str::FromStr;
 
 pub struct Config {
     pub filename: String,
@@ -21,7 +22,7 @@ impl Config {
     }
     pub fn read(&mut self) {
         let path: &Path = Path::new(self.filename.as_str());
-        let file: File = match File::open(&path) {
+        let file: File = match File::open(path) {
             Err(_) => panic!("{}", exceptions::FileError{filename: self.filename.clone()}),
             Ok(file) => file,
         };
@@ -35,16 +36,26 @@ impl Config {
 
     pub fn get(&mut self, key: String) -> Result<String, exceptions::ConfigPropertiesError> {
         if key.is_empty() {
-            return Err(exceptions::ConfigPropertiesError::InvalidConfigPropertyKeyError{
-                0:exceptions::InvalidConfigPropertyKeyError{key},
-            });
+            return Err(exceptions::ConfigPropertiesError::InvalidConfigPropertyKeyError(exceptions::InvalidConfigPropertyKeyError{key}));
         }
         let value: Option<&String> = self.properties.get(key.as_str());
         if value.is_none() {
-            return Err(exceptions::ConfigPropertiesError::MissingConfigPropertyError{
-                0:exceptions::MissingConfigPropertyError{property: key.clone()},
-            });
+            return Err(exceptions::ConfigPropertiesError::MissingConfigPropertyError(exceptions::MissingConfigPropertyError{property: key.clone()}));
         }
-        return Ok(String::from((*value.unwrap()).clone()));
+        return Ok((*value.unwrap()).clone());
+    }
+
+    pub fn get_or_default<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        let value: String = match self.get(String::from(key)) {
+            Ok(v) => v,
+            Err(_) => return default,
+        };
+        return match value.trim().parse::<T>() {
+            Ok(v) => v,
+            Err(_) => {
+                warn!(crate::LOGGER, "Invalid value for property {}, using default: {}", key, value);
+                default
+            },
+        };
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::ChunkEntry;
+use crate::data::representational::chunk_entry::ChunkEntry;
 use crate::encoding::errors::encoding_errors;
 use crate::encoding::transcoder::Transcoder;
 
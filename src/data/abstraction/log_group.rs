@@ -1,4 +1,4 @@
-use crate::Chunk;
+use crate::data::representational::chunk::Chunk;
 use crate::encoding::errors::encoding_errors;
 use crate::encoding::transcoder::Transcoder;
 
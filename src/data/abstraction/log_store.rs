@@ -1,4 +1,4 @@
-use crate::ChunkStore;
+use crate::data::representational::store::chunk_store::ChunkStore;
 use crate::encoding::errors::encoding_errors;
 use crate::encoding::transcoder::Transcoder;
 
@@ -1,4 +1,13 @@
+use flate2::Crc;
+use crate::compression::compressor::{CompressionCodec, Compressor};
+use crate::compression::exception::compressor_exceptions;
 use crate::data::abstraction::log_group::LogGroup;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_entry_index::ChunkEntryIndex;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::chunk_summary::ChunkSummary;
+use crate::data::representational::store::index::trigram::{self, Trigram};
 use crate::encoding::errors::encoding_errors;
 use crate::encoding::transcoder::Transcoder;
 use crate::{byte_layout, reify};
@@ -12,9 +21,16 @@ reify! {
         pub timestamp_from: u64,
         #[bytes_size=8]
         pub timestamp_to: u64,
+        #[bytes_size=1]
+        pub codec: u8,
+        #[bytes_size=4]
+        pub checksum: u32,
         #[bytes_size=4]
         pub entries_length: u32,
         pub entries: Vec<u8>,
+        #[bytes_size=2]
+        pub sections_length: u16,
+        pub sections: Vec<ChunkMetadataSection>,
     }
 }
 
@@ -23,11 +39,236 @@ byte_layout! {
     value [length, u32, Big]
     value [timestamp_from, u64, Big]
     value [timestamp_to, u64, Big]
+    value [codec, u8]
+    value [checksum, u32, Big]
     value [entries_length, u32, Big]
     bytes_vec [entries, entries_length]
+    value [sections_length, u16, Big]
+    composite_vec [sections, sections_length, ChunkMetadataSection]
 }
 
 impl Chunk {
+    // Fixed size prefix of a serialised chunk, ahead of its entries, which
+    // readers parse in place to skip chunks without reading them whole
+    pub const LENGTH_OFFSET: usize = 0;
+    pub const TIMESTAMP_FROM_OFFSET: usize = Self::LENGTH_OFFSET + 4;
+    pub const TIMESTAMP_TO_OFFSET: usize = Self::TIMESTAMP_FROM_OFFSET + 8;
+    pub const CODEC_OFFSET: usize = Self::TIMESTAMP_TO_OFFSET + 8;
+    pub const CHECKSUM_OFFSET: usize = Self::CODEC_OFFSET + 1;
+    pub const ENTRIES_LENGTH_OFFSET: usize = Self::CHECKSUM_OFFSET + 4;
+    pub const PREFIX_LENGTH: usize = Self::ENTRIES_LENGTH_OFFSET + 4;
+
+    ///
+    /// Compress a set of buffered entries into a sealed chunk with the default codec.
+    ///
+    pub fn seal(entries: &[ChunkEntry]) -> Result<Chunk, compressor_exceptions::CompressionError> {
+        return Self::seal_with(entries, CompressionCodec::default(), ChunkBloomFilter::DEFAULT_FALSE_POSITIVE_RATE);
+    }
+    ///
+    /// Compress a set of buffered entries into a sealed chunk. Entries are sorted
+    /// stably by timestamp, so entries sharing a timestamp keep the order they
+    /// were accepted in, and the chunk bounds are taken from the sorted entries.
+    ///
+    /// # Arguments
+    /// * entries: Entries of the open chunk in the order they were accepted
+    /// * codec: Codec to compress the entries with
+    /// * bloom_false_positive_rate: False positive rate the target and trigram bloom filters are sized for
+    ///
+    /// # Returns
+    /// `Result<Chunk>`: Sealed chunk with length and timestamp bounds populated
+    ///
+    pub fn seal_with(entries: &[ChunkEntry], codec: CompressionCodec, bloom_false_positive_rate: f64) -> Result<Chunk, compressor_exceptions::CompressionError> {
+        let mut chunk: Chunk = Chunk::default();
+        let mut sorted_entries: Vec<ChunkEntry> = entries.to_vec();
+        sorted_entries.sort_by_key(|e: &ChunkEntry| e.timestamp);
+        let mut raw_entries: Vec<u8> = Vec::new();
+        for entry in sorted_entries.iter() {
+            raw_entries.append(&mut entry.into_bytes());
+        }
+        if let (Some(first), Some(last)) = (sorted_entries.first(), sorted_entries.last()) {
+            chunk.timestamp_from = first.timestamp;
+            chunk.timestamp_to = last.timestamp;
+        }
+        chunk.set_entries(&raw_entries, codec)?;
+        chunk.build_sections(sorted_entries.as_slice(), bloom_false_positive_rate);
+        return Ok(chunk);
+    }
+    ///
+    /// Replace the metadata sections with ones derived from the given entries,
+    /// which must be this chunk's entries.
+    ///
+    fn build_sections(&mut self, entries: &[ChunkEntry], bloom_false_positive_rate: f64) {
+        self.sections = vec![
+            ChunkMetadataSection::new(ChunkMetadataSection::ENTRY_INDEX, ChunkEntryIndex::build(entries).into_bytes()),
+            ChunkMetadataSection::new(
+                ChunkMetadataSection::BLOOM_FILTER,
+                ChunkBloomFilter::build(entries, bloom_false_positive_rate).into_bytes(),
+            ),
+            ChunkMetadataSection::new(
+                ChunkMetadataSection::TRIGRAM_FILTER,
+                ChunkBloomFilter::from_items(
+                    trigram::entry_trigrams(entries).into_iter().collect::<Vec<Trigram>>().as_slice(),
+                    bloom_false_positive_rate,
+                ).into_bytes(),
+            ),
+            ChunkMetadataSection::new(ChunkMetadataSection::SUMMARY, ChunkSummary::build(entries).into_bytes()),
+        ];
+        self.sections_length = self.sections.len() as u16;
+        self.length = self.into_bytes().len() as u32;
+    }
+    ///
+    /// Regenerate the metadata sections from the chunk's entries, for chunks
+    /// written before a section existed.
+    ///
+    pub fn rebuild_sections(&mut self, bloom_false_positive_rate: f64) -> Result<(), compressor_exceptions::DecompressionError> {
+        let entries: Vec<ChunkEntry> = self.decode_entries()?;
+        self.build_sections(entries.as_slice(), bloom_false_positive_rate);
+        return Ok(());
+    }
+    pub fn section(&self, kind: u8) -> Option<&ChunkMetadataSection> {
+        self.sections.iter().find(|s: &&ChunkMetadataSection| s.kind == kind && s.is_valid())
+    }
+    pub fn entry_index(&self) -> Option<ChunkEntryIndex> {
+        return ChunkEntryIndex::from_section(self.section(ChunkMetadataSection::ENTRY_INDEX)?);
+    }
+    pub fn summary(&self) -> Option<ChunkSummary> {
+        return ChunkSummary::from_section(self.section(ChunkMetadataSection::SUMMARY)?);
+    }
+    pub fn trigram_filter(&self) -> Option<ChunkBloomFilter> {
+        return ChunkBloomFilter::from_section(self.section(ChunkMetadataSection::TRIGRAM_FILTER)?);
+    }
+    pub fn bloom_filter(&self) -> Option<ChunkBloomFilter> {
+        return ChunkBloomFilter::from_section(self.section(ChunkMetadataSection::BLOOM_FILTER)?);
+    }
+    ///
+    /// Whether the chunk may hold entries for the given target, answered from the
+    /// bloom filter without decompressing the entries. Chunks without a filter
+    /// always may.
+    ///
+    pub fn may_contain_target(&self, target: &[u8]) -> bool {
+        self.bloom_filter().is_none_or(|f: ChunkBloomFilter| f.might_contain(target))
+    }
+    ///
+    /// Compress serialised entries into this chunk with the given codec, updating
+    /// the codec id, checksum and lengths to match. The checksum covers only the
+    /// compressed entries; metadata sections carry their own.
+    ///
+    fn set_entries(&mut self, raw_entries: &Vec<u8>, codec: CompressionCodec) -> Result<(), compressor_exceptions::CompressionError> {
+        let mut compressor: Compressor = Compressor::with_codec(codec);
+        self.entries = compressor.compress_vec(raw_entries)?;
+        self.entries_length = match u32::try_from(self.entries.len()) {
+            Ok(v) => v,
+            Err(_) => return Err(compressor_exceptions::CompressionError{
+                message: format!("Compressed entries exceed chunk capacity: {} bytes", self.entries.len()),
+            }),
+        };
+        self.codec = codec.id();
+        self.checksum = Self::checksum_of(&self.entries);
+        self.length = self.into_bytes().len() as u32;
+        return Ok(());
+    }
+    pub fn checksum_of(bytes: &[u8]) -> u32 {
+        let mut crc: Crc = Crc::new();
+        crc.update(bytes);
+        return crc.sum();
+    }
+    pub fn compression_codec(&self) -> Option<CompressionCodec> {
+        CompressionCodec::from_id(self.codec)
+    }
+    ///
+    /// Decompress the entries, verifying them against the stored checksum first.
+    ///
+    pub fn decode_raw_entries(&self) -> Result<Vec<u8>, compressor_exceptions::DecompressionError> {
+        if Self::checksum_of(&self.entries) != self.checksum {
+            return Err(compressor_exceptions::DecompressionError{
+                message: format!("Chunk checksum mismatch, expected {:08X}", self.checksum),
+            });
+        }
+        let codec: CompressionCodec = match self.compression_codec() {
+            Some(c) => c,
+            None => return Err(compressor_exceptions::DecompressionError{
+                message: format!("Unknown chunk codec id {}", self.codec),
+            }),
+        };
+        let mut compressor: Compressor = Compressor::with_codec(codec);
+        return compressor.decompress_vec(&self.entries);
+    }
+    pub fn decode_entries(&self) -> Result<Vec<ChunkEntry>, compressor_exceptions::DecompressionError> {
+        return self.decode_entries_from(0, usize::MAX);
+    }
+    ///
+    /// Decode up to `limit` entries starting at the given position in the chunk.
+    /// With an entry index parsing starts directly at the entry, otherwise the
+    /// preceding entries are parsed and skipped.
+    ///
+    /// # Arguments
+    /// * position: Index of the first entry to decode, in chunk order
+    /// * limit: Maximum number of entries to decode
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkEntry>>`: Decoded entries, empty if `position` is past the end
+    ///
+    pub fn decode_entries_from(&self, position: usize, limit: usize) -> Result<Vec<ChunkEntry>, compressor_exceptions::DecompressionError> {
+        let raw_entries: Vec<u8> = self.decode_raw_entries()?;
+        let (start_offset, mut skip): (usize, usize) = match self.entry_index() {
+            Some(index) => match index.offset_of(position) {
+                Some(offset) => (offset, 0),
+                None => return Ok(Vec::new()),
+            },
+            None => (0, position),
+        };
+        let mut entries: Vec<ChunkEntry> = Vec::new();
+        let mut tail: &[u8] = match raw_entries.get(start_offset..) {
+            Some(t) => t,
+            None => return Err(compressor_exceptions::DecompressionError{
+                message: format!("Entry offset {} lies outside the chunk", start_offset),
+            }),
+        };
+        while !tail.is_empty() && entries.len() < limit {
+            let mut entry: ChunkEntry = ChunkEntry::default();
+            tail = match entry.parse_bytes::<&'_ [u8], nom::error::Error<_>>(tail) {
+                Ok(t) => t,
+                Err(e) => return Err(compressor_exceptions::DecompressionError{
+                    message: e.to_string(),
+                }),
+            };
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            entries.push(entry);
+        }
+        return Ok(entries);
+    }
+    ///
+    /// Position of the first entry, in chunk order, with a timestamp at or after
+    /// the given one. Answered from the entry index without decompressing when
+    /// the chunk has one.
+    ///
+    pub fn position_at_or_after(&self, timestamp: u64) -> Result<Option<usize>, compressor_exceptions::DecompressionError> {
+        if let Some(index) = self.entry_index() {
+            return Ok(index.first_at_or_after(timestamp));
+        }
+        return Ok(self.decode_entries()?.iter().position(|e: &ChunkEntry| e.timestamp >= timestamp));
+    }
+    ///
+    /// Re-encode the chunk's entries with another codec. Timestamps and entries
+    /// are unchanged, only the encoded form and its checksum differ.
+    ///
+    /// # Arguments
+    /// * codec: Codec to re-encode with
+    ///
+    /// # Returns
+    /// `Result<Chunk>`: Chunk holding the same entries encoded with `codec`
+    ///
+    pub fn recompress(&self, codec: CompressionCodec) -> Result<Chunk, compressor_exceptions::CompressorError> {
+        let raw_entries: Vec<u8> = self.decode_raw_entries()
+            .map_err(compressor_exceptions::CompressorError::DecompressionError)?;
+        let mut chunk: Chunk = self.clone();
+        chunk.set_entries(&raw_entries, codec)
+            .map_err(compressor_exceptions::CompressorError::CompressionError)?;
+        return Ok(chunk);
+    }
     pub fn header_length() -> Result<u32, std::io::Error> {
         let attribute: Option<String> = match Self::get_field_attribute("length") {
             Ok(v) => v,
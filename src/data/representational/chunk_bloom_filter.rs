@@ -0,0 +1,128 @@
+use crate::{byte_layout, reify};
+use crate::configuration::config::Config;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::utils::hash_utils::{fnv1a, FNV_OFFSET_BASIS};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkBloomFilter {
+        #[bytes_size=1]
+        pub hash_count: u8,
+        #[bytes_size=4]
+        pub bits_length: u32,
+        pub bits: Vec<u8>,
+    }
+}
+
+byte_layout!{
+    ChunkBloomFilter
+    value [hash_count, u8]
+    value [bits_length, u32, Big]
+    bytes_vec [bits, bits_length]
+}
+
+///
+/// Bloom filter over a set of byte strings drawn from a chunk's entries, such
+/// as their targets or message trigrams, so a query can rule out a chunk from
+/// its uncompressed metadata alone. Bit positions are derived by double
+/// hashing with FNV-1a.
+///
+impl ChunkBloomFilter {
+    pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+    const MAX_HASH_COUNT: u8 = 16;
+    // Rotated offset basis, giving a second independent hash for double hashing
+    const FNV_SECOND_BASIS: u64 = 0x84222325CBF29CE4;
+
+    ///
+    /// False positive rate chunk filters are sized for, read from
+    /// `store.chunk.bloom_false_positive_rate` by every config sealing chunks.
+    ///
+    pub fn false_positive_rate_from_config(config: &mut Config) -> f64 {
+        let rate: f64 = config.get_or_default("store.chunk.bloom_false_positive_rate", Self::DEFAULT_FALSE_POSITIVE_RATE);
+        if rate > 0.0 && rate < 1.0 {
+            return rate;
+        }
+        warn!(crate::LOGGER, "Bloom false positive rate must be between 0 and 1, defaulting to {}", Self::DEFAULT_FALSE_POSITIVE_RATE);
+        return Self::DEFAULT_FALSE_POSITIVE_RATE;
+    }
+
+    ///
+    /// Build a filter sized for the distinct targets of the given entries.
+    ///
+    /// # Arguments
+    /// * entries: Entries of the chunk the filter describes
+    /// * false_positive_rate: Probability that a target absent from the entries is reported as present
+    ///
+    /// # Returns
+    /// `ChunkBloomFilter`: Filter containing every target of the entries
+    ///
+    pub fn build(entries: &[ChunkEntry], false_positive_rate: f64) -> ChunkBloomFilter {
+        let mut targets: Vec<&[u8]> = entries.iter()
+            .map(|e: &ChunkEntry| e.target.as_slice())
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        return Self::from_items(targets.as_slice(), false_positive_rate);
+    }
+    ///
+    /// Build a filter sized for and containing the given distinct items.
+    ///
+    pub fn from_items<T: AsRef<[u8]>>(items: &[T], false_positive_rate: f64) -> ChunkBloomFilter {
+        let mut filter: ChunkBloomFilter = ChunkBloomFilter::with_capacity(items.len(), false_positive_rate);
+        for item in items.iter() {
+            filter.insert(item.as_ref());
+        }
+        return filter;
+    }
+    ///
+    /// Empty filter with the optimal number of bits and hashes for the expected
+    /// number of distinct items and the requested false positive rate.
+    ///
+    pub fn with_capacity(expected_items: usize, false_positive_rate: f64) -> ChunkBloomFilter {
+        let items: f64 = expected_items.max(1) as f64;
+        let rate: f64 = if false_positive_rate > 0.0 && false_positive_rate < 1.0 {
+            false_positive_rate
+        } else {
+            Self::DEFAULT_FALSE_POSITIVE_RATE
+        };
+        let ln2: f64 = std::f64::consts::LN_2;
+        let bit_count: f64 = (-items * rate.ln() / (ln2 * ln2)).ceil().max(8.0);
+        let hash_count: f64 = (bit_count / items * ln2).round();
+        let bytes_length: usize = (bit_count as usize).div_ceil(8);
+        ChunkBloomFilter {
+            hash_count: (hash_count as u8).clamp(1, Self::MAX_HASH_COUNT),
+            bits_length: bytes_length as u32,
+            bits: vec![0x00u8; bytes_length],
+        }
+    }
+    pub fn from_section(section: &ChunkMetadataSection) -> Option<ChunkBloomFilter> {
+        if !section.is_valid() {
+            return None;
+        }
+        let mut filter: ChunkBloomFilter = ChunkBloomFilter::default();
+        return match filter.parse_bytes::<&'_ [u8], nom::error::Error<_>>(section.data.as_slice()) {
+            Ok(_) if !filter.bits.is_empty() && filter.hash_count > 0 => Some(filter),
+            _ => None,
+        };
+    }
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in self.bit_positions(item) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    ///
+    /// Whether the item may have been inserted. `false` is definitive, `true`
+    /// is wrong at roughly the rate the filter was sized for.
+    ///
+    pub fn might_contain(&self, item: &[u8]) -> bool {
+        self.bit_positions(item).all(|bit: usize| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+    fn bit_positions(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let bit_count: u64 = (self.bits.len() * 8) as u64;
+        let first: u64 = fnv1a(FNV_OFFSET_BASIS, item);
+        let second: u64 = fnv1a(Self::FNV_SECOND_BASIS, item) | 1;
+        return (0..self.hash_count as u64)
+            .map(move |i: u64| (first.wrapping_add(i.wrapping_mul(second)) % bit_count) as usize);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{byte_layout, reify};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkEntryIndexEntry {
+        #[bytes_size=8]
+        pub timestamp: u64,
+        #[bytes_size=4]
+        pub offset: u32,
+    }
+}
+
+byte_layout!{
+    ChunkEntryIndexEntry
+    value [timestamp, u64, Big]
+    value [offset, u32, Big]
+}
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkEntryIndex {
+        #[bytes_size=4]
+        pub entries_length: u32,
+        pub entries: Vec<ChunkEntryIndexEntry>,
+    }
+}
+
+byte_layout!{
+    ChunkEntryIndex
+    value [entries_length, u32, Big]
+    composite_vec [entries, entries_length, ChunkEntryIndexEntry]
+}
+
+///
+/// Timestamp and byte offset of every entry within a chunk's decompressed
+/// entries, so a reader can start parsing at any entry instead of scanning
+/// each null terminated field from the beginning.
+///
+impl ChunkEntryIndex {
+    pub fn build(entries: &[ChunkEntry]) -> ChunkEntryIndex {
+        let mut index: ChunkEntryIndex = ChunkEntryIndex::default();
+        let mut offset: u32 = 0;
+        for entry in entries.iter() {
+            index.entries.push(ChunkEntryIndexEntry {
+                timestamp: entry.timestamp,
+                offset,
+            });
+            offset += entry.into_bytes().len() as u32;
+        }
+        index.entries_length = index.entries.len() as u32;
+        return index;
+    }
+    pub fn from_section(section: &ChunkMetadataSection) -> Option<ChunkEntryIndex> {
+        if section.kind != ChunkMetadataSection::ENTRY_INDEX || !section.is_valid() {
+            return None;
+        }
+        let mut index: ChunkEntryIndex = ChunkEntryIndex::default();
+        return match index.parse_bytes::<&'_ [u8], nom::error::Error<_>>(section.data.as_slice()) {
+            Ok(_) => Some(index),
+            Err(_) => None,
+        };
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn offset_of(&self, position: usize) -> Option<usize> {
+        self.entries.get(position).map(|e: &ChunkEntryIndexEntry| e.offset as usize)
+    }
+    ///
+    /// Position of the first entry with a timestamp at or after the given one.
+    /// Entries are sorted by timestamp when the chunk is sealed, so this is a
+    /// binary search.
+    ///
+    pub fn first_at_or_after(&self, timestamp: u64) -> Option<usize> {
+        let position: usize = self.entries.partition_point(|e: &ChunkEntryIndexEntry| e.timestamp < timestamp);
+        if position == self.entries.len() {
+            return None;
+        }
+        return Some(position);
+    }
+}
@@ -0,0 +1,52 @@
+use flate2::Crc;
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkMetadataSection {
+        #[bytes_size=1]
+        pub kind: u8,
+        #[bytes_size=4]
+        pub checksum: u32,
+        #[bytes_size=4]
+        pub length: u32,
+        pub data: Vec<u8>,
+    }
+}
+
+byte_layout!{
+    ChunkMetadataSection
+    value [kind, u8]
+    value [checksum, u32, Big]
+    value [length, u32, Big]
+    bytes_vec [data, length]
+}
+
+///
+/// Tagged block of derived data stored after a chunk's entries, such as an
+/// index over them. Readers skip kinds they do not recognise, so new kinds can
+/// be added without changing the chunk layout. Kind ids are persisted and must
+/// never be renumbered.
+///
+impl ChunkMetadataSection {
+    pub const ENTRY_INDEX: u8 = 1;
+    pub const BLOOM_FILTER: u8 = 2;
+    pub const TRIGRAM_FILTER: u8 = 3;
+    pub const SUMMARY: u8 = 4;
+
+    pub fn new(kind: u8, data: Vec<u8>) -> ChunkMetadataSection {
+        let mut crc: Crc = Crc::new();
+        crc.update(data.as_slice());
+        ChunkMetadataSection {
+            kind,
+            checksum: crc.sum(),
+            length: data.len() as u32,
+            data,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        let mut crc: Crc = Crc::new();
+        crc.update(self.data.as_slice());
+        self.data.len() == self.length as usize && crc.sum() == self.checksum
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use crate::{byte_layout, reify};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::hyper_log_log::HyperLogLog;
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkActionCount {
+        #[bytes_size=1]
+        pub action: u8,
+        #[bytes_size=4]
+        pub count: u32,
+    }
+}
+
+byte_layout!{
+    ChunkActionCount
+    value [action, u8]
+    value [count, u32, Big]
+}
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkSummary {
+        #[bytes_size=4]
+        pub entry_count: u32,
+        #[bytes_size=8]
+        pub timestamp_from: u64,
+        #[bytes_size=8]
+        pub timestamp_to: u64,
+        #[bytes_size=4]
+        pub uncompressed_length: u32,
+        #[bytes_size=2]
+        pub actions_length: u16,
+        pub actions: Vec<ChunkActionCount>,
+        #[bytes_size=4]
+        pub target_registers_length: u32,
+        pub target_registers: Vec<u8>,
+    }
+}
+
+byte_layout!{
+    ChunkSummary
+    value [entry_count, u32, Big]
+    value [timestamp_from, u64, Big]
+    value [timestamp_to, u64, Big]
+    value [uncompressed_length, u32, Big]
+    value [actions_length, u16, Big]
+    composite_vec [actions, actions_length, ChunkActionCount]
+    value [target_registers_length, u32, Big]
+    bytes_vec [target_registers, target_registers_length]
+}
+
+///
+/// Statistics over a chunk's entries, kept uncompressed with the chunk so that
+/// counts, time bounds and sizes can be answered without decompressing it.
+/// Actions are listed in ascending order, each with the number of entries
+/// carrying it, and distinct targets are counted approximately by a
+/// HyperLogLog sketch.
+///
+impl ChunkSummary {
+    pub fn build(entries: &[ChunkEntry]) -> ChunkSummary {
+        let mut actions: BTreeMap<u8, u32> = BTreeMap::new();
+        let mut targets: HyperLogLog = HyperLogLog::new(HyperLogLog::DEFAULT_PRECISION);
+        let mut uncompressed_length: u32 = 0;
+        for entry in entries.iter() {
+            *actions.entry(entry.action).or_insert(0) += 1;
+            targets.insert(entry.target.as_slice());
+            uncompressed_length += entry.into_bytes().len() as u32;
+        }
+        let mut summary: ChunkSummary = ChunkSummary {
+            entry_count: entries.len() as u32,
+            timestamp_from: entries.iter().map(|e: &ChunkEntry| e.timestamp).min().unwrap_or(0),
+            timestamp_to: entries.iter().map(|e: &ChunkEntry| e.timestamp).max().unwrap_or(0),
+            uncompressed_length,
+            actions: actions.into_iter()
+                .map(|(action, count): (u8, u32)| ChunkActionCount { action, count })
+                .collect(),
+            target_registers: targets.registers().to_vec(),
+            ..ChunkSummary::default()
+        };
+        summary.actions_length = summary.actions.len() as u16;
+        summary.target_registers_length = summary.target_registers.len() as u32;
+        return summary;
+    }
+    pub fn from_section(section: &ChunkMetadataSection) -> Option<ChunkSummary> {
+        if section.kind != ChunkMetadataSection::SUMMARY || !section.is_valid() {
+            return None;
+        }
+        let mut summary: ChunkSummary = ChunkSummary::default();
+        return match summary.parse_bytes::<&'_ [u8], nom::error::Error<_>>(section.data.as_slice()) {
+            Ok(_) => Some(summary),
+            Err(_) => None,
+        };
+    }
+    pub fn action_count(&self, action: u8) -> u64 {
+        return self.actions.iter()
+            .find(|a: &&ChunkActionCount| a.action == action)
+            .map_or(0, |a: &ChunkActionCount| a.count as u64);
+    }
+    pub fn target_sketch(&self) -> Option<HyperLogLog> {
+        return HyperLogLog::from_registers(self.target_registers.clone());
+    }
+    ///
+    /// Approximate number of distinct targets, `None` if the sketch is unreadable.
+    ///
+    pub fn distinct_targets(&self) -> Option<u64> {
+        return self.target_sketch().map(|s: HyperLogLog| s.estimate());
+    }
+}
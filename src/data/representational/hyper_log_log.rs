@@ -0,0 +1,98 @@
+use crate::utils::hash_utils::{fnv1a, FNV_OFFSET_BASIS};
+
+///
+/// HyperLogLog sketch estimating how many distinct byte strings were added to
+/// it from a fixed number of registers, so a chunk can record roughly how many
+/// targets it holds in a few hundred bytes. Sketches of equal precision merge
+/// into the sketch of the union. Items are hashed with FNV-1a and a 64-bit
+/// finaliser.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub const DEFAULT_PRECISION: u8 = 8;
+    const MIN_PRECISION: u8 = 4;
+    const MAX_PRECISION: u8 = 16;
+
+    ///
+    /// Empty sketch with `2^precision` registers. Each extra bit of precision
+    /// doubles the size and cuts the typical error by a factor of `sqrt(2)`,
+    /// from about 6.5% at the default of 8.
+    ///
+    pub fn new(precision: u8) -> HyperLogLog {
+        let precision: u8 = precision.clamp(Self::MIN_PRECISION, Self::MAX_PRECISION);
+        return HyperLogLog {
+            registers: vec![0x00u8; 1 << precision],
+        };
+    }
+    pub fn from_registers(registers: Vec<u8>) -> Option<HyperLogLog> {
+        let length: usize = registers.len();
+        if !length.is_power_of_two()
+            || !((1 << Self::MIN_PRECISION)..=(1 << Self::MAX_PRECISION)).contains(&length) {
+            return None;
+        }
+        return Some(HyperLogLog { registers });
+    }
+    pub fn registers(&self) -> &[u8] {
+        self.registers.as_slice()
+    }
+    pub fn insert(&mut self, item: &[u8]) {
+        let precision: u32 = self.registers.len().trailing_zeros();
+        let hash: u64 = Self::hash(item);
+        let register: usize = (hash >> (64 - precision)) as usize;
+        // The marker bit caps the rank when every remaining hash bit is zero
+        let rank: u8 = ((hash << precision) | (1 << (precision - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+    ///
+    /// Fold another sketch into this one.
+    ///
+    /// # Returns
+    /// `bool`: `false`, leaving this sketch unchanged, if the precisions differ
+    ///
+    pub fn merge(&mut self, other: &HyperLogLog) -> bool {
+        if self.registers.len() != other.registers.len() {
+            return false;
+        }
+        for (register, other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(*other_register);
+        }
+        return true;
+    }
+    pub fn estimate(&self) -> u64 {
+        let m: f64 = self.registers.len() as f64;
+        let alpha: f64 = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter()
+            .map(|r: &u8| 2.0f64.powi(-(*r as i32)))
+            .sum();
+        let raw: f64 = alpha * m * m / sum;
+        let empty: usize = self.registers.iter().filter(|r: &&u8| **r == 0).count();
+        // Linear counting is more accurate while many registers are still empty
+        let estimate: f64 = if raw <= 2.5 * m && empty > 0 {
+            m * (m / empty as f64).ln()
+        } else {
+            raw
+        };
+        return estimate.round() as u64;
+    }
+    fn hash(item: &[u8]) -> u64 {
+        let mut hash: u64 = fnv1a(FNV_OFFSET_BASIS, item);
+        // FNV-1a leaves the high bits poorly mixed for short inputs
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xFF51AFD7ED558CCD);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xC4CEB9FE1A85EC53);
+        hash ^= hash >> 33;
+        return hash;
+    }
+}
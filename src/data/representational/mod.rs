@@ -1,3 +1,8 @@
 pub mod chunk;
+pub mod chunk_bloom_filter;
 pub mod chunk_entry;
-pub mod store;
\ No newline at end of file
+pub mod chunk_entry_index;
+pub mod chunk_metadata_section;
+pub mod chunk_summary;
+pub mod hyper_log_log;
+pub mod store;
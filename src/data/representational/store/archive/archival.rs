@@ -0,0 +1,325 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::Duration;
+use crate::compression::compressor::CompressionCodec;
+use crate::configuration::config::Config;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::store::chunk_offsets::ChunkOffsets;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::data::representational::store::store_write_lock::StoreWriteLock;
+use super::archive_reference::ArchiveReference;
+use super::archive_store::ArchiveStore;
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// Chunks whose newest entry is older than this are moved to an archive
+    pub min_age: Duration,
+    /// Codec archived chunks are recompressed with
+    pub codec: CompressionCodec,
+    /// How often the writer's maintenance archives aged chunks
+    pub interval: Duration,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        ArchiveConfig {
+            min_age: Duration::from_millis(7 * 24 * 60 * 60 * 1000),
+            codec: CompressionCodec::ZLIB_BEST,
+            interval: Duration::from_millis(60 * 60 * 1000),
+        }
+    }
+}
+
+impl ArchiveConfig {
+    pub fn from_config(config: &mut Config) -> ArchiveConfig {
+        let defaults: ArchiveConfig = ArchiveConfig::default();
+        let codec: CompressionCodec = match CompressionCodec::from_name(
+            config.get_or_default("store.archive.codec", String::from("zlib_best")).as_str(),
+        ) {
+            Some(c) => c,
+            None => {
+                warn!(crate::LOGGER, "Unknown archive codec, defaulting to zlib_best");
+                defaults.codec
+            },
+        };
+        ArchiveConfig {
+            min_age: Duration::from_millis(config.get_or_default(
+                "store.archive.min_age_ms",
+                defaults.min_age.as_millis() as u64,
+            )),
+            codec,
+            interval: Duration::from_millis(config.get_or_default(
+                "store.archive.interval_ms",
+                defaults.interval.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ArchiveSummary {
+    pub chunks_archived: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub archive_path: Option<String>,
+}
+
+///
+/// Archive written from aged chunks, waiting to replace them in the store.
+/// Until it is applied nothing references the archive file, so it is deleted
+/// if it cannot be applied.
+///
+#[derive(Debug, Clone)]
+pub struct PlannedArchive {
+    /// Where the archived chunks sit, identifying them however the chunk list has changed since
+    pub originals: Vec<ChunkOffsets>,
+    pub original_length: u64,
+    pub reference: ArchiveReference,
+    pub archive_path: String,
+    pub archive_length: u64,
+}
+
+impl PlannedArchive {
+    fn discard(&self) {
+        if let Err(e) = std::fs::remove_file(self.archive_path.as_str()) {
+            warn!(crate::LOGGER, "Unable to delete unapplied archive {}: {}", self.archive_path, e);
+        }
+    }
+}
+
+impl ChunkStore {
+    ///
+    /// Resolve an archive referenced by this store's header to its file path.
+    ///
+    pub fn archive_path(&self, reference: &ArchiveReference) -> String {
+        let directory: &Path = Path::new(self.path.as_str()).parent().unwrap_or(Path::new(""));
+        return directory.join(reference.file_name_string()).to_string_lossy().into_owned();
+    }
+    ///
+    /// Move chunks older than the configured age into a new sealed archive file
+    /// next to the store. The archive is written completely before a single
+    /// header commit both removes the chunks from the store and records the
+    /// archive, so a chunk is never lost or visible in both tiers. The latest
+    /// chunk is left alone as a writer may be extending it.
+    ///
+    /// # Arguments
+    /// * config: Age threshold and archive codec
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<ArchiveSummary>`: Chunks archived and their size before and after
+    ///
+    pub fn archive_aged(&mut self, config: &ArchiveConfig, now: u64) -> Result<ArchiveSummary, Error> {
+        return match self.plan_archival(config, now)? {
+            Some(planned) => self.apply_archival(planned),
+            None => Ok(ArchiveSummary::default()),
+        };
+    }
+    ///
+    /// Write the chunks due for archival into a new archive file without
+    /// changing the store. Chunks are picked from the bounds in their fixed size
+    /// prefix, so only the chunks archived are read whole. Like
+    /// `plan_recompression` this takes no lock, and the instance's lease keeps
+    /// the originals in place until the archive is applied.
+    ///
+    /// # Arguments
+    /// * config: Age threshold and archive codec
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<Option<PlannedArchive>>`: Archive for `apply_archival`, or `None` if no chunk is due
+    ///
+    pub fn plan_archival(&self, config: &ArchiveConfig, now: u64) -> Result<Option<PlannedArchive>, Error> {
+        let chunk_count: usize = self.header.chunk_offsets.len();
+        if chunk_count < 2 {
+            return Ok(None);
+        }
+        let mut file: File = File::open(self.path.as_str())?;
+        let cutoff: u64 = now.saturating_sub(config.min_age.as_millis() as u64);
+        let mut originals: Vec<ChunkOffsets> = Vec::new();
+        let mut original_length: u64 = 0;
+        let mut chunks: Vec<Chunk> = Vec::new();
+        for index in 0..(chunk_count - 1) {
+            if self.read_chunk_bounds(&mut file, index)?.1 >= cutoff {
+                continue;
+            }
+            let chunk: Chunk = self.read_chunk_from(&mut file, index)?;
+            original_length += chunk.length as u64;
+            originals.push(self.header.chunk_offsets[index].clone());
+            chunks.push(if chunk.codec == config.codec.id() {
+                chunk
+            } else {
+                match chunk.recompress(config.codec) {
+                    Ok(c) => c,
+                    Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+                }
+            });
+        }
+        if chunks.is_empty() {
+            return Ok(None);
+        }
+        let store_file_name: String = Path::new(self.path.as_str())
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Named uniquely, as the generation it will be committed at is not yet known
+        let reference: ArchiveReference = ArchiveReference {
+            timestamp_from: chunks.iter().map(|c: &Chunk| c.timestamp_from).min().unwrap_or_default(),
+            timestamp_to: chunks.iter().map(|c: &Chunk| c.timestamp_to).max().unwrap_or_default(),
+            chunk_count: chunks.len() as u64,
+            file_name: format!("{}.{}.archive", store_file_name, uuid::Uuid::new_v4()).into_bytes(),
+        };
+        let archive_path: String = self.archive_path(&reference);
+        ArchiveStore::write(archive_path.as_str(), chunks.as_slice())?;
+        return Ok(Some(PlannedArchive {
+            originals,
+            original_length,
+            reference,
+            archive_length: std::fs::metadata(archive_path.as_str())?.len(),
+            archive_path,
+        }));
+    }
+    ///
+    /// Swap a planned archive in for the chunks it holds in a single header
+    /// commit. If any of them is no longer in the store, or is now its latest
+    /// chunk, the archive is discarded instead.
+    ///
+    /// # Arguments
+    /// * planned: Archive written by `plan_archival`
+    ///
+    /// # Returns
+    /// `Result<ArchiveSummary>`: Chunks archived and their size before and after
+    ///
+    pub fn apply_archival(&mut self, planned: PlannedArchive) -> Result<ArchiveSummary, Error> {
+        let summary: Result<ArchiveSummary, Error> = self.commit_archival(&planned);
+        if !summary.as_ref().is_ok_and(|s: &ArchiveSummary| s.chunks_archived > 0) {
+            planned.discard();
+        }
+        return summary;
+    }
+    fn commit_archival(&mut self, planned: &PlannedArchive) -> Result<ArchiveSummary, Error> {
+        let mut summary: ArchiveSummary = ArchiveSummary::default();
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        let latest: usize = self.header.chunk_offsets.len().saturating_sub(1);
+        let mut archived: Vec<usize> = Vec::with_capacity(planned.originals.len());
+        for original in planned.originals.iter() {
+            match self.header.chunk_offsets.iter().position(|o: &ChunkOffsets| o == original) {
+                Some(i) if i < latest => archived.push(i),
+                _ => {
+                    info!(crate::LOGGER, "Discarding archive {}, its chunks changed since it was written", planned.archive_path);
+                    return Ok(summary);
+                },
+            }
+        }
+        for index in archived.iter() {
+            self.retire_chunk(&mut file, *index)?;
+        }
+        let chunk_offsets: Vec<ChunkOffsets> = self.header.chunk_offsets.iter()
+            .enumerate()
+            .filter(|(i, _)| !archived.contains(i))
+            .map(|(_, o): (usize, &ChunkOffsets)| o.clone())
+            .collect();
+        self.header.chunk_offsets = chunk_offsets;
+        self.header.chunk_offsets_length = self.header.chunk_offsets.len() as u64;
+        self.header.chunk_count = self.header.chunk_offsets_length;
+        self.header.archives.push(planned.reference.clone());
+        self.header.archives_length = self.header.archives.len() as u64;
+        self.header.renumber_chunks();
+        self.commit_header(&mut file)?;
+        summary.chunks_archived = archived.len();
+        summary.bytes_before = planned.original_length;
+        summary.bytes_after = planned.archive_length;
+        summary.archive_path = Some(planned.archive_path.clone());
+        info!(
+            crate::LOGGER,
+            "Archived {} chunks of {} to {} ({}B -> {}B)",
+            summary.chunks_archived,
+            self.path,
+            planned.archive_path,
+            summary.bytes_before,
+            summary.bytes_after
+        );
+        return Ok(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk_entry::ChunkEntry;
+    use crate::utils::test_utils::{chunk_of, temp_store_path};
+
+    fn messages(chunk: &Chunk) -> Vec<Vec<u8>> {
+        return chunk.decode_entries().ok().expect("Could not decode entries")
+            .into_iter()
+            .map(|e: ChunkEntry| e.message)
+            .collect();
+    }
+
+    fn test_config() -> ArchiveConfig {
+        return ArchiveConfig {
+            min_age: Duration::from_millis(100),
+            ..ArchiveConfig::default()
+        };
+    }
+
+    #[test]
+    fn archived_chunks_read_back_unchanged() {
+        let path: String = temp_store_path("archived_chunks_read_back_unchanged");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        let originals: Vec<Chunk> = vec![chunk_of(&[1]), chunk_of(&[2]), chunk_of(&[3])];
+        for chunk in originals.iter() {
+            store.append_chunk(chunk.clone()).unwrap();
+        }
+        store.append_chunk(chunk_of(&[950])).unwrap();
+        store.append_chunk(chunk_of(&[10])).unwrap();
+
+        let summary: ArchiveSummary = store.archive_aged(&test_config(), 1000).unwrap();
+        assert_eq!(summary.chunks_archived, 3);
+        // The recent chunk and the latest chunk stay in the store
+        assert_eq!(store.header.chunk_offsets.len(), 2);
+        assert_eq!(store.header.chunk_count, 2);
+        assert_eq!(messages(&store.read_chunk(0).unwrap()), vec![b"message 950".to_vec()]);
+
+        let reopened: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(reopened.header.archives.len(), 1);
+        let reference: &ArchiveReference = &reopened.header.archives[0];
+        assert_eq!((reference.timestamp_from, reference.timestamp_to, reference.chunk_count), (1, 3, 3));
+        let archive_path: String = reopened.archive_path(reference);
+        assert_eq!(summary.archive_path, Some(archive_path.clone()));
+
+        let archive: ArchiveStore = ArchiveStore::open(archive_path.as_str()).unwrap();
+        assert_eq!(archive.chunk_count(), 3);
+        assert_eq!(archive.chunks_overlapping(2, 2), vec![1]);
+        for (index, original) in originals.iter().enumerate() {
+            let archived: Chunk = archive.read_chunk(index).unwrap();
+            assert_eq!(archived.codec, CompressionCodec::ZLIB_BEST.id());
+            assert_eq!((archived.timestamp_from, archived.timestamp_to), (original.timestamp_from, original.timestamp_to));
+            assert_eq!(messages(&archived), messages(original));
+        }
+    }
+
+    #[test]
+    fn discards_archive_whose_chunks_changed() {
+        let path: String = temp_store_path("discards_archive_whose_chunks_changed");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=3 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        let planner: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let planned: PlannedArchive = planner.plan_archival(&test_config(), 1000).unwrap().unwrap();
+        assert_eq!(planned.originals.len(), 2);
+        let planned_path: String = planned.archive_path.clone();
+        assert!(Path::new(planned_path.as_str()).exists());
+
+        // Another instance archives the same chunks first
+        let mut archiver: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(archiver.archive_aged(&test_config(), 1000).unwrap().chunks_archived, 2);
+        let summary: ArchiveSummary = store.apply_archival(planned).unwrap();
+        assert_eq!(summary.chunks_archived, 0);
+        assert!(!Path::new(planned_path.as_str()).exists());
+        assert_eq!(store.header.archives.len(), 1);
+        assert_eq!(store.header.chunk_offsets.len(), 1);
+    }
+}
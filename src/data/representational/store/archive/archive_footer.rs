@@ -0,0 +1,41 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ArchiveFooter {
+        #[byte_size=8]
+        pub index_offset: u64,
+        #[byte_size=8]
+        pub index_count: u64,
+        #[byte_size=4]
+        pub index_checksum: u32,
+        #[byte_size=2]
+        pub format_version: u16,
+        #[byte_size=4]
+        pub magic: u32,
+    }
+}
+
+byte_layout!{
+    ArchiveFooter
+    value [index_offset, u64, Big]
+    value [index_count, u64, Big]
+    value [index_checksum, u32, Big]
+    value [format_version, u16, Big]
+    value [magic, u32, Big]
+}
+
+///
+/// Fixed size trailer of an archive file locating its chunk index. Archives
+/// are written once, so the footer is the last thing written and an archive
+/// without a valid footer is incomplete.
+///
+impl ArchiveFooter {
+    pub const MAGIC: u32 = 0x43484B41; // "CHKA"
+    pub const FORMAT_VERSION: u16 = 1;
+    pub const LENGTH: u64 = 26;
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == Self::MAGIC && self.format_version == Self::FORMAT_VERSION
+    }
+}
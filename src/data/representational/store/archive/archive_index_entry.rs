@@ -0,0 +1,30 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ArchiveIndexEntry {
+        #[byte_size=8]
+        pub timestamp_from: u64,
+        #[byte_size=8]
+        pub timestamp_to: u64,
+        #[byte_size=8]
+        pub offset: u64,
+        #[byte_size=4]
+        pub length: u32,
+    }
+}
+
+byte_layout!{
+    ArchiveIndexEntry
+    value [timestamp_from, u64, Big]
+    value [timestamp_to, u64, Big]
+    value [offset, u64, Big]
+    value [length, u32, Big]
+}
+
+impl ArchiveIndexEntry {
+    #[inline]
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        self.timestamp_from <= to && self.timestamp_to >= from
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone,PartialEq)]
+    pub struct ArchiveReference {
+        #[byte_size=8]
+        pub timestamp_from: u64,
+        #[byte_size=8]
+        pub timestamp_to: u64,
+        #[byte_size=8]
+        pub chunk_count: u64,
+        pub file_name: Vec<u8>,
+    }
+}
+
+byte_layout!{
+    ArchiveReference
+    value [timestamp_from, u64, Big]
+    value [timestamp_to, u64, Big]
+    value [chunk_count, u64, Big]
+    bytes_vec_null_term [file_name]
+}
+
+impl ArchiveReference {
+    /// Archive files are referenced by name relative to the store's directory,
+    /// so a store can be moved together with its archives.
+    pub fn file_name_string(&self) -> String {
+        String::from_utf8_lossy(self.file_name.as_slice()).into_owned()
+    }
+    #[inline]
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        self.timestamp_from <= to && self.timestamp_to >= from
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs::{File, OpenOptions, Permissions};
+use std::io::{BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use flate2::Crc;
+use crate::data::representational::chunk::Chunk;
+use crate::utils::file_utils::sync_parent_directory;
+use super::archive_footer::ArchiveFooter;
+use super::archive_index_entry::ArchiveIndexEntry;
+
+///
+/// A sealed, read-only file of cold chunks. Chunks are stored back to back,
+/// followed by an index of each chunk's time range and position, and a footer
+/// locating the index. Time range lookups only need the index, so chunks that
+/// do not overlap a query are never read or decompressed.
+///
+pub struct ArchiveStore {
+    pub path: String,
+    pub footer: ArchiveFooter,
+    pub index: Vec<ArchiveIndexEntry>,
+}
+
+impl ArchiveStore {
+    ///
+    /// Write chunks into a new archive. The archive is assembled in a temporary
+    /// file and renamed into place once durable, then made read-only, so an
+    /// archive at `path` is always complete.
+    ///
+    /// # Arguments
+    /// * path: Location of the archive file
+    /// * chunks: Chunks to archive, in store order
+    ///
+    /// # Returns
+    /// `Result<ArchiveStore>`: The sealed archive
+    ///
+    pub fn write(path: &str, chunks: &[Chunk]) -> Result<ArchiveStore, Error> {
+        let temp_path: String = format!("{}.tmp", path);
+        let file: File = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(temp_path.as_str())?;
+        let mut writer: BufWriter<File> = BufWriter::new(file);
+        let mut index: Vec<ArchiveIndexEntry> = Vec::with_capacity(chunks.len());
+        let mut offset: u64 = 0;
+        for chunk in chunks.iter() {
+            let chunk_bytes: Vec<u8> = chunk.into_bytes();
+            writer.write_all(chunk_bytes.as_slice())?;
+            index.push(ArchiveIndexEntry {
+                timestamp_from: chunk.timestamp_from,
+                timestamp_to: chunk.timestamp_to,
+                offset,
+                length: chunk_bytes.len() as u32,
+            });
+            offset += chunk_bytes.len() as u64;
+        }
+        let mut index_bytes: Vec<u8> = Vec::new();
+        for entry in index.iter() {
+            index_bytes.append(&mut entry.into_bytes());
+        }
+        let mut crc: Crc = Crc::new();
+        crc.update(index_bytes.as_slice());
+        let footer: ArchiveFooter = ArchiveFooter {
+            index_offset: offset,
+            index_count: index.len() as u64,
+            index_checksum: crc.sum(),
+            format_version: ArchiveFooter::FORMAT_VERSION,
+            magic: ArchiveFooter::MAGIC,
+        };
+        writer.write_all(index_bytes.as_slice())?;
+        writer.write_all(footer.into_bytes().as_slice())?;
+        let file: File = match writer.into_inner() {
+            Ok(f) => f,
+            Err(e) => return Err(e.into_error()),
+        };
+        file.sync_all()?;
+        let mut permissions: Permissions = file.metadata()?.permissions();
+        permissions.set_readonly(true);
+        file.set_permissions(permissions)?;
+        std::fs::rename(temp_path.as_str(), path)?;
+        sync_parent_directory(path)?;
+        return Ok(ArchiveStore {
+            path: String::from(path),
+            footer,
+            index,
+        });
+    }
+    ///
+    /// Open an archive and load its index, verifying the footer and index checksum.
+    ///
+    /// # Arguments
+    /// * path: Location of the archive file
+    ///
+    /// # Returns
+    /// `Result<ArchiveStore>`: Archive with its index loaded
+    ///
+    pub fn open(path: &str) -> Result<ArchiveStore, Error> {
+        let mut file: File = File::open(path)?;
+        let file_length: u64 = file.metadata()?.len();
+        if file_length < ArchiveFooter::LENGTH {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Archive {} is too short to hold a footer", path),
+            ));
+        }
+        let mut footer_bytes: Vec<u8> = vec![0x00u8; ArchiveFooter::LENGTH as usize];
+        file.seek(SeekFrom::Start(file_length - ArchiveFooter::LENGTH))?;
+        file.read_exact(footer_bytes.as_mut_slice())?;
+        let mut footer: ArchiveFooter = ArchiveFooter::default();
+        if footer.parse_bytes::<&'_ [u8], nom::error::Error<_>>(footer_bytes.as_slice()).is_err() || !footer.is_valid() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Archive {} has no valid footer", path),
+            ));
+        }
+        let index_end: u64 = file_length - ArchiveFooter::LENGTH;
+        if footer.index_offset > index_end {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Archive {} index lies outside the file", path),
+            ));
+        }
+        let mut index_bytes: Vec<u8> = vec![0x00u8; (index_end - footer.index_offset) as usize];
+        file.seek(SeekFrom::Start(footer.index_offset))?;
+        file.read_exact(index_bytes.as_mut_slice())?;
+        let mut crc: Crc = Crc::new();
+        crc.update(index_bytes.as_slice());
+        if crc.sum() != footer.index_checksum {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Archive {} index checksum mismatch", path),
+            ));
+        }
+        let mut index: Vec<ArchiveIndexEntry> = Vec::with_capacity(footer.index_count as usize);
+        let mut tail: &[u8] = index_bytes.as_slice();
+        for _ in 0..footer.index_count {
+            let mut entry: ArchiveIndexEntry = ArchiveIndexEntry::default();
+            tail = match entry.parse_bytes::<&'_ [u8], nom::error::Error<_>>(tail) {
+                Ok(t) => t,
+                Err(e) => return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    e.to_string(),
+                )),
+            };
+            index.push(entry);
+        }
+        return Ok(ArchiveStore {
+            path: String::from(path),
+            footer,
+            index,
+        });
+    }
+    pub fn chunk_count(&self) -> usize {
+        self.index.len()
+    }
+    pub fn read_chunk(&self, index: usize) -> Result<Chunk, Error> {
+        let entry: &ArchiveIndexEntry = match self.index.get(index) {
+            Some(e) => e,
+            None => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("No chunk at index {}", index),
+            )),
+        };
+        let mut file: File = File::open(self.path.as_str())?;
+        let mut chunk_bytes: Vec<u8> = vec![0x00u8; entry.length as usize];
+        file.seek(SeekFrom::Start(entry.offset))?;
+        file.read_exact(chunk_bytes.as_mut_slice())?;
+        let mut chunk: Chunk = Chunk::default();
+        return match chunk.parse_bytes::<&'_ [u8], nom::error::Error<_>>(chunk_bytes.as_slice()) {
+            Ok(_) => Ok(chunk),
+            Err(e) => Err(Error::new(
+                ErrorKind::InvalidData,
+                e.to_string(),
+            )),
+        };
+    }
+    ///
+    /// Indices of the archived chunks whose time range overlaps the given range.
+    ///
+    pub fn chunks_overlapping(&self, from: u64, to: u64) -> Vec<usize> {
+        self.index.iter()
+            .enumerate()
+            .filter(|(_, e): &(usize, &ArchiveIndexEntry)| e.overlaps(from, to))
+            .map(|(i, _): (usize, &ArchiveIndexEntry)| i)
+            .collect()
+    }
+}
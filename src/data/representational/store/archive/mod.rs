@@ -0,0 +1,5 @@
+pub mod archival;
+pub mod archive_footer;
+pub mod archive_index_entry;
+pub mod archive_reference;
+pub mod archive_store;
@@ -0,0 +1,228 @@
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use crate::compression::compressor::CompressionCodec;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::chunk_summary::ChunkSummary;
+use crate::data::representational::hyper_log_log::HyperLogLog;
+use super::chunk_store::ChunkStore;
+
+///
+/// Size and content statistics of one chunk in a store, taken from its prefix
+/// and summary section, or from its decompressed entries when it was written
+/// without a summary.
+///
+#[derive(Debug, Clone)]
+pub struct ChunkInspection {
+    pub index: usize,
+    pub timestamp_from: u64,
+    pub timestamp_to: u64,
+    pub codec: String,
+    pub entry_count: u64,
+    pub compressed_length: u64,
+    pub uncompressed_length: u64,
+    pub distinct_targets: Option<u64>,
+    /// Sketch of the chunk's targets, merged with other chunks' to count targets across them
+    pub target_sketch: Option<HyperLogLog>,
+}
+
+impl ChunkInspection {
+    pub fn compression_ratio(&self) -> f64 {
+        if self.compressed_length == 0 {
+            return 0.0;
+        }
+        return self.uncompressed_length as f64 / self.compressed_length as f64;
+    }
+    ///
+    /// Approximate number of distinct targets across the inspected chunks, from
+    /// the union of their sketches.
+    ///
+    /// # Returns
+    /// `Option<u64>`: Estimate, `None` if a chunk's sketch is unreadable or of another precision
+    ///
+    pub fn distinct_targets_across(inspections: &[ChunkInspection]) -> Option<u64> {
+        let mut union: Option<HyperLogLog> = None;
+        for inspection in inspections.iter() {
+            let sketch: &HyperLogLog = inspection.target_sketch.as_ref()?;
+            match union.as_mut() {
+                Some(u) => if !u.merge(sketch) {
+                    return None;
+                },
+                None => union = Some(sketch.clone()),
+            }
+        }
+        return Some(union.map_or(0, |u: HyperLogLog| u.estimate()));
+    }
+}
+
+impl ChunkStore {
+    ///
+    /// Inspect every chunk in the store, excluding archived chunks. Only each
+    /// chunk's prefix and metadata sections are read; entries are decompressed
+    /// just for chunks without a summary section.
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkInspection>>`: Statistics of each chunk in store order
+    ///
+    pub fn inspect(&self) -> Result<Vec<ChunkInspection>, Error> {
+        let mut file: File = File::open(self.path.as_str())?;
+        let mut inspections: Vec<ChunkInspection> = Vec::with_capacity(self.header.chunk_offsets.len());
+        for index in 0..self.header.chunk_offsets.len() {
+            let (timestamp_from, timestamp_to): (u64, u64) = self.read_chunk_bounds(&mut file, index)?;
+            let codec: u8 = self.read_chunk_codec(&mut file, index)?;
+            let entries_length: u32 = self.read_chunk_entries_length(&mut file, index)?;
+            let sections: Vec<ChunkMetadataSection> = self.read_chunk_sections(&mut file, index)?;
+            let summary: ChunkSummary = match sections.iter().find_map(ChunkSummary::from_section) {
+                Some(s) => s,
+                None => {
+                    let chunk: Chunk = self.read_chunk_from(&mut file, index)?;
+                    match chunk.decode_entries() {
+                        Ok(entries) => ChunkSummary::build(entries.as_slice()),
+                        Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+                    }
+                },
+            };
+            inspections.push(ChunkInspection {
+                index,
+                timestamp_from,
+                timestamp_to,
+                codec: CompressionCodec::from_id(codec).map_or(format!("UNKNOWN({})", codec), |c: CompressionCodec| format!("{:?}", c)),
+                entry_count: summary.entry_count as u64,
+                compressed_length: entries_length as u64,
+                uncompressed_length: summary.uncompressed_length as u64,
+                distinct_targets: summary.distinct_targets(),
+                target_sketch: summary.target_sketch(),
+            });
+        }
+        return Ok(inspections);
+    }
+    ///
+    /// Render `inspect` as a table, one chunk per row, followed by a total.
+    ///
+    pub fn string_format_inspection(&self) -> Result<String, Error> {
+        let inspections: Vec<ChunkInspection> = self.inspect()?;
+        let mut report: String = String::new();
+        let _ = writeln!(
+            report,
+            "{:>6} {:>14} {:>14} {:>12} {:>8} {:>10} {:>12} {:>7} {:>8}",
+            "chunk", "from", "to", "codec", "entries", "stored", "uncompressed", "ratio", "targets",
+        );
+        for inspection in inspections.iter() {
+            let _ = writeln!(
+                report,
+                "{:>6} {:>14} {:>14} {:>12} {:>8} {:>9}B {:>11}B {:>7.2} {:>8}",
+                inspection.index,
+                inspection.timestamp_from,
+                inspection.timestamp_to,
+                inspection.codec,
+                inspection.entry_count,
+                inspection.compressed_length,
+                inspection.uncompressed_length,
+                inspection.compression_ratio(),
+                inspection.distinct_targets.map_or(String::from("?"), |t: u64| format!("~{}", t)),
+            );
+        }
+        let compressed: u64 = inspections.iter().map(|i: &ChunkInspection| i.compressed_length).sum();
+        let uncompressed: u64 = inspections.iter().map(|i: &ChunkInspection| i.uncompressed_length).sum();
+        let _ = writeln!(
+            report,
+            "{} chunks, {} entries, {}B stored of {}B uncompressed, ratio {:.2}, {} targets",
+            inspections.len(),
+            inspections.iter().map(|i: &ChunkInspection| i.entry_count).sum::<u64>(),
+            compressed,
+            uncompressed,
+            if compressed == 0 { 0.0 } else { uncompressed as f64 / compressed as f64 },
+            ChunkInspection::distinct_targets_across(inspections.as_slice()).map_or(String::from("?"), |t: u64| format!("~{}", t)),
+        );
+        return Ok(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk_entry::ChunkEntry;
+    use crate::utils::test_utils::{entry_at, temp_store_path};
+
+    fn entries_for(targets: std::ops::Range<u64>, timestamp_from: u64) -> Vec<ChunkEntry> {
+        return targets.enumerate()
+            .map(|(i, target): (usize, u64)| ChunkEntry {
+                target: format!("target-{}", target).into_bytes(),
+                message: format!("message {}", i).into_bytes(),
+                ..entry_at(timestamp_from + i as u64)
+            })
+            .collect();
+    }
+
+    fn assert_near(estimate: u64, actual: u64) {
+        let error: f64 = (estimate as f64 - actual as f64).abs() / actual as f64;
+        assert!(error < 0.15, "estimate {} of {}", estimate, actual);
+    }
+
+    #[test]
+    fn inspects_chunks_from_their_summaries() {
+        let path: String = temp_store_path("inspects_chunks_from_their_summaries");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        let first: Vec<ChunkEntry> = entries_for(0..300, 1000);
+        let second: Vec<ChunkEntry> = entries_for(200..600, 2000);
+        let first_chunk: Chunk = Chunk::seal_with(first.as_slice(), CompressionCodec::ZLIB_FAST, 0.01).ok().expect("Could not seal chunk");
+        let first_length: u64 = first_chunk.entries_length as u64;
+        store.append_chunk(first_chunk).unwrap();
+        // A chunk written before summaries existed is summarised from its entries
+        let mut second_chunk: Chunk = Chunk::seal_with(second.as_slice(), CompressionCodec::ZLIB_BEST, 0.01).ok().expect("Could not seal chunk");
+        second_chunk.sections.retain(|s: &ChunkMetadataSection| s.kind != ChunkMetadataSection::SUMMARY);
+        second_chunk.sections_length = second_chunk.sections.len() as u16;
+        second_chunk.length = second_chunk.into_bytes().len() as u32;
+        store.append_chunk(second_chunk).unwrap();
+
+        let inspections: Vec<ChunkInspection> = store.inspect().unwrap();
+        assert_eq!(inspections.len(), 2);
+        assert_eq!((inspections[0].timestamp_from, inspections[0].timestamp_to), (1000, 1299));
+        assert_eq!((inspections[1].timestamp_from, inspections[1].timestamp_to), (2000, 2399));
+        assert_eq!(inspections[0].codec, "ZLIB_FAST");
+        assert_eq!(inspections[1].codec, "ZLIB_BEST");
+        assert_eq!(inspections[0].entry_count, 300);
+        assert_eq!(inspections[1].entry_count, 400);
+        assert_eq!(inspections[0].compressed_length, first_length);
+        let uncompressed: u64 = first.iter().map(|e: &ChunkEntry| e.into_bytes().len() as u64).sum();
+        assert_eq!(inspections[0].uncompressed_length, uncompressed);
+        assert!(inspections[0].compression_ratio() > 1.0);
+        assert_near(inspections[0].distinct_targets.unwrap(), 300);
+        assert_near(inspections[1].distinct_targets.unwrap(), 400);
+        // Targets 200 to 299 are in both chunks, so the union counts them once
+        assert_near(ChunkInspection::distinct_targets_across(inspections.as_slice()).unwrap(), 600);
+        assert_eq!(ChunkInspection::distinct_targets_across(&[]), Some(0));
+    }
+
+    #[test]
+    fn formats_inspection_report() {
+        let path: String = temp_store_path("formats_inspection_report");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for (targets, timestamp_from) in [(0..10, 100), (5..20, 200)] {
+            let entries: Vec<ChunkEntry> = entries_for(targets, timestamp_from);
+            store.append_chunk(Chunk::seal_with(entries.as_slice(), CompressionCodec::ZLIB_FAST, 0.01).ok().expect("Could not seal chunk")).unwrap();
+        }
+        let inspections: Vec<ChunkInspection> = store.inspect().unwrap();
+        let report: String = store.string_format_inspection().unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<&str>>(),
+            vec!["chunk", "from", "to", "codec", "entries", "stored", "uncompressed", "ratio", "targets"],
+        );
+        for (line, inspection) in lines[1..3].iter().zip(inspections.iter()) {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(columns[0], inspection.index.to_string());
+            assert_eq!(columns[1], inspection.timestamp_from.to_string());
+            assert_eq!(columns[2], inspection.timestamp_to.to_string());
+            assert_eq!(columns[3], "ZLIB_FAST");
+            assert_eq!(columns[5], format!("{}B", inspection.compressed_length));
+            assert_eq!(columns[8], format!("~{}", inspection.distinct_targets.unwrap()));
+        }
+        assert!(lines[1].split_whitespace().nth(4) == Some("10") && lines[2].split_whitespace().nth(4) == Some("15"));
+        let compressed: u64 = inspections.iter().map(|i: &ChunkInspection| i.compressed_length).sum();
+        assert!(lines[3].starts_with(format!("2 chunks, 25 entries, {}B stored of ", compressed).as_str()), "{}", lines[3]);
+        assert!(lines[3].ends_with(format!("~{} targets", ChunkInspection::distinct_targets_across(inspections.as_slice()).unwrap()).as_str()), "{}", lines[3]);
+    }
+}
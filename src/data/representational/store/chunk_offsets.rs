@@ -1,24 +1,50 @@
+use std::io;
 use crate::{byte_layout, reify};
 
 reify!{
-    #[derive(Debug,Default,Clone)]
+    #[derive(Debug,Default,Clone,PartialEq)]
     pub struct ChunkOffsets {
+        #[byte_size=8]
+        pub sector_index: u64,
         #[byte_size=4]
-        pub sector_index: u32,
-        #[byte_size=2]
-        pub sector_offset: u16,
+        pub sector_offset: u32,
     }
 }
 
 byte_layout!{
     ChunkOffsets
-    value [sector_index, u32, Big]
-    value [sector_offset, u16, Big]
+    value [sector_index, u64, Big]
+    value [sector_offset, u32, Big]
 }
 
 impl ChunkOffsets {
+    pub fn from_relative_offset(offset: u64, sector_size: u32) -> Result<ChunkOffsets, io::Error> {
+        if sector_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Sector size must be non-zero",
+            ));
+        }
+        return Ok(ChunkOffsets {
+            sector_index: offset / sector_size as u64,
+            sector_offset: (offset % sector_size as u64) as u32,
+        });
+    }
     #[inline]
-    pub fn calculate_offset(&self, sector_size: u32) -> u32 {
-        (self.sector_index * sector_size) + self.sector_offset as u32
+    pub fn calculate_offset(&self, sector_size: u32) -> Result<u64, io::Error> {
+        return match self.sector_index
+            .checked_mul(sector_size as u64)
+            .and_then(|v: u64| v.checked_add(self.sector_offset as u64)) {
+            Some(v) => Ok(v),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Chunk offset overflows: sector {} offset {} with sector size {}",
+                    self.sector_index,
+                    self.sector_offset,
+                    sector_size
+                ),
+            )),
+        };
     }
-}
\ No newline at end of file
+}
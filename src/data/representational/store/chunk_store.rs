@@ -1,117 +1,638 @@
-use std::borrow::Borrow;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, Error, Read, Seek};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use nom::AsBytes;
 use crate::data::abstraction::log_store::LogStore;
-use crate::{byte_layout, ChunkOffsets, reify};
+use crate::reify;
+use super::chunk_offsets::ChunkOffsets;
 use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
 use super::chunk_store_header::ChunkStoreHeader;
+use super::chunk_store_header_slot::ChunkStoreHeaderSlot;
+use super::free_sector_range::FreeSectorRange;
+use super::reader_registry::{ReadLease, ReaderRegistry};
+use super::chunk_store_view::ChunkStoreView;
+use super::store_write_lock::StoreWriteLock;
+use super::legacy::baseline_chunk_store::BaselineChunkStore;
 use crate::encoding::errors::encoding_errors;
 use crate::encoding::transcoder::Transcoder;
+use crate::utils::file_utils::sync_parent_directory;
 
 reify!{
     #[derive(Debug,Default)]
     pub struct ChunkStore {
+        pub path: String,
         pub header: ChunkStoreHeader,
-        #[byte_size=8]
-        pub chunks_length: u64,
-        pub chunks: Vec<Chunk>,
+        pub active_slot: ChunkStoreHeaderSlot,
+        #[byte_size=1]
+        pub active_slot_index: u8,
         pub latest_chunk: Chunk,
-        // TODO: Add a field to indicate chunk compression scheme
+        pub readers: ReaderRegistry,
+        pub lease: Option<ReadLease>,
     }
 }
 
-byte_layout!{
-    ChunkStore
-    composite [header, ChunkStoreHeader]
-    value [chunks_length, u64, Big]
-    composite_vec [chunks, chunks_length, Chunk]
-}
-
 impl ChunkStore {
-    pub fn chunks_length_bytes_length() -> Result<u64, std::io::Error> {
-        let attribute: Option<String> = match Self::get_field_attribute("chunks_length") {
-            Ok(v) => v,
-            Err(e) => return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                e.to_string(),
-            )),
-        };
-        if attribute.is_none() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Field has no attributes",
-            ))
-        }
-        let attr_string: String = attribute.unwrap();
-        let attribute_split: Vec<&str> = attr_string.split("=").collect::<Vec<&str>>();
-        let split_val: Option<&&str> = attribute_split.get(1);
-        if split_val.is_none() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Field has no attributes",
+    ///
+    /// Create an empty store, overwriting anything at the given path. The first
+    /// header is committed to slot 0 with generation 1.
+    ///
+    /// # Arguments
+    /// * path: Location of the store file
+    /// * sector_size: Size in bytes of the sectors chunks are addressed by
+    /// * align_chunks: Whether chunks start on sector boundaries, fixed for the life of the store
+    ///
+    /// # Returns
+    /// `Result<ChunkStore>`: The newly created store
+    ///
+    pub fn create(path: &str, sector_size: u32, align_chunks: bool) -> Result<ChunkStore, Error> {
+        if sector_size == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Sector size must be non-zero",
             ));
         }
-        return match split_val.unwrap().parse::<u64>() {
-            Ok(v) => Ok(v),
-            Err(e) => Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Could not parse field",
-            )),
+        let mut file: File = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.write_all(vec![0x00u8; ChunkStoreHeaderSlot::DATA_OFFSET as usize].as_slice())?;
+        let mut store: ChunkStore = ChunkStore {
+            path: String::from(path),
+            readers: ReaderRegistry::for_store(path),
+            ..ChunkStore::default()
         };
+        store.header.sector_size = sector_size;
+        store.header.align_chunks = align_chunks as u8;
+        // Slot 1 is the inactive slot so the first commit lands in slot 0
+        store.active_slot_index = 1;
+        store.commit_header(&mut file)?;
+        return Ok(store);
     }
+    ///
+    /// Open an existing store, selecting the newest header slot whose checksum
+    /// validates, and load the latest chunk.
+    ///
+    /// # Arguments
+    /// * path: Location of the store file
+    ///
+    /// # Returns
+    /// `Result<ChunkStore>`: Store with its header and latest chunk loaded
+    ///
     pub fn read_from_file(path: &str) -> Result<ChunkStore, Error> {
-        let mut store: ChunkStore = ChunkStore::default();
-        let file: File = OpenOptions::new()
+        let mut file: File = OpenOptions::new()
             .read(true)
             .write(false)
             .create(false)
             .open(path)?;
-        let mut buf_reader: BufReader<&File> = BufReader::new(&file);
-        store.header.read_from_file(&mut buf_reader)?;
-        let mut length_bytes: Vec<u8> = Vec::with_capacity(8usize);
-        buf_reader.get_ref()
-            .take(8)
-            .read_to_end(&mut length_bytes)?;
-        store.chunks_length = match nom::number::complete::be_u64::<_, nom::error::Error<_>>(length_bytes.as_bytes()) {
-            Ok((_, v)) => v,
-            Err(e) => return Err(Error::new(
-                std::io::ErrorKind::InvalidData
+        let mut store: ChunkStore = ChunkStore {
+            path: String::from(path),
+            readers: ReaderRegistry::for_store(path),
+            ..ChunkStore::default()
+        };
+        let (index, slot, header_bytes, lease) = loop {
+            let (index, slot, header_bytes) = match Self::newest_slot(&mut file, path)? {
+                Some(v) => v,
+                None => {
+                    if let Some(baseline) = BaselineChunkStore::read_from_file(&mut file)? {
+                        drop(file);
+                        Self::migrate_baseline(path, baseline)?;
+                        return Self::read_from_file(path);
+                    }
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("No valid header slot in store {}", path),
+                    ));
+                },
+            };
+            let lease: ReadLease = store.readers.acquire(slot.generation)?;
+            // Space the header references is only safe once the lease is visible
+            // to writers, so retry if a commit landed before it was taken
+            let still_newest: bool = Self::newest_slot(&mut file, path)?
+                .is_some_and(|(_, newest, _)| newest.generation == slot.generation);
+            if still_newest {
+                break (index, slot, header_bytes, lease);
+            }
+        };
+        store.header = Self::parse_header(slot.format_version, header_bytes.as_slice())?;
+        store.active_slot = slot;
+        store.active_slot_index = index as u8;
+        store.lease = Some(lease);
+        if store.header.chunk_offsets_length > 0 {
+            store.latest_chunk = store.read_chunk_from(&mut file, store.header.chunk_offsets_length as usize - 1)?;
+        }
+        return Ok(store);
+    }
+    fn parse_header(format_version: u16, header_bytes: &[u8]) -> Result<ChunkStoreHeader, Error> {
+        if format_version != ChunkStoreHeaderSlot::FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported store format version {}", format_version),
+            ));
+        }
+        let mut header: ChunkStoreHeader = ChunkStoreHeader::default();
+        if let Err(e) = header.parse_bytes::<&'_ [u8], nom::error::Error<_>>(header_bytes) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                e.to_string(),
+            ));
+        }
+        if header.sector_size == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Store header has a sector size of zero",
+            ));
+        }
+        return Ok(header);
+    }
+    pub(crate) fn parse_chunk(chunk_bytes: &[u8]) -> Result<Chunk, Error> {
+        let mut chunk: Chunk = Chunk::default();
+        return match chunk.parse_bytes::<&'_ [u8], nom::error::Error<_>>(chunk_bytes) {
+            Ok(_) => Ok(chunk),
+            Err(e) => Err(Error::new(
+                ErrorKind::InvalidData,
                 e.to_string(),
             )),
         };
-        let last_offset: &ChunkOffsets = match store.header.chunk_offsets.get(store.header.chunk_offsets_length as usize - 1) {
+    }
+    ///
+    /// Rewrite a store written before header slots existed in the current
+    /// format. The chunks are appended to a new store beside the original,
+    /// which then replaces it, so a crash part way through leaves the original
+    /// untouched.
+    ///
+    /// # Arguments
+    /// * path: Location of the baseline store
+    /// * baseline: Contents read from it
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result once the migrated store has replaced the original
+    ///
+    fn migrate_baseline(path: &str, baseline: BaselineChunkStore) -> Result<(), Error> {
+        info!(
+            crate::LOGGER,
+            "Migrating baseline store {} with {} chunks to format version {}",
+            path,
+            baseline.chunks.len(),
+            ChunkStoreHeaderSlot::FORMAT_VERSION
+        );
+        let migrating_path: String = format!("{}.migrating", path);
+        let mut store: ChunkStore = ChunkStore::create(migrating_path.as_str(), baseline.header.sector_size as u32, false)?;
+        for chunk in baseline.chunks.into_iter() {
+            store.append_chunk(chunk)?;
+        }
+        let migrating_readers: ReaderRegistry = store.readers.clone();
+        drop(store);
+        migrating_readers.remove_directory();
+        let _ = std::fs::remove_file(StoreWriteLock::path_for_store(migrating_path.as_str()));
+        std::fs::rename(migrating_path.as_str(), path)?;
+        return sync_parent_directory(path);
+    }
+    ///
+    /// Slot with the highest generation whose checksum validates.
+    ///
+    fn newest_slot(file: &mut File, path: &str) -> Result<Option<(usize, ChunkStoreHeaderSlot, Vec<u8>)>, Error> {
+        let mut active: Option<(usize, ChunkStoreHeaderSlot, Vec<u8>)> = None;
+        for index in 0..(ChunkStoreHeaderSlot::SLOT_COUNT as usize) {
+            let candidate: Option<(ChunkStoreHeaderSlot, Vec<u8>)> = match Self::read_slot(file, index) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(crate::LOGGER, "Ignoring unreadable header slot {} in {}: {}", index, path, e);
+                    None
+                },
+            };
+            if let Some((slot, header_bytes)) = candidate {
+                let is_newer: bool = match &active {
+                    Some((_, current, _)) => slot.generation > current.generation,
+                    None => true,
+                };
+                if is_newer {
+                    active = Some((index, slot, header_bytes));
+                }
+            }
+        }
+        return Ok(active);
+    }
+    fn read_slot(file: &mut File, index: usize) -> Result<Option<(ChunkStoreHeaderSlot, Vec<u8>)>, Error> {
+        let mut slot_bytes: Vec<u8> = vec![0x00u8; ChunkStoreHeaderSlot::SLOT_LENGTH as usize];
+        file.seek(SeekFrom::Start(ChunkStoreHeaderSlot::slot_offset(index)))?;
+        file.read_exact(slot_bytes.as_mut_slice())?;
+        let mut slot: ChunkStoreHeaderSlot = ChunkStoreHeaderSlot::default();
+        if slot.parse_bytes::<&'_ [u8], nom::error::Error<_>>(slot_bytes.as_bytes()).is_err() || !slot.is_initialised() {
+            return Ok(None);
+        }
+        let file_length: u64 = file.metadata()?.len();
+        if slot.header_offset.saturating_add(slot.header_length) > file_length {
+            return Ok(None);
+        }
+        let mut header_bytes: Vec<u8> = vec![0x00u8; slot.header_length as usize];
+        file.seek(SeekFrom::Start(slot.header_offset))?;
+        file.read_exact(header_bytes.as_mut_slice())?;
+        if !slot.is_valid_for(header_bytes.as_slice()) {
+            return Ok(None);
+        }
+        return Ok(Some((slot, header_bytes)));
+    }
+    pub fn generation(&self) -> u64 {
+        self.active_slot.generation
+    }
+    ///
+    /// Open the store file to change the store, taking the store's write lock
+    /// and reloading the header if another instance has committed since this
+    /// one last read or committed it. Every change must be made from the header
+    /// as it is once this returns, and committed before the lock is dropped.
+    ///
+    /// # Returns
+    /// `Result<(File, StoreWriteLock)>`: Store file open for writing, and the lock to hold until committed
+    ///
+    pub(crate) fn open_for_write(&mut self) -> Result<(File, StoreWriteLock), Error> {
+        let lock: StoreWriteLock = StoreWriteLock::acquire(self.path.as_str())?;
+        let mut file: File = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(self.path.as_str())?;
+        let (index, slot, header_bytes) = match Self::newest_slot(&mut file, self.path.as_str())? {
             Some(v) => v,
             None => return Err(Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!(
-                    "Could not get last entry at index {}",
-                    store.header.chunk_offsets_length - 1
-                ),
-            ))
+                ErrorKind::InvalidData,
+                format!("No valid header slot in store {}", self.path),
+            )),
         };
-        buf_reader.seek_relative(last_offset.calculate_offset(store.header.sector_size as u32) as i64)?;
-        let chunk_length_bytes: Vec<u8> = Vec::with_capacity(4usize);
-        buf_reader.get_ref()
-            .take(4)
-            .read_to_end(&mut length_bytes)?;
-        let chunk_length: u32 = match nom::number::complete::be_u32::<_, nom::error::Error<_>>(chunk_length_bytes.as_bytes()) {
-            Ok((_, v)) => v,
-            Err(e) => return Err(Error::new(
-                std::io::ErrorKind::InvalidData,
-                e.to_string(),
+        if slot.generation != self.generation() {
+            info!(
+                crate::LOGGER,
+                "Reloading header of {} at generation {}, committed by another instance",
+                self.path,
+                slot.generation
+            );
+            // No commit can land while the lock is held, so the lease needs no retry
+            let lease: ReadLease = self.readers.acquire(slot.generation)?;
+            self.header = Self::parse_header(slot.format_version, header_bytes.as_slice())?;
+            self.active_slot = slot;
+            self.active_slot_index = index as u8;
+            self.lease = Some(lease);
+            self.latest_chunk = match self.header.chunk_offsets.len() {
+                0 => Chunk::default(),
+                count => self.read_chunk_from(&mut file, count - 1)?,
+            };
+        }
+        return Ok((file, lock));
+    }
+    pub fn chunk_file_offset(&self, index: usize) -> Result<u64, Error> {
+        return match self.header.chunk_offsets.get(index) {
+            Some(offset) => match offset.calculate_offset(self.header.sector_size)?.checked_add(ChunkStoreHeaderSlot::DATA_OFFSET) {
+                Some(v) => Ok(v),
+                None => Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("File offset of chunk {} overflows", index),
+                )),
+            },
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("No chunk at index {}", index),
             )),
-        };buf_reader.seek_relative(-(chunk_length as i64))?;
-        let chunk_bytes: Vec<u8> = Vec::with_capacity(chunk_length as usize);
-        buf_reader.take(chunk_length as u64)
-            .read_to_end(&mut length_bytes)?;
-        return match store.latest_chunk.parse_bytes::<&'_ [u8], nom::error::Error<_>>(chunk_bytes.as_bytes()) {
-            Ok(_) => Ok(store),
+        };
+    }
+    pub fn read_chunk(&self, index: usize) -> Result<Chunk, Error> {
+        let mut file: File = File::open(self.path.as_str())?;
+        return self.read_chunk_from(&mut file, index);
+    }
+    pub(crate) fn read_chunk_length(&self, file: &mut File, index: usize) -> Result<u32, Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let chunk_length_bytes_length: u32 = Chunk::header_length()?;
+        let mut length_bytes: Vec<u8> = vec![0x00u8; chunk_length_bytes_length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(length_bytes.as_mut_slice())?;
+        return match nom::number::complete::be_u32::<_, nom::error::Error<_>>(length_bytes.as_bytes()) {
+            Ok((_, v)) => Ok(v),
             Err(e) => Err(Error::new(
-                std::io::ErrorKind::InvalidInput,
-                e.to_string()
+                ErrorKind::InvalidData,
+                e.to_string(),
+            )),
+        };
+    }
+    pub(crate) fn read_chunk_from(&self, file: &mut File, index: usize) -> Result<Chunk, Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let chunk_length: u32 = self.read_chunk_length(file, index)?;
+        let mut chunk_bytes: Vec<u8> = vec![0x00u8; chunk_length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(chunk_bytes.as_mut_slice())?;
+        return Self::parse_chunk(chunk_bytes.as_bytes());
+    }
+    ///
+    /// Read a chunk's time range from its fixed size prefix without reading its entries.
+    ///
+    pub(crate) fn read_chunk_bounds(&self, file: &mut File, index: usize) -> Result<(u64, u64), Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let mut prefix_bytes: [u8; Chunk::CODEC_OFFSET] = [0x00u8; Chunk::CODEC_OFFSET];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut prefix_bytes)?;
+        let mut timestamp_from: [u8; 8] = [0x00u8; 8];
+        let mut timestamp_to: [u8; 8] = [0x00u8; 8];
+        timestamp_from.copy_from_slice(&prefix_bytes[Chunk::TIMESTAMP_FROM_OFFSET..Chunk::TIMESTAMP_TO_OFFSET]);
+        timestamp_to.copy_from_slice(&prefix_bytes[Chunk::TIMESTAMP_TO_OFFSET..Chunk::CODEC_OFFSET]);
+        return Ok((u64::from_be_bytes(timestamp_from), u64::from_be_bytes(timestamp_to)));
+    }
+    ///
+    /// Read the id of the codec a chunk's entries are compressed with from its
+    /// fixed size prefix.
+    ///
+    pub(crate) fn read_chunk_codec(&self, file: &mut File, index: usize) -> Result<u8, Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let mut codec: [u8; 1] = [0x00u8; 1];
+        file.seek(SeekFrom::Start(offset + Chunk::CODEC_OFFSET as u64))?;
+        file.read_exact(&mut codec)?;
+        return Ok(codec[0]);
+    }
+    ///
+    /// Read the length of a chunk's compressed entries from its fixed size prefix.
+    ///
+    pub(crate) fn read_chunk_entries_length(&self, file: &mut File, index: usize) -> Result<u32, Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let mut entries_length: [u8; 4] = [0x00u8; 4];
+        file.seek(SeekFrom::Start(offset + Chunk::ENTRIES_LENGTH_OFFSET as u64))?;
+        file.read_exact(&mut entries_length)?;
+        return Ok(u32::from_be_bytes(entries_length));
+    }
+    ///
+    /// Read a chunk's metadata sections, seeking past its compressed entries.
+    ///
+    pub(crate) fn read_chunk_sections(&self, file: &mut File, index: usize) -> Result<Vec<ChunkMetadataSection>, Error> {
+        let offset: u64 = self.chunk_file_offset(index)?;
+        let mut prefix_bytes: [u8; Chunk::PREFIX_LENGTH] = [0x00u8; Chunk::PREFIX_LENGTH];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut prefix_bytes)?;
+        let mut chunk_length: [u8; 4] = [0x00u8; 4];
+        let mut entries_length: [u8; 4] = [0x00u8; 4];
+        chunk_length.copy_from_slice(&prefix_bytes[Chunk::LENGTH_OFFSET..Chunk::TIMESTAMP_FROM_OFFSET]);
+        entries_length.copy_from_slice(&prefix_bytes[Chunk::ENTRIES_LENGTH_OFFSET..Chunk::PREFIX_LENGTH]);
+        let sections_offset: u64 = prefix_bytes.len() as u64 + u32::from_be_bytes(entries_length) as u64;
+        let chunk_length: u64 = u32::from_be_bytes(chunk_length) as u64;
+        if sections_offset > chunk_length {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Entries of chunk {} overrun the chunk", index),
+            ));
+        }
+        let mut section_bytes: Vec<u8> = vec![0x00u8; (chunk_length - sections_offset) as usize];
+        file.seek(SeekFrom::Start(offset + sections_offset))?;
+        file.read_exact(section_bytes.as_mut_slice())?;
+        let (mut tail, sections_length): (&[u8], u16) = match nom::number::complete::be_u16::<_, nom::error::Error<_>>(section_bytes.as_bytes()) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(
+                ErrorKind::InvalidData,
+                e.to_string(),
             )),
         };
+        let mut sections: Vec<ChunkMetadataSection> = Vec::with_capacity(sections_length as usize);
+        for _ in 0..sections_length {
+            let mut section: ChunkMetadataSection = ChunkMetadataSection::default();
+            tail = match section.parse_bytes::<&'_ [u8], nom::error::Error<_>>(tail) {
+                Ok(t) => t,
+                Err(e) => return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    e.to_string(),
+                )),
+            };
+            sections.push(section);
+        }
+        return Ok(sections);
+    }
+    ///
+    /// Indices of the chunks overlapping a time range that may hold entries for
+    /// the given target. Each chunk's bloom filter is read from its metadata
+    /// sections, so chunks ruled out are never decompressed.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    /// * target: Target the entries must belong to
+    ///
+    /// # Returns
+    /// `Result<Vec<usize>>`: Candidate chunk indices in store order
+    ///
+    pub fn chunks_for_target(&self, from: u64, to: u64, target: &[u8]) -> Result<Vec<usize>, Error> {
+        let mut file: File = File::open(self.path.as_str())?;
+        let mut indices: Vec<usize> = Vec::new();
+        for index in self.chunks_overlapping_range(from, to)? {
+            let sections: Vec<ChunkMetadataSection> = self.read_chunk_sections(&mut file, index)?;
+            let may_contain: bool = sections.iter()
+                .find(|s: &&ChunkMetadataSection| s.kind == ChunkMetadataSection::BLOOM_FILTER)
+                .and_then(ChunkBloomFilter::from_section)
+                .is_none_or(|f: ChunkBloomFilter| f.might_contain(target));
+            if may_contain {
+                indices.push(index);
+            }
+        }
+        return Ok(indices);
+    }
+    pub fn has_overlapping_chunks(&self) -> bool {
+        self.header.overlapping_chunks != 0
+    }
+    ///
+    /// Indices of the chunks whose time range overlaps the given range. While no
+    /// chunk overlaps an earlier one, chunk ranges are ordered and the first
+    /// candidate is found by binary search; otherwise every chunk is checked.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    ///
+    /// # Returns
+    /// `Result<Vec<usize>>`: Chunk indices in store order
+    ///
+    pub fn chunks_overlapping_range(&self, from: u64, to: u64) -> Result<Vec<usize>, Error> {
+        let mut file: File = File::open(self.path.as_str())?;
+        let chunk_count: usize = self.header.chunk_offsets.len();
+        let mut indices: Vec<usize> = Vec::new();
+        let mut start: usize = 0;
+        if !self.has_overlapping_chunks() {
+            let mut end: usize = chunk_count;
+            while start < end {
+                let middle: usize = start + (end - start) / 2;
+                if self.read_chunk_bounds(&mut file, middle)?.1 < from {
+                    start = middle + 1;
+                } else {
+                    end = middle;
+                }
+            }
+        }
+        for index in start..chunk_count {
+            let (timestamp_from, timestamp_to) = self.read_chunk_bounds(&mut file, index)?;
+            if timestamp_from > to && !self.has_overlapping_chunks() {
+                break;
+            }
+            if timestamp_from <= to && timestamp_to >= from {
+                indices.push(index);
+            }
+        }
+        return Ok(indices);
+    }
+    ///
+    /// Retire the sectors occupied by a chunk that the next header will no longer
+    /// reference. They are freed once no view of an older generation remains.
+    ///
+    pub(crate) fn retire_chunk(&mut self, file: &mut File, index: usize) -> Result<(), Error> {
+        let offset: u64 = self.chunk_file_offset(index)? - ChunkStoreHeaderSlot::DATA_OFFSET;
+        let length: u32 = self.read_chunk_length(file, index)?;
+        self.header.retire(self.generation() + 1, offset, length as u64);
+        return Ok(());
+    }
+    ///
+    /// Take a memory mapped snapshot of the store at its current generation.
+    ///
+    pub fn view(&self) -> Result<ChunkStoreView, Error> {
+        let file: File = File::open(self.path.as_str())?;
+        return ChunkStoreView::new(
+            &file,
+            self.header.clone(),
+            self.readers.acquire(self.generation())?,
+        );
+    }
+    ///
+    /// Append a sealed chunk to the end of the file and commit a header that
+    /// references it. Until the header slot is flipped the chunk is invisible, so
+    /// a crash part way through leaves the previous header in effect.
+    ///
+    /// # Arguments
+    /// * chunk: Sealed chunk to append
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result once the new header is durable
+    ///
+    pub fn append_chunk(&mut self, chunk: Chunk) -> Result<(), Error> {
+        let wal_sequence: u64 = self.header.wal_sequence;
+        return self.append_logged_chunk(chunk, wal_sequence);
+    }
+    ///
+    /// Append a chunk sealed from write-ahead log records, recording the last
+    /// record's sequence number in the same header commit, so the records are
+    /// skipped on replay if the log is not truncated after the commit.
+    ///
+    /// # Arguments
+    /// * chunk: Sealed chunk to append
+    /// * wal_sequence: Sequence number of the last log record in the chunk
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result once the header is committed
+    ///
+    pub fn append_logged_chunk(&mut self, chunk: Chunk, wal_sequence: u64) -> Result<(), Error> {
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        let chunk_offset: ChunkOffsets = self.write_chunk(&mut file, &chunk)?;
+        if !self.header.chunk_offsets.is_empty() && chunk.timestamp_from < self.latest_chunk.timestamp_to {
+            self.header.overlapping_chunks = 1;
+        }
+        self.header.push_chunk_offset(chunk_offset)?;
+        self.header.wal_sequence = wal_sequence;
+        self.commit_header(&mut file)?;
+        self.latest_chunk = chunk;
+        return Ok(());
+    }
+    ///
+    /// Replace the latest chunk with a re-sealed version of it. The new chunk is
+    /// written to the end of the file and the last offset repointed at it, so the
+    /// old chunk remains readable until the new header is committed.
+    ///
+    /// # Arguments
+    /// * chunk: Re-sealed chunk containing the latest chunk's entries and more
+    /// * wal_sequence: Sequence number of the last write-ahead log record in the chunk
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result once the new header is durable
+    ///
+    pub fn replace_latest_chunk(&mut self, chunk: Chunk, wal_sequence: u64) -> Result<(), Error> {
+        if self.header.chunk_offsets.is_empty() {
+            return self.append_logged_chunk(chunk, wal_sequence);
+        }
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        let last_index: usize = self.header.chunk_offsets.len() - 1;
+        if !self.has_overlapping_chunks() && last_index > 0
+            && chunk.timestamp_from < self.read_chunk_bounds(&mut file, last_index - 1)?.1 {
+            self.header.overlapping_chunks = 1;
+        }
+        let chunk_offset: ChunkOffsets = self.write_chunk(&mut file, &chunk)?;
+        self.retire_chunk(&mut file, last_index)?;
+        self.header.chunk_offsets[last_index] = chunk_offset;
+        self.header.wal_sequence = wal_sequence;
+        self.commit_header(&mut file)?;
+        self.latest_chunk = chunk;
+        return Ok(());
+    }
+    ///
+    /// Decode entries starting part way into a chunk, such as from a pagination
+    /// cursor holding a (chunk, entry) position.
+    ///
+    /// # Arguments
+    /// * chunk_index: Index of the chunk in the store
+    /// * entry_index: Position of the first entry within the chunk
+    /// * limit: Maximum number of entries to decode
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkEntry>>`: Decoded entries, empty if `entry_index` is past the end
+    ///
+    pub fn read_entries_at(&self, chunk_index: usize, entry_index: usize, limit: usize) -> Result<Vec<ChunkEntry>, Error> {
+        let chunk: Chunk = self.read_chunk(chunk_index)?;
+        return match chunk.decode_entries_from(entry_index, limit) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+    }
+    pub(crate) fn write_chunk(&mut self, file: &mut File, chunk: &Chunk) -> Result<ChunkOffsets, Error> {
+        let chunk_bytes: Vec<u8> = chunk.into_bytes();
+        let relative_offset: u64 = self.header.allocate(chunk_bytes.len() as u64)?;
+        file.seek(SeekFrom::Start(ChunkStoreHeaderSlot::DATA_OFFSET + relative_offset))?;
+        file.write_all(chunk_bytes.as_slice())?;
+        return ChunkOffsets::from_relative_offset(
+            relative_offset,
+            self.header.sector_size,
+        );
+    }
+    ///
+    /// Write the in-memory header to the end of the file and point the inactive
+    /// slot at it with the next generation, making it the active header.
+    ///
+    pub(crate) fn commit_header(&mut self, file: &mut File) -> Result<(), Error> {
+        self.header.release_retired(self.generation(), self.readers.oldest_generation()?);
+        // Reserve the new header's space before releasing the superseded one, so it
+        // can never be placed over the header that is still active. Freeing may add
+        // one range to the free list, so leave room for it.
+        let reserved_length: u64 = self.header.into_bytes().len() as u64 + FreeSectorRange::default().into_bytes().len() as u64;
+        let header_offset: u64 = ChunkStoreHeaderSlot::DATA_OFFSET + self.header.allocate(reserved_length)?;
+        if self.active_slot.is_initialised() && self.active_slot.header_offset >= ChunkStoreHeaderSlot::DATA_OFFSET {
+            self.header.free(
+                self.active_slot.header_offset - ChunkStoreHeaderSlot::DATA_OFFSET,
+                self.active_slot.header_length,
+            );
+        }
+        self.header.length = self.header.into_bytes().len() as u64;
+        let header_bytes: Vec<u8> = self.header.into_bytes();
+        file.seek(SeekFrom::Start(header_offset))?;
+        file.write_all(header_bytes.as_slice())?;
+        file.sync_data()?;
+        let slot_index: usize = 1 - self.active_slot_index as usize;
+        let slot: ChunkStoreHeaderSlot = ChunkStoreHeaderSlot::new(
+            self.active_slot.generation + 1,
+            header_offset,
+            header_bytes.as_slice(),
+        );
+        file.seek(SeekFrom::Start(ChunkStoreHeaderSlot::slot_offset(slot_index)))?;
+        file.write_all(slot.padded_bytes().as_slice())?;
+        file.sync_data()?;
+        self.active_slot = slot;
+        self.active_slot_index = slot_index as u8;
+        self.lease = Some(self.readers.acquire(self.generation())?);
+        // Readers of older generations, in this or any other process, may still
+        // map the space past the end of the data, so the file only shrinks once
+        // none remain
+        let data_end: u64 = ChunkStoreHeaderSlot::DATA_OFFSET + self.header.data_length;
+        let oldest_reader: Option<u64> = self.readers.oldest_generation()?;
+        if oldest_reader.is_none_or(|g: u64| g >= self.generation()) && file.metadata()?.len() > data_end {
+            file.set_len(data_end)?;
+        }
+        return Ok(());
     }
 }
 
@@ -119,4 +640,354 @@ impl Transcoder<LogStore> for ChunkStore {
     fn transcode(&self) -> Result<Box<LogStore>, encoding_errors::TranscoderError<LogStore>> {
         todo!("Implement transcoding for ChunkStore<->LogStore")
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::compressor::{CompressionCodec, Compressor};
+    use super::super::compaction::{CompactionConfig, CompactionSummary};
+    use crate::utils::test_utils::{chunk_of, entry_at, stored_timestamps, temp_store_path};
+    use super::super::legacy::baseline_chunk_store::{BaselineChunkOffsets, BaselineChunkPrefix, BaselineChunkStoreHeader};
+
+    #[test]
+    fn header_commits_reuse_freed_space() {
+        let path: String = temp_store_path("header_commits_reuse_freed_space");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, true).unwrap();
+        store.append_chunk(chunk_of(&[1])).unwrap();
+        let mut file: File = OpenOptions::new().read(true).write(true).open(path.as_str()).unwrap();
+        store.commit_header(&mut file).unwrap();
+        let settled_length: u64 = file.metadata().unwrap().len();
+        for _ in 0..100 {
+            store.commit_header(&mut file).unwrap();
+        }
+        assert_eq!(file.metadata().unwrap().len(), settled_length);
+        assert_eq!(ChunkStore::read_from_file(path.as_str()).unwrap().header.chunk_offsets.len(), 1);
+    }
+
+    fn baseline_entry_at(timestamp: u64) -> ChunkEntry {
+        return ChunkEntry {
+            timestamp,
+            action: 3,
+            target: b"baseline".to_vec(),
+            message: format!("baseline message {}", timestamp).into_bytes(),
+        };
+    }
+
+    ///
+    /// Bytes of a baseline chunk holding the given entries, with the entry
+    /// count in `entries_length` as the baseline writer stored it.
+    ///
+    fn baseline_chunk(entries: &[ChunkEntry]) -> Vec<u8> {
+        let mut raw_entries: Vec<u8> = Vec::new();
+        for entry in entries.iter() {
+            raw_entries.append(&mut entry.into_bytes());
+        }
+        let compressed: Vec<u8> = Compressor::with_codec(CompressionCodec::ZLIB_DEFAULT)
+            .compress_vec(&raw_entries)
+            .ok()
+            .expect("Could not compress entries");
+        let prefix: BaselineChunkPrefix = BaselineChunkPrefix {
+            length: (BaselineChunkStore::PREFIX_LENGTH as usize + compressed.len()) as u32,
+            timestamp_from: entries.first().map_or(0, |e: &ChunkEntry| e.timestamp),
+            timestamp_to: entries.last().map_or(0, |e: &ChunkEntry| e.timestamp),
+            entries_length: entries.len() as u32,
+        };
+        let mut bytes: Vec<u8> = prefix.into_bytes();
+        bytes.extend_from_slice(compressed.as_slice());
+        return bytes;
+    }
+
+    ///
+    /// Write a baseline store of the given chunk bytes, laid out back to back
+    /// in 30 byte sectors.
+    ///
+    fn write_baseline_store(path: &str, chunks: &[Vec<u8>]) {
+        let mut offset: usize = 0;
+        let mut chunk_offsets: Vec<BaselineChunkOffsets> = Vec::new();
+        for chunk in chunks.iter() {
+            chunk_offsets.push(BaselineChunkOffsets { sector_index: (offset / 30) as u32, sector_offset: (offset % 30) as u16 });
+            offset += chunk.len();
+        }
+        let mut header: BaselineChunkStoreHeader = BaselineChunkStoreHeader {
+            length: 0,
+            sector_size: 30,
+            chunk_count: chunks.len() as u16,
+            chunk_offsets_length: chunks.len() as u32,
+            chunk_offsets,
+        };
+        header.length = header.into_bytes().len() as u64;
+        let mut bytes: Vec<u8> = header.into_bytes();
+        bytes.extend_from_slice(&(chunks.len() as u64).to_be_bytes());
+        bytes.extend(chunks.concat());
+        std::fs::write(path, bytes.as_slice()).unwrap();
+    }
+
+    #[test]
+    fn reopens_current_format() {
+        let path: String = temp_store_path("reopens_current_format");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=3 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        let reopened: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(reopened.active_slot.format_version, ChunkStoreHeaderSlot::FORMAT_VERSION);
+        assert_eq!(reopened.generation(), store.generation());
+        assert_eq!(reopened.header.chunk_offsets.len(), 3);
+        for index in 0..3 {
+            let entries: Vec<ChunkEntry> = reopened.read_chunk(index).unwrap().decode_entries().ok().expect("Could not decode entries");
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].message, format!("message {}", index + 1).into_bytes());
+        }
+        assert_eq!(reopened.latest_chunk.timestamp_from, 3);
+    }
+
+    #[test]
+    fn migrates_baseline_store() {
+        let path: String = temp_store_path("migrates_baseline_store");
+        let chunks: Vec<Vec<u8>> = vec![
+            baseline_chunk(&[baseline_entry_at(10), baseline_entry_at(11), baseline_entry_at(12)]),
+            baseline_chunk(&[baseline_entry_at(20)]),
+        ];
+        write_baseline_store(path.as_str(), chunks.as_slice());
+
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(store.active_slot.format_version, ChunkStoreHeaderSlot::FORMAT_VERSION);
+        assert_eq!(store.header.sector_size, 30);
+        assert_eq!(store.header.chunk_offsets.len(), 2);
+        let first: Chunk = store.read_chunk(0).unwrap();
+        assert_eq!((first.timestamp_from, first.timestamp_to), (10, 12));
+        assert_eq!(first.decode_entries().ok().expect("Could not decode entries").len(), 3);
+        assert!(first.entry_index().is_some());
+        assert_eq!(store.latest_chunk.decode_entries().ok().expect("Could not decode entries")[0].message, baseline_entry_at(20).message);
+        assert_eq!(store.chunks_for_target(0, u64::MAX, b"baseline").unwrap(), vec![0, 1]);
+        assert!(!std::path::Path::new(format!("{}.migrating", path).as_str()).exists());
+
+        let reopened: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(reopened.generation(), store.generation());
+        assert_eq!(reopened.read_chunk(0).unwrap().decode_entries().ok().expect("Could not decode entries")[1].message, baseline_entry_at(11).message);
+    }
+
+    #[test]
+    fn migrates_out_of_order_baseline_chunk() {
+        let path: String = temp_store_path("migrates_out_of_order_baseline_chunk");
+        // The prefix bounds, taken from the first and last entries, do not cover the chunk
+        let chunks: Vec<Vec<u8>> = vec![baseline_chunk(&[
+            baseline_entry_at(30),
+            baseline_entry_at(10),
+            baseline_entry_at(25),
+            baseline_entry_at(20),
+        ])];
+        write_baseline_store(path.as_str(), chunks.as_slice());
+
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let chunk: Chunk = store.read_chunk(0).unwrap();
+        assert_eq!((chunk.timestamp_from, chunk.timestamp_to), (10, 30));
+        let entries: Vec<ChunkEntry> = chunk.decode_entries().ok().expect("Could not decode entries");
+        assert_eq!(entries.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>(), vec![10, 20, 25, 30]);
+        assert_eq!(chunk.position_at_or_after(21).ok().expect("Could not search entries"), Some(2));
+        assert_eq!(store.chunks_overlapping_range(0, 15).unwrap(), vec![0]);
+        assert_eq!(store.chunks_for_target(0, 15, b"baseline").unwrap(), vec![0]);
+        let later: Vec<u64> = store.read_entries_at(0, 2, usize::MAX).unwrap()
+            .iter()
+            .map(|e: &ChunkEntry| e.timestamp)
+            .collect();
+        assert_eq!(later, vec![25, 30]);
+    }
+
+    #[test]
+    fn migrates_sample_baseline_store() {
+        let path: String = temp_store_path("migrates_sample_baseline_store");
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/baseline_chunk_store.bin"), path.as_str()).unwrap();
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(store.header.chunk_offsets.len(), 2);
+        for index in 0..2 {
+            let entries: Vec<ChunkEntry> = store.read_chunk(index).unwrap().decode_entries().ok().expect("Could not decode entries");
+            assert_eq!(entries.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>(), vec![0, 1]);
+        }
+    }
+
+    #[test]
+    fn rejects_zero_sector_size() {
+        let path: String = temp_store_path("rejects_zero_sector_size");
+        let error: Error = ChunkStore::create(path.as_str(), 0, true).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(!std::path::Path::new(path.as_str()).exists());
+
+        let header_bytes: Vec<u8> = ChunkStoreHeader::default().into_bytes();
+        let slot: ChunkStoreHeaderSlot = ChunkStoreHeaderSlot::new(1, ChunkStoreHeaderSlot::DATA_OFFSET, header_bytes.as_slice());
+        let mut bytes: Vec<u8> = slot.into_bytes();
+        bytes.resize(ChunkStoreHeaderSlot::DATA_OFFSET as usize, 0x00u8);
+        bytes.extend_from_slice(header_bytes.as_slice());
+        std::fs::write(path.as_str(), bytes.as_slice()).unwrap();
+        assert_eq!(ChunkStore::read_from_file(path.as_str()).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn views_survive_compaction_by_another_instance() {
+        let path: String = temp_store_path("views_survive_compaction_by_another_instance");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=6 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        drop(store);
+        let reader: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let view: ChunkStoreView = reader.view().unwrap();
+        let before: Vec<Vec<u8>> = (0..view.chunk_count())
+            .map(|i: usize| view.chunk_bytes(i).unwrap().to_vec())
+            .collect();
+
+        let mut compactor: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let summary: CompactionSummary = compactor.compact(&CompactionConfig::default()).unwrap();
+        assert!(summary.chunks_merged > 0);
+        for timestamp in 7..=12 {
+            compactor.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        assert!(!compactor.header.retired_regions.is_empty());
+        for (index, bytes) in before.iter().enumerate() {
+            assert_eq!(view.chunk_bytes(index).unwrap(), bytes.as_slice());
+            assert_eq!(reader.read_chunk(index).unwrap().decode_entries().ok().expect("Could not decode entries")[0].timestamp, index as u64 + 1);
+        }
+
+        drop(view);
+        drop(reader);
+        compactor.append_chunk(chunk_of(&[13])).unwrap();
+        assert!(compactor.header.retired_regions.is_empty());
+    }
+
+    fn sorted_stored_timestamps(path: &str) -> Vec<u64> {
+        let mut timestamps: Vec<u64> = stored_timestamps(&ChunkStore::read_from_file(path).unwrap());
+        timestamps.sort();
+        return timestamps;
+    }
+
+    #[test]
+    fn stale_instance_keeps_chunks_committed_by_another() {
+        let path: String = temp_store_path("stale_instance_keeps_chunks_committed_by_another");
+        let mut first: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        first.append_chunk(chunk_of(&[1])).unwrap();
+        let mut second: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        second.append_chunk(chunk_of(&[2])).unwrap();
+        // The first instance's header predates the second's commit
+        first.append_chunk(chunk_of(&[3])).unwrap();
+        assert_eq!(first.header.chunk_offsets.len(), 3);
+        assert_eq!(first.latest_chunk.timestamp_from, 3);
+        assert_eq!(sorted_stored_timestamps(path.as_str()), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn concurrent_instances_keep_each_others_chunks() {
+        let path: String = temp_store_path("concurrent_instances_keep_each_others_chunks");
+        drop(ChunkStore::create(path.as_str(), 64, false).unwrap());
+        let threads: Vec<std::thread::JoinHandle<()>> = [0u64, 1000u64].iter()
+            .map(|base: &u64| {
+                let (path, base): (String, u64) = (path.clone(), *base);
+                std::thread::spawn(move || {
+                    let mut store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+                    for timestamp in base..(base + 20) {
+                        store.append_chunk(chunk_of(&[timestamp])).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads.into_iter() {
+            thread.join().unwrap();
+        }
+        let expected: Vec<u64> = (0..20).chain(1000..1020).collect();
+        assert_eq!(sorted_stored_timestamps(path.as_str()), expected);
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(store.header.chunk_count, 40);
+    }
+
+    #[test]
+    fn reads_chunk_prefix_in_place() {
+        let path: String = temp_store_path("reads_chunk_prefix_in_place");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        let chunk: Chunk = chunk_of(&[7]);
+        let bytes: Vec<u8> = chunk.into_bytes();
+        assert_eq!(&bytes[Chunk::PREFIX_LENGTH..Chunk::PREFIX_LENGTH + chunk.entries.len()], chunk.entries.as_slice());
+        store.append_chunk(chunk.clone()).unwrap();
+        let mut file: File = File::open(path.as_str()).unwrap();
+        assert_eq!(store.read_chunk_bounds(&mut file, 0).unwrap(), (7, 7));
+        assert_eq!(store.read_chunk_codec(&mut file, 0).unwrap(), chunk.codec);
+        assert_eq!(store.read_chunk_sections(&mut file, 0).unwrap().len(), chunk.sections.len());
+        assert_eq!(store.view().unwrap().chunk_bytes(0).unwrap(), bytes.as_slice());
+    }
+
+    fn chunk_of_targets(targets: &[&str], timestamp_from: u64) -> Chunk {
+        let entries: Vec<ChunkEntry> = targets.iter().enumerate()
+            .map(|(i, target): (usize, &&str)| ChunkEntry {
+                target: target.as_bytes().to_vec(),
+                message: format!("message {}", i).into_bytes(),
+                ..entry_at(timestamp_from + 10 * i as u64)
+            })
+            .collect();
+        return Chunk::seal(entries.as_slice()).ok().expect("Could not seal chunk");
+    }
+
+    #[test]
+    fn reads_entries_by_position_and_timestamp() {
+        let path: String = temp_store_path("reads_entries_by_position_and_timestamp");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        let targets: Vec<String> = (0..10).map(|i: usize| format!("target-{}", i)).collect();
+        let targets: Vec<&str> = targets.iter().map(String::as_str).collect();
+        store.append_chunk(chunk_of_targets(targets.as_slice(), 10)).unwrap();
+        // The same entries without an entry index are parsed from the start and skipped
+        let mut unindexed: Chunk = chunk_of_targets(targets.as_slice(), 10);
+        unindexed.sections.retain(|s: &ChunkMetadataSection| s.kind != ChunkMetadataSection::ENTRY_INDEX);
+        unindexed.sections_length = unindexed.sections.len() as u16;
+        unindexed.length = unindexed.into_bytes().len() as u32;
+        store.append_chunk(unindexed).unwrap();
+        assert!(store.read_chunk(0).unwrap().entry_index().is_some());
+        assert!(store.read_chunk(1).unwrap().entry_index().is_none());
+
+        for chunk_index in 0..2 {
+            let timestamps = |entries: Vec<ChunkEntry>| entries.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>();
+            assert_eq!(timestamps(store.read_entries_at(chunk_index, 3, 2).unwrap()), vec![40, 50]);
+            assert_eq!(timestamps(store.read_entries_at(chunk_index, 8, usize::MAX).unwrap()), vec![90, 100]);
+            assert_eq!(store.read_entries_at(chunk_index, 0, usize::MAX).unwrap()[4].target, b"target-4".to_vec());
+            assert!(store.read_entries_at(chunk_index, 10, 1).unwrap().is_empty());
+
+            let chunk: Chunk = store.read_chunk(chunk_index).unwrap();
+            let position = |timestamp: u64| chunk.position_at_or_after(timestamp).ok().expect("Could not decode entries");
+            assert_eq!(position(0), Some(0));
+            assert_eq!(position(10), Some(0));
+            assert_eq!(position(35), Some(3));
+            assert_eq!(position(40), Some(3));
+            assert_eq!(position(100), Some(9));
+            assert_eq!(position(101), None);
+        }
+        assert_eq!(store.read_entries_at(2, 0, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn prunes_targets_by_bloom_filter_without_false_negatives() {
+        let path: String = temp_store_path("prunes_targets_by_bloom_filter_without_false_negatives");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        let mut chunk_targets: Vec<Vec<String>> = Vec::new();
+        for chunk_index in 0..20 {
+            let mut targets: Vec<String> = (0..50).map(|i: usize| format!("host-{}-{}", chunk_index, i)).collect();
+            if chunk_index % 2 == 0 {
+                targets.push(String::from("shared"));
+            }
+            let target_refs: Vec<&str> = targets.iter().map(String::as_str).collect();
+            store.append_chunk(chunk_of_targets(target_refs.as_slice(), 1000 * chunk_index as u64)).unwrap();
+            chunk_targets.push(targets);
+        }
+        for (chunk_index, targets) in chunk_targets.iter().enumerate() {
+            for target in targets.iter() {
+                let chunks: Vec<usize> = store.chunks_for_target(0, u64::MAX, target.as_bytes()).unwrap();
+                assert!(chunks.contains(&chunk_index), "{} not found in chunk {}", target, chunk_index);
+            }
+        }
+        let shared: Vec<usize> = store.chunks_for_target(0, u64::MAX, b"shared").unwrap();
+        assert!((0..20).step_by(2).all(|i: usize| shared.contains(&i)));
+        // Absent targets rule out nearly every chunk at the default 1% rate
+        let false_positives: usize = (0..200)
+            .map(|i: usize| store.chunks_for_target(0, u64::MAX, format!("absent-{}", i).as_bytes()).unwrap().len())
+            .sum();
+        assert!(false_positives < 4000 / 25, "{} false positives in 4000 checks", false_positives);
+        // Chunks outside the range are not considered at all
+        assert_eq!(store.chunks_for_target(2000, 2999, b"host-2-0").unwrap(), vec![2]);
+        assert!(store.chunks_for_target(2000, 2999, b"host-3-0").unwrap().is_empty());
+    }
+}
@@ -1,8 +1,12 @@
-use crate::{byte_layout, Chunk, ChunkStore, reify};
+use crate::{byte_layout, reify};
+use crate::data::representational::chunk::Chunk;
 use super::chunk_offsets::ChunkOffsets;
+use super::free_sector_range::FreeSectorRange;
+use super::retired_region::RetiredRegion;
+use super::archive::archive_reference::ArchiveReference;
+use super::chunk_store_header_slot::ChunkStoreHeaderSlot;
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, ErrorKind, Read, Seek};
 use memmap::{Mmap, MmapOptions};
 use nom::AsBytes;
 
@@ -12,23 +16,53 @@ reify!{
     pub struct ChunkStoreHeader {
         #[byte_size=8]
         pub length: u64,
-        #[byte_size=2]
-        pub sector_size: u16,
-        #[byte_size=2]
-        pub chunk_count: u16,
         #[byte_size=4]
-        pub chunk_offsets_length: u32,
+        pub sector_size: u32,
+        #[byte_size=1]
+        pub align_chunks: u8,
+        #[byte_size=1]
+        pub overlapping_chunks: u8,
+        #[byte_size=8]
+        pub chunk_epoch: u64,
+        #[byte_size=8]
+        pub wal_sequence: u64,
+        #[byte_size=8]
+        pub chunk_count: u64,
+        #[byte_size=8]
+        pub chunk_offsets_length: u64,
         pub chunk_offsets: Vec<ChunkOffsets>,
+        #[byte_size=8]
+        pub data_length: u64,
+        #[byte_size=8]
+        pub free_sectors_length: u64,
+        pub free_sectors: Vec<FreeSectorRange>,
+        #[byte_size=8]
+        pub retired_regions_length: u64,
+        pub retired_regions: Vec<RetiredRegion>,
+        #[byte_size=8]
+        pub archives_length: u64,
+        pub archives: Vec<ArchiveReference>,
     }
 }
 
 byte_layout! {
     ChunkStoreHeader
     value [length, u64, Big]
-    value [sector_size, u16, Big]
-    value [chunk_count, u16, Big]
-    value [chunk_offsets_length, u32, Big]
+    value [sector_size, u32, Big]
+    value [align_chunks, u8]
+    value [overlapping_chunks, u8]
+    value [chunk_epoch, u64, Big]
+    value [wal_sequence, u64, Big]
+    value [chunk_count, u64, Big]
+    value [chunk_offsets_length, u64, Big]
     composite_vec [chunk_offsets, chunk_offsets_length, ChunkOffsets]
+    value [data_length, u64, Big]
+    value [free_sectors_length, u64, Big]
+    composite_vec [free_sectors, free_sectors_length, FreeSectorRange]
+    value [retired_regions_length, u64, Big]
+    composite_vec [retired_regions, retired_regions_length, RetiredRegion]
+    value [archives_length, u64, Big]
+    composite_vec [archives, archives_length, ArchiveReference]
 }
 
 impl ChunkStoreHeader {
@@ -57,40 +91,165 @@ impl ChunkStoreHeader {
             Err(_) => Ok(0),
         };
     }
-    pub fn read_from_file(&mut self, buf_reader: &mut BufReader<&File>) -> Result<(), io::Error> {
-        let header_length: usize = match Self::header_length() {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        let mut length_bytes: Vec<u8> = Vec::with_capacity(header_length);
-        buf_reader.by_ref()
-            .take(header_length as u64)
-            .read_to_end(&mut length_bytes)?;
-        let sized_length_bytes: u64 = match nom::number::complete::be_u64::<_, nom::error::Error<_>>(length_bytes.as_bytes()) {
-            Ok((_, v)) => v,
-            Err(e) => return Err(io::Error::new(
+    pub fn push_chunk_offset(&mut self, offset: ChunkOffsets) -> Result<(), io::Error> {
+        let (chunk_count, chunk_offsets_length) = match (self.chunk_count.checked_add(1), self.chunk_offsets_length.checked_add(1)) {
+            (Some(c), Some(l)) => (c, l),
+            _ => return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                e.to_string(),
+                format!("Chunk count overflows at {} chunks", self.chunk_count),
             )),
         };
-        let mut header_bytes: Vec<u8> = Vec::with_capacity(sized_length_bytes as usize);
-        buf_reader.rewind()?;
-        buf_reader.take(sized_length_bytes as u64)
-            .read_to_end(&mut header_bytes)?;
-        return match self.parse_bytes::<&'_ [u8], nom::error::Error<_>>(header_bytes.as_bytes()) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(io::Error::new(
-                ErrorKind::InvalidInput,
-                e.to_string()
+        self.chunk_offsets.push(offset);
+        self.chunk_count = chunk_count;
+        self.chunk_offsets_length = chunk_offsets_length;
+        return Ok(());
+    }
+    ///
+    /// Reserve space for a region of the given length in the data region. Freed
+    /// sectors are reused first-fit, otherwise the region is placed at the end of
+    /// the data, on a sector boundary when the store aligns chunks.
+    ///
+    /// # Arguments
+    /// * length: Number of bytes to reserve
+    ///
+    /// # Returns
+    /// `Result<u64>`: Offset of the region relative to the start of the data region
+    ///
+    pub fn allocate(&mut self, length: u64) -> Result<u64, io::Error> {
+        let sector_size: u64 = self.sector_size as u64;
+        let sectors_needed: u64 = length.div_ceil(sector_size);
+        let reusable: Option<usize> = self.free_sectors.iter()
+            .position(|r: &FreeSectorRange| r.sector_count >= sectors_needed);
+        if let (Some(index), true) = (reusable, sectors_needed > 0) {
+            let range: &mut FreeSectorRange = &mut self.free_sectors[index];
+            let sector_index: u64 = range.sector_index;
+            range.sector_index += sectors_needed;
+            range.sector_count -= sectors_needed;
+            if range.sector_count == 0 {
+                self.free_sectors.remove(index);
+            }
+            self.free_sectors_length = self.free_sectors.len() as u64;
+            return match sector_index.checked_mul(sector_size) {
+                Some(v) => Ok(v),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Offset of sector {} overflows", sector_index),
+                )),
+            };
+        }
+        return self.allocate_at_end(length);
+    }
+    pub fn allocate_at_end(&mut self, length: u64) -> Result<u64, io::Error> {
+        let sector_size: u64 = self.sector_size as u64;
+        let offset: Option<u64> = if self.align_chunks != 0 {
+            self.data_length
+                .checked_add(sector_size - 1)
+                .map(|v: u64| v / sector_size * sector_size)
+        } else {
+            Some(self.data_length)
+        };
+        let data_length: Option<u64> = offset.and_then(|o: u64| o.checked_add(length));
+        return match (offset, data_length) {
+            (Some(o), Some(l)) => {
+                self.data_length = l;
+                Ok(o)
+            },
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Data region overflows allocating {} bytes after {}", length, self.data_length),
             )),
         };
     }
+    ///
+    /// Return the sectors of a region to the free list. Sectors only partially
+    /// covered by the region may be shared with a neighbour in a packed store and
+    /// are kept, while an aligned store owns the whole trailing sector. Freed
+    /// sectors at the end of the data shrink the data region instead.
+    ///
+    /// # Arguments
+    /// * offset: Start of the region relative to the start of the data region
+    /// * length: Length of the region in bytes
+    ///
+    pub fn free(&mut self, offset: u64, length: u64) {
+        let sector_size: u64 = self.sector_size as u64;
+        let end: u64 = offset.saturating_add(length);
+        let first_sector: u64 = offset.div_ceil(sector_size);
+        let end_sector: u64 = if self.align_chunks != 0 && offset.is_multiple_of(sector_size) {
+            end.div_ceil(sector_size)
+        } else {
+            end / sector_size
+        };
+        if end_sector <= first_sector {
+            return;
+        }
+        self.free_sectors.push(FreeSectorRange {
+            sector_index: first_sector,
+            sector_count: end_sector - first_sector,
+        });
+        self.free_sectors.sort_by_key(|r: &FreeSectorRange| r.sector_index);
+        let mut merged: Vec<FreeSectorRange> = Vec::with_capacity(self.free_sectors.len());
+        for range in self.free_sectors.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end_sector() >= range.sector_index => {
+                    let end_sector: u64 = last.end_sector().max(range.end_sector());
+                    last.sector_count = end_sector - last.sector_index;
+                },
+                _ => merged.push(range),
+            }
+        }
+        // Give back a free run at the end of the data instead of tracking it
+        if let Some(last) = merged.last() {
+            if last.end_sector() * sector_size >= self.data_length {
+                self.data_length = last.sector_index * sector_size;
+                merged.pop();
+            }
+        }
+        self.free_sectors = merged;
+        self.free_sectors_length = self.free_sectors.len() as u64;
+    }
+    ///
+    /// Mark a region as no longer referenced from the given generation onwards.
+    /// It is moved to the free list by `release_retired` once no reader can still
+    /// see it.
+    ///
+    pub fn retire(&mut self, generation: u64, offset: u64, length: u64) {
+        self.retired_regions.push(RetiredRegion {
+            generation,
+            offset,
+            length,
+        });
+        self.retired_regions_length = self.retired_regions.len() as u64;
+    }
+    ///
+    /// Record that chunks were removed or merged, moving the chunks after them to
+    /// new indices. Rewriting a chunk in place with the same entries, or adding
+    /// chunks at the end, leaves the epoch alone, so data keyed by chunk index
+    /// stays valid across those.
+    ///
+    pub fn renumber_chunks(&mut self) {
+        self.chunk_epoch += 1;
+    }
+    pub fn release_retired(&mut self, committed_generation: u64, oldest_reader_generation: Option<u64>) {
+        let (releasable, retained): (Vec<RetiredRegion>, Vec<RetiredRegion>) = self.retired_regions
+            .drain(..)
+            .partition(|r: &RetiredRegion| r.is_releasable(committed_generation, oldest_reader_generation));
+        self.retired_regions = retained;
+        self.retired_regions_length = self.retired_regions.len() as u64;
+        for region in releasable.iter() {
+            self.free(region.offset, region.length);
+        }
+    }
+    pub fn free_bytes(&self) -> u64 {
+        self.free_sectors.iter()
+            .map(|r: &FreeSectorRange| r.sector_count * self.sector_size as u64)
+            .sum()
+    }
     pub fn string_format_chunk_sector_ratio(&self, file: &File) -> Result<String, io::Error> {
         let mut chunks_outer: String = String::new();
         let mut chunks: String = String::new();
         for i in 0..(self.chunk_offsets_length - 1) {
             let next_offset: &ChunkOffsets = self.chunk_offsets.get(i as usize + 1).unwrap();
-            let chunk_size: u32 = (next_offset.sector_index * self.sector_size as u32) + next_offset.sector_offset as u32;
+            let chunk_size: u64 = next_offset.calculate_offset(self.sector_size)?;
             chunks_outer.push('+');
             chunks_outer.push_str("-".repeat(chunk_size as usize -1).as_str());
             let mut chunk_str: String = String::new();
@@ -105,20 +264,10 @@ impl ChunkStoreHeader {
             chunks.push_str(chunk_str.as_str());
         }
         let last_offset: &ChunkOffsets = self.chunk_offsets.get((self.chunk_offsets_length - 1) as usize).unwrap();
-        let chunk_length: u32 = match Chunk::header_length() {
-            Ok(v) => v,
-            Err(e) => return Err(e),
-        };
-        let chunk_store_chunks_length_bytes_length: u64 = match ChunkStore::chunks_length_bytes_length() {
-            Ok(v) => v,
-            Err(e) => return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                e.to_string(),
-            )),
-        };
+        let chunk_length: u32 = Chunk::header_length()?;
         let mmap_file: Mmap = unsafe {
             MmapOptions::new()
-                .offset(self.length + chunk_store_chunks_length_bytes_length + (last_offset.sector_index as u64 * self.sector_size as u64) + last_offset.sector_offset as u64)
+                .offset(ChunkStoreHeaderSlot::DATA_OFFSET + last_offset.calculate_offset(self.sector_size)?)
                 .len(chunk_length as usize)
                 .map(file)?
         };
@@ -132,7 +281,7 @@ impl ChunkStoreHeader {
 
         let mut sector_markers_outer: String = String::new();
         let mut sector_markers: String = String::new();
-        for i in 0..(last_offset.sector_index + (last_chunk_length / self.sector_size as u32) + 1) {
+        for i in 0..(last_offset.sector_index + (last_chunk_length / self.sector_size) as u64 + 1) {
             sector_markers_outer.push('+');
             sector_markers_outer.push_str("-".repeat((self.sector_size - 1) as usize).as_str());
             sector_markers.push('|');
@@ -0,0 +1,86 @@
+use flate2::Crc;
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct ChunkStoreHeaderSlot {
+        #[byte_size=4]
+        pub magic: u32,
+        #[byte_size=2]
+        pub format_version: u16,
+        #[byte_size=8]
+        pub generation: u64,
+        #[byte_size=8]
+        pub header_offset: u64,
+        #[byte_size=8]
+        pub header_length: u64,
+        #[byte_size=4]
+        pub checksum: u32,
+    }
+}
+
+byte_layout!{
+    ChunkStoreHeaderSlot
+    value [magic, u32, Big]
+    value [format_version, u16, Big]
+    value [generation, u64, Big]
+    value [header_offset, u64, Big]
+    value [header_length, u64, Big]
+    value [checksum, u32, Big]
+}
+
+///
+/// One of the two fixed-position slots at the start of a store file. A slot
+/// points at a serialised `ChunkStoreHeader` elsewhere in the file and carries a
+/// checksum over both itself and that header, so a partially written slot or
+/// header is never mistaken for a valid one. The slot with the highest
+/// generation that validates is the active header.
+///
+impl ChunkStoreHeaderSlot {
+    pub const MAGIC: u32 = 0x43484B4C; // "CHKL"
+    pub const FORMAT_VERSION: u16 = 1;
+    /// Reserved bytes per slot, leaving room for the slot to grow
+    pub const SLOT_LENGTH: u64 = 64;
+    pub const SLOT_COUNT: u64 = 2;
+    /// Start of sector 0, immediately after both slots
+    pub const DATA_OFFSET: u64 = Self::SLOT_LENGTH * Self::SLOT_COUNT;
+
+    pub fn new(generation: u64, header_offset: u64, header_bytes: &[u8]) -> ChunkStoreHeaderSlot {
+        let mut slot: ChunkStoreHeaderSlot = ChunkStoreHeaderSlot {
+            magic: Self::MAGIC,
+            format_version: Self::FORMAT_VERSION,
+            generation,
+            header_offset,
+            header_length: header_bytes.len() as u64,
+            checksum: 0,
+        };
+        slot.checksum = slot.compute_checksum(header_bytes);
+        return slot;
+    }
+    pub fn slot_offset(index: usize) -> u64 {
+        index as u64 * Self::SLOT_LENGTH
+    }
+    pub fn compute_checksum(&self, header_bytes: &[u8]) -> u32 {
+        let mut crc: Crc = Crc::new();
+        crc.update(&self.magic.to_be_bytes());
+        crc.update(&self.format_version.to_be_bytes());
+        crc.update(&self.generation.to_be_bytes());
+        crc.update(&self.header_offset.to_be_bytes());
+        crc.update(&self.header_length.to_be_bytes());
+        crc.update(header_bytes);
+        return crc.sum();
+    }
+    pub fn is_initialised(&self) -> bool {
+        self.magic == Self::MAGIC
+    }
+    pub fn is_valid_for(&self, header_bytes: &[u8]) -> bool {
+        self.is_initialised()
+            && header_bytes.len() as u64 == self.header_length
+            && self.compute_checksum(header_bytes) == self.checksum
+    }
+    pub fn padded_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.into_bytes();
+        bytes.resize(Self::SLOT_LENGTH as usize, 0x00);
+        return bytes;
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use memmap::{Mmap, MmapOptions};
+use crate::data::representational::chunk::Chunk;
+use super::chunk_store::ChunkStore;
+use super::chunk_store_header::ChunkStoreHeader;
+use super::chunk_store_header_slot::ChunkStoreHeaderSlot;
+use super::reader_registry::ReadLease;
+
+///
+/// A read-only, memory mapped snapshot of a store at one header generation.
+/// Space referenced by the snapshot is not reused while the view is alive, so
+/// reads stay valid even if the store is compacted or trimmed concurrently.
+///
+pub struct ChunkStoreView {
+    pub header: ChunkStoreHeader,
+    pub generation: u64,
+    mmap: Mmap,
+    _lease: ReadLease,
+}
+
+impl ChunkStoreView {
+    pub fn new(file: &File, header: ChunkStoreHeader, lease: ReadLease) -> Result<ChunkStoreView, Error> {
+        let mmap: Mmap = unsafe { MmapOptions::new().map(file)? };
+        return Ok(ChunkStoreView {
+            header,
+            generation: lease.generation,
+            mmap,
+            _lease: lease,
+        });
+    }
+    pub fn chunk_count(&self) -> usize {
+        self.header.chunk_offsets.len()
+    }
+    pub fn chunk_bytes(&self, index: usize) -> Result<&[u8], Error> {
+        let offset: u64 = match self.header.chunk_offsets.get(index) {
+            Some(o) => ChunkStoreHeaderSlot::DATA_OFFSET + o.calculate_offset(self.header.sector_size)?,
+            None => return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("No chunk at index {}", index),
+            )),
+        };
+        let start: usize = offset as usize;
+        let length_bytes: &[u8] = match self.mmap.get(start + Chunk::LENGTH_OFFSET..start + Chunk::TIMESTAMP_FROM_OFFSET) {
+            Some(v) => v,
+            None => return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Chunk {} lies outside the mapped store", index),
+            )),
+        };
+        let length: usize = u32::from_be_bytes([length_bytes[0], length_bytes[1], length_bytes[2], length_bytes[3]]) as usize;
+        return match self.mmap.get(start..start + length) {
+            Some(v) => Ok(v),
+            None => Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Chunk {} lies outside the mapped store", index),
+            )),
+        };
+    }
+    pub fn read_chunk(&self, index: usize) -> Result<Chunk, Error> {
+        return ChunkStore::parse_chunk(self.chunk_bytes(index)?);
+    }
+}
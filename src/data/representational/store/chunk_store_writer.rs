@@ -0,0 +1,695 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use crate::cache::cache::Cache;
+use crate::cache::write_ahead_log::{FsyncPolicy, WriteAheadLog};
+use crate::compression::compressor::CompressionCodec;
+use crate::configuration::config::Config;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use super::archive::archival::{ArchiveConfig, ArchiveSummary, PlannedArchive};
+use super::chunk_store::ChunkStore;
+use super::compaction::{CompactedRun, CompactionConfig, CompactionSummary};
+use super::index::inverted_index::InvertedIndex;
+use super::recompression::{RecompressedChunk, RecompressionConfig, RecompressionSummary};
+use super::retention::RetentionConfig;
+use super::subscription::Subscriber;
+
+///
+/// What a writer does with an entry older than the newest entry already sealed
+/// into the store, which would otherwise be written into a chunk whose time
+/// range overlaps an earlier one.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LateEntryPolicy {
+    /// Accept the entry, marking the store as holding overlapping chunks
+    ACCEPT,
+    /// Refuse the entry with an error to the caller
+    REJECT,
+    /// Discard the entry with a warning
+    DROP,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkStoreWriterConfig {
+    pub sector_size: u32,
+    pub align_chunks: bool,
+    pub max_chunk_entries: usize,
+    pub max_chunk_bytes: usize,
+    pub max_chunk_age: Duration,
+    pub resume_latest_chunk: bool,
+    pub fsync_policy: FsyncPolicy,
+    pub late_entry_policy: LateEntryPolicy,
+    /// Codec chunks are sealed with, favouring ingest speed
+    pub ingest_codec: CompressionCodec,
+    /// False positive rate of each chunk's target and trigram bloom filters
+    pub bloom_false_positive_rate: f64,
+    /// Recompression of aged chunks run by the writer's maintenance, disabled if `None`
+    pub recompression: Option<RecompressionConfig>,
+    /// Compaction of small chunks run by the writer's maintenance, disabled if `None`
+    pub compaction: Option<CompactionConfig>,
+    /// Archival of cold chunks run by the writer's maintenance, disabled if `None`
+    pub archive: Option<ArchiveConfig>,
+    /// Limits on the data kept, enforced from `tick`
+    pub retention: RetentionConfig,
+    /// Maintain an inverted index over entry messages as chunks are sealed
+    pub full_text_index: bool,
+    /// Entries each subscription can hold unread before new ones are dropped
+    pub subscription_buffer: usize,
+}
+
+impl Default for ChunkStoreWriterConfig {
+    fn default() -> Self {
+        ChunkStoreWriterConfig {
+            sector_size: 4096,
+            align_chunks: true,
+            max_chunk_entries: 4096,
+            max_chunk_bytes: 1024 * 1024,
+            max_chunk_age: Duration::from_millis(2000),
+            resume_latest_chunk: true,
+            fsync_policy: FsyncPolicy::INTERVAL(Duration::from_millis(100)),
+            late_entry_policy: LateEntryPolicy::ACCEPT,
+            ingest_codec: CompressionCodec::ZLIB_FAST,
+            bloom_false_positive_rate: ChunkBloomFilter::DEFAULT_FALSE_POSITIVE_RATE,
+            recompression: Some(RecompressionConfig::default()),
+            compaction: Some(CompactionConfig::default()),
+            archive: None,
+            retention: RetentionConfig::default(),
+            full_text_index: false,
+            subscription_buffer: 1024,
+        }
+    }
+}
+
+impl ChunkStoreWriterConfig {
+    pub fn from_config(config: &mut Config) -> ChunkStoreWriterConfig {
+        let defaults: ChunkStoreWriterConfig = ChunkStoreWriterConfig::default();
+        let fsync_interval_ms: u64 = config.get_or_default("store.wal.fsync_interval_ms", 100u64);
+        let fsync_policy: FsyncPolicy = match config.get_or_default("store.wal.fsync_policy", String::from("interval")).as_str() {
+            "entry" => FsyncPolicy::EVERY_ENTRY,
+            "os" => FsyncPolicy::OS_MANAGED,
+            "interval" => FsyncPolicy::INTERVAL(Duration::from_millis(fsync_interval_ms)),
+            other => {
+                warn!(crate::LOGGER, "Unknown WAL fsync policy {}, defaulting to interval", other);
+                FsyncPolicy::INTERVAL(Duration::from_millis(fsync_interval_ms))
+            },
+        };
+        let late_entry_policy: LateEntryPolicy = match config.get_or_default("store.chunk.late_entry_policy", String::from("accept")).as_str() {
+            "accept" => LateEntryPolicy::ACCEPT,
+            "reject" => LateEntryPolicy::REJECT,
+            "drop" => LateEntryPolicy::DROP,
+            other => {
+                warn!(crate::LOGGER, "Unknown late entry policy {}, defaulting to accept", other);
+                defaults.late_entry_policy
+            },
+        };
+        let ingest_codec: CompressionCodec = match CompressionCodec::from_name(
+            config.get_or_default("store.chunk.codec", String::from("zlib_fast")).as_str(),
+        ) {
+            Some(c) => c,
+            None => {
+                warn!(crate::LOGGER, "Unknown chunk codec, defaulting to zlib_fast");
+                defaults.ingest_codec
+            },
+        };
+        let sector_size: u32 = match config.get_or_default("store.sector_size", defaults.sector_size) {
+            0 => {
+                warn!(crate::LOGGER, "Sector size must be non-zero, defaulting to {}", defaults.sector_size);
+                defaults.sector_size
+            },
+            s => s,
+        };
+        let recompression: Option<RecompressionConfig> = if config.get_or_default("store.recompression.enabled", true) {
+            Some(RecompressionConfig::from_config(config))
+        } else {
+            None
+        };
+        let compaction: Option<CompactionConfig> = if config.get_or_default("store.compaction.enabled", true) {
+            Some(CompactionConfig::from_config(config))
+        } else {
+            None
+        };
+        let archive: Option<ArchiveConfig> = if config.get_or_default("store.archive.enabled", false) {
+            Some(ArchiveConfig::from_config(config))
+        } else {
+            None
+        };
+        ChunkStoreWriterConfig {
+            sector_size,
+            align_chunks: config.get_or_default("store.align_chunks", defaults.align_chunks),
+            max_chunk_entries: config.get_or_default("store.chunk.max_entries", defaults.max_chunk_entries),
+            max_chunk_bytes: config.get_or_default("store.chunk.max_bytes", defaults.max_chunk_bytes),
+            max_chunk_age: Duration::from_millis(config.get_or_default(
+                "store.chunk.max_age_ms",
+                defaults.max_chunk_age.as_millis() as u64,
+            )),
+            resume_latest_chunk: config.get_or_default("store.chunk.resume_latest", defaults.resume_latest_chunk),
+            fsync_policy,
+            late_entry_policy,
+            ingest_codec,
+            bloom_false_positive_rate: ChunkBloomFilter::false_positive_rate_from_config(config),
+            recompression,
+            compaction,
+            archive,
+            retention: RetentionConfig::from_config(config),
+            full_text_index: config.get_or_default("store.index.full_text.enabled", defaults.full_text_index),
+            subscription_buffer: config.get_or_default("store.subscription.buffer", defaults.subscription_buffer),
+        }
+    }
+}
+
+///
+/// Buffered writer for a single store file. Entries are recorded in the store's
+/// write-ahead log before being accepted into the open chunk, and the log is
+/// truncated only after the sealed chunk has been durably written to the store.
+///
+pub struct ChunkStoreWriter {
+    pub path: String,
+    pub config: ChunkStoreWriterConfig,
+    pub store: ChunkStore,
+    pub cache: Cache,
+    /// Whether the open chunk extends the store's latest chunk rather than a new one
+    pub resumed_latest_chunk: bool,
+    /// Newest timestamp sealed into a chunk the open chunk does not replace
+    sealed_watermark: u64,
+    index: Option<InvertedIndex>,
+    pub(crate) subscribers: Vec<Subscriber>,
+    pub(crate) next_subscription_id: u64,
+    wal: WriteAheadLog,
+    last_recompression: Instant,
+    last_compaction: Instant,
+    last_archival: Instant,
+    last_retention: Instant,
+}
+
+impl ChunkStoreWriter {
+    ///
+    /// Open the store at the given path, creating it if it does not exist, and
+    /// replay any entries left in the write-ahead log that the store has not
+    /// sealed into the open chunk.
+    ///
+    /// # Arguments
+    /// * path: Location of the store file
+    /// * config: Chunk sealing and WAL sync settings
+    ///
+    /// # Returns
+    /// `Result<ChunkStoreWriter>`: Writer with the open chunk restored
+    ///
+    pub fn open(path: &str, config: ChunkStoreWriterConfig) -> Result<ChunkStoreWriter, Error> {
+        let store: ChunkStore = if Path::new(path).exists() {
+            ChunkStore::read_from_file(path)?
+        } else {
+            ChunkStore::create(path, config.sector_size, config.align_chunks)?
+        };
+        let mut wal: WriteAheadLog = WriteAheadLog::open(
+            WriteAheadLog::path_for_store(path).as_str(),
+            config.fsync_policy,
+        )?;
+        let mut cache: Cache = Cache::new();
+        let resumed_latest_chunk: bool = config.resume_latest_chunk
+            && Self::resume_latest_chunk(&store, &config, &mut cache)?;
+        let sealed_watermark: u64 = Self::sealed_watermark(&store, resumed_latest_chunk)?;
+        let index: Option<InvertedIndex> = if config.full_text_index {
+            Some(InvertedIndex::open(&store)?)
+        } else {
+            None
+        };
+        let replayed: Vec<ChunkEntry> = wal.replay(store.header.wal_sequence)?;
+        if !replayed.is_empty() {
+            info!(
+                crate::LOGGER,
+                "Replayed {} entries from write-ahead log {}",
+                replayed.len(),
+                wal.path
+            );
+        }
+        for entry in replayed.into_iter() {
+            cache.push(entry);
+        }
+        return Ok(ChunkStoreWriter {
+            path: String::from(path),
+            config,
+            store,
+            cache,
+            resumed_latest_chunk,
+            sealed_watermark,
+            index,
+            subscribers: Vec::new(),
+            next_subscription_id: 0,
+            wal,
+            last_recompression: Instant::now(),
+            last_compaction: Instant::now(),
+            last_archival: Instant::now(),
+            last_retention: Instant::now(),
+        });
+    }
+    ///
+    /// Load the store's latest chunk into the open chunk if it is still below the
+    /// configured seal size and entry count, so restarts do not leave behind a
+    /// trail of tiny chunks. Entry timestamps say nothing about when the chunk
+    /// was sealed, so age is not considered; the resumed chunk's age starts
+    /// from the restart.
+    ///
+    /// # Returns
+    /// `Result<bool>`: `true` if the latest chunk was resumed
+    ///
+    fn resume_latest_chunk(store: &ChunkStore, config: &ChunkStoreWriterConfig, cache: &mut Cache) -> Result<bool, Error> {
+        if store.header.chunk_offsets_length == 0 {
+            return Ok(false);
+        }
+        let entries: Vec<ChunkEntry> = match store.latest_chunk.decode_entries() {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+        let entries_bytes_length: usize = entries.iter()
+            .map(|e: &ChunkEntry| e.into_bytes().len())
+            .sum();
+        if entries.len() >= config.max_chunk_entries || entries_bytes_length >= config.max_chunk_bytes {
+            return Ok(false);
+        }
+        info!(
+            crate::LOGGER,
+            "Resuming latest chunk of {} with {} entries",
+            store.path,
+            entries.len()
+        );
+        cache.resume(entries);
+        return Ok(true);
+    }
+    ///
+    /// Newest timestamp of the sealed chunk preceding the open chunk. When the
+    /// latest chunk is being resumed it is replaced on flush, so the chunk
+    /// before it is the one new entries must not fall behind.
+    ///
+    fn sealed_watermark(store: &ChunkStore, resumed_latest_chunk: bool) -> Result<u64, Error> {
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        if !resumed_latest_chunk {
+            return Ok(if chunk_count == 0 { 0 } else { store.latest_chunk.timestamp_to });
+        }
+        if chunk_count < 2 {
+            return Ok(0);
+        }
+        return Ok(store.read_chunk(chunk_count - 2)?.timestamp_to);
+    }
+    ///
+    /// Accept an entry into the open chunk. The entry is acknowledged once it is
+    /// in the write-ahead log, at which point it is also published to matching
+    /// subscriptions, and the chunk is sealed if it is now full. Entries older
+    /// than the newest sealed entry are handled by the late entry policy.
+    ///
+    /// # Arguments
+    /// * entry: Entry to buffer
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result
+    ///
+    pub fn append(&mut self, entry: ChunkEntry) -> Result<(), Error> {
+        if entry.timestamp < self.sealed_watermark {
+            match self.config.late_entry_policy {
+                LateEntryPolicy::ACCEPT => {},
+                LateEntryPolicy::REJECT => return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Entry at {} is older than the latest sealed entry at {}",
+                        entry.timestamp,
+                        self.sealed_watermark
+                    ),
+                )),
+                LateEntryPolicy::DROP => {
+                    warn!(
+                        crate::LOGGER,
+                        "Dropping late entry at {} older than the latest sealed entry at {} in {}",
+                        entry.timestamp,
+                        self.sealed_watermark,
+                        self.path
+                    );
+                    return Ok(());
+                },
+            }
+        }
+        self.wal.append(&entry)?;
+        if !self.subscribers.is_empty() {
+            self.publish(&entry);
+        }
+        self.cache.push(entry);
+        if self.is_seal_due() {
+            return self.flush();
+        }
+        return Ok(());
+    }
+    ///
+    /// Periodic housekeeping: sync the WAL if the interval policy is due, seal
+    /// the open chunk once it has exceeded its maximum age, enforce retention
+    /// when its interval has elapsed, and save or rebuild the inverted index.
+    /// Retention only reads chunk prefixes and commits a header, so it runs
+    /// here. Recompression, compaction and archival are left to
+    /// `take_due_recompression`, `take_due_compaction` and `take_due_archival`,
+    /// as they are too slow to run under the writer.
+    ///
+    pub fn tick(&mut self) -> Result<(), Error> {
+        self.wal.sync_if_due()?;
+        if self.is_seal_due() {
+            self.flush()?;
+        }
+        if self.config.retention.is_enabled() && self.last_retention.elapsed() >= self.config.retention.interval {
+            let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            self.store.enforce_retention(&self.config.retention, now)?;
+            self.last_retention = Instant::now();
+        }
+        if let Some(index) = self.index.as_mut() {
+            // The index is derived from the store, so failing to maintain it must
+            // not fail the tick; it is retried on the next one
+            if let Err(e) = index.maintain(&self.store) {
+                warn!(crate::LOGGER, "Unable to maintain inverted index {}: {}", index.path, e);
+            }
+        }
+        return Ok(());
+    }
+    ///
+    /// Recompression settings if its interval has elapsed, restarting the
+    /// interval. The caller plans the run against its own instance of the store
+    /// and applies it with `apply_recompression`.
+    ///
+    pub fn take_due_recompression(&mut self) -> Option<RecompressionConfig> {
+        let recompression: &RecompressionConfig = self.config.recompression.as_ref()?;
+        if self.last_recompression.elapsed() < recompression.interval {
+            return None;
+        }
+        self.last_recompression = Instant::now();
+        return Some(recompression.clone());
+    }
+    pub fn apply_recompression(&mut self, recompressed: Vec<RecompressedChunk>, config: &RecompressionConfig) -> Result<RecompressionSummary, Error> {
+        return self.store.apply_recompression(recompressed, config);
+    }
+    ///
+    /// Compaction settings if its interval has elapsed, restarting the interval.
+    /// The caller plans the run against its own instance of the store and
+    /// applies it with `apply_compaction`.
+    ///
+    pub fn take_due_compaction(&mut self) -> Option<CompactionConfig> {
+        let compaction: &CompactionConfig = self.config.compaction.as_ref()?;
+        if self.last_compaction.elapsed() < compaction.interval {
+            return None;
+        }
+        self.last_compaction = Instant::now();
+        return Some(compaction.clone());
+    }
+    ///
+    /// Swap merged runs into the store. Chunks after them are renumbered, so the
+    /// inverted index is rebuilt on the next `tick`.
+    ///
+    pub fn apply_compaction(&mut self, compacted: Vec<CompactedRun>) -> Result<CompactionSummary, Error> {
+        return self.store.apply_compaction(compacted);
+    }
+    ///
+    /// Archival settings if its interval has elapsed, restarting the interval.
+    /// The caller writes the archive from its own instance of the store and
+    /// applies it with `apply_archival`.
+    ///
+    pub fn take_due_archival(&mut self) -> Option<ArchiveConfig> {
+        let archive: &ArchiveConfig = self.config.archive.as_ref()?;
+        if self.last_archival.elapsed() < archive.interval {
+            return None;
+        }
+        self.last_archival = Instant::now();
+        return Some(archive.clone());
+    }
+    ///
+    /// Swap a planned archive in for its chunks. Chunks after them are
+    /// renumbered, so the inverted index is rebuilt on the next `tick`.
+    ///
+    pub fn apply_archival(&mut self, planned: PlannedArchive) -> Result<ArchiveSummary, Error> {
+        return self.store.apply_archival(planned);
+    }
+    ///
+    /// The inverted index, if enabled and current with the store. It is not
+    /// current between a commit that renumbered chunks and the next `tick`.
+    ///
+    pub fn index(&self) -> Option<&InvertedIndex> {
+        self.index.as_ref().filter(|i: &&InvertedIndex| i.is_current(&self.store))
+    }
+    pub fn is_seal_due(&self) -> bool {
+        !self.cache.is_empty()
+            && (self.cache.entries.len() >= self.config.max_chunk_entries
+                || self.cache.entries_bytes_length >= self.config.max_chunk_bytes
+                || self.cache.age() >= self.config.max_chunk_age)
+    }
+    ///
+    /// Seal the open chunk into the store, write the store durably and only then
+    /// discard the write-ahead log. The inverted index, if enabled, is updated
+    /// last, in memory; `tick` saves it.
+    ///
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.cache.is_empty() {
+            return Ok(());
+        }
+        let chunk: Chunk = match Chunk::seal_with(
+            self.cache.entries.as_slice(),
+            self.config.ingest_codec,
+            self.config.bloom_false_positive_rate,
+        ) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::other(e.to_string())),
+        };
+        let timestamp_to: u64 = chunk.timestamp_to;
+        let indexed_chunk: Option<Chunk> = self.index.as_ref().map(|_| chunk.clone());
+        let wal_sequence: u64 = self.wal.last_sequence();
+        if self.resumed_latest_chunk {
+            self.store.replace_latest_chunk(chunk, wal_sequence)?;
+            self.resumed_latest_chunk = false;
+        } else {
+            self.store.append_logged_chunk(chunk, wal_sequence)?;
+        }
+        // Only once the chunk is committed, as entries stay open if it fails
+        self.sealed_watermark = self.sealed_watermark.max(timestamp_to);
+        self.wal.truncate()?;
+        self.cache.take_entries();
+        if let (Some(index), Some(chunk)) = (self.index.as_mut(), indexed_chunk) {
+            // The index is derived from the store, so failing to update it must not
+            // fail the seal; it is left stale and rebuilt on the next tick or open
+            let chunk_index: usize = self.store.header.chunk_offsets.len() - 1;
+            if let Err(e) = index.chunk_sealed(&self.store, chunk_index, &chunk) {
+                warn!(crate::LOGGER, "Unable to update inverted index {}: {}", index.path, e);
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+    use crate::data::representational::store::index::inverted_index_header::InvertedIndexHeader;
+    use crate::utils::test_utils::{entry_at, stored_timestamps, temp_store_path, test_writer_config};
+
+    #[test]
+    fn resumes_backfilled_latest_chunk() {
+        let path: String = temp_store_path("resumes_backfilled_latest_chunk");
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        writer.append(entry_at(1_000)).unwrap();
+        writer.append(entry_at(2_000)).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        assert!(writer.resumed_latest_chunk);
+        assert_eq!(writer.cache.entries.len(), 2);
+        assert!(!writer.is_seal_due());
+    }
+
+    #[test]
+    fn does_not_resume_full_latest_chunk() {
+        let path: String = temp_store_path("does_not_resume_full_latest_chunk");
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        for timestamp in 0..4 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        drop(writer);
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        assert!(!writer.resumed_latest_chunk);
+        assert!(writer.cache.is_empty());
+    }
+
+    #[test]
+    fn enforces_retention_on_tick() {
+        let path: String = temp_store_path("enforces_retention_on_tick");
+        let mut config: ChunkStoreWriterConfig = test_writer_config(1);
+        config.retention.max_chunks = Some(2);
+        config.retention.interval = Duration::ZERO;
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+        for timestamp in 0..5 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        assert_eq!(writer.store.header.chunk_offsets.len(), 5);
+        writer.tick().unwrap();
+        assert_eq!(writer.store.header.chunk_offsets.len(), 2);
+        assert_eq!(writer.store.read_chunk(0).unwrap().timestamp_from, 3);
+    }
+
+    fn policy_config(policy: LateEntryPolicy) -> ChunkStoreWriterConfig {
+        let mut config: ChunkStoreWriterConfig = test_writer_config(2);
+        config.late_entry_policy = policy;
+        return config;
+    }
+
+    fn writer_with_policy(name: &str, policy: LateEntryPolicy) -> ChunkStoreWriter {
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(temp_store_path(name).as_str(), policy_config(policy)).unwrap();
+        writer.append(entry_at(100)).unwrap();
+        writer.append(entry_at(200)).unwrap();
+        assert_eq!(writer.store.header.chunk_offsets.len(), 1);
+        return writer;
+    }
+
+    fn open_timestamps(writer: &ChunkStoreWriter) -> Vec<u64> {
+        writer.cache.entries.iter().map(|e: &ChunkEntry| e.timestamp).collect()
+    }
+
+    #[test]
+    fn accepts_late_entries_into_overlapping_chunk() {
+        let mut writer: ChunkStoreWriter = writer_with_policy("accepts_late_entries_into_overlapping_chunk", LateEntryPolicy::ACCEPT);
+        writer.append(entry_at(150)).unwrap();
+        // Entries at the watermark are not late
+        writer.append(entry_at(200)).unwrap();
+        assert_eq!(writer.store.header.chunk_offsets.len(), 2);
+        assert!(writer.store.has_overlapping_chunks());
+        assert_eq!(writer.store.read_chunk(1).unwrap().timestamp_from, 150);
+    }
+
+    #[test]
+    fn rejects_late_entries() {
+        let mut writer: ChunkStoreWriter = writer_with_policy("rejects_late_entries", LateEntryPolicy::REJECT);
+        assert_eq!(writer.append(entry_at(150)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        writer.append(entry_at(250)).unwrap();
+        assert_eq!(open_timestamps(&writer), vec![250]);
+        // The refused entry never reached the write-ahead log
+        let path: String = writer.path.clone();
+        drop(writer);
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), policy_config(LateEntryPolicy::REJECT)).unwrap();
+        assert_eq!(open_timestamps(&writer), vec![250]);
+    }
+
+    #[test]
+    fn drops_late_entries() {
+        let mut writer: ChunkStoreWriter = writer_with_policy("drops_late_entries", LateEntryPolicy::DROP);
+        writer.append(entry_at(150)).unwrap();
+        writer.append(entry_at(250)).unwrap();
+        assert_eq!(open_timestamps(&writer), vec![250]);
+        writer.flush().unwrap();
+        assert!(!writer.store.has_overlapping_chunks());
+    }
+
+    fn saved_index_chunk_count(path: &str) -> u64 {
+        let bytes: Vec<u8> = std::fs::read(InvertedIndex::path_for_store(path)).unwrap();
+        let mut header: InvertedIndexHeader = InvertedIndexHeader::default();
+        header.parse_bytes::<&'_ [u8], nom::error::Error<_>>(bytes.as_slice()).unwrap();
+        return header.chunk_count;
+    }
+
+    #[test]
+    fn indexes_seals_across_recompression_and_saves_on_tick() {
+        let path: String = temp_store_path("indexes_seals_across_recompression_and_saves_on_tick");
+        let mut config: ChunkStoreWriterConfig = test_writer_config(4);
+        config.full_text_index = true;
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+        for timestamp in 0..8 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        let recompression: RecompressionConfig = RecompressionConfig {
+            min_age: Duration::from_millis(100),
+            ..RecompressionConfig::default()
+        };
+        assert_eq!(writer.store.recompress_aged(&recompression, 1_000_000).unwrap().chunks_recompressed, 1);
+        for timestamp in 8..12 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        // Recompression kept chunk indices, so the seal after it was indexed in place
+        let index: &InvertedIndex = writer.index().expect("Index is stale");
+        assert_eq!(index.chunk_count, 3);
+        assert_eq!(index.postings("message").len(), 12);
+        // Seals leave the sidecar alone until the next tick
+        assert_eq!(saved_index_chunk_count(path.as_str()), 0);
+        writer.tick().unwrap();
+        assert_eq!(saved_index_chunk_count(path.as_str()), 3);
+    }
+
+    ///
+    /// Seal the open chunk as if the process died after the header commit and
+    /// before the write-ahead log was truncated.
+    ///
+    fn flush_and_crash(writer: ChunkStoreWriter) {
+        let wal_path: String = writer.wal.path.clone();
+        let wal_bytes: Vec<u8> = std::fs::read(wal_path.as_str()).unwrap();
+        let mut writer: ChunkStoreWriter = writer;
+        writer.flush().unwrap();
+        drop(writer);
+        std::fs::write(wal_path.as_str(), wal_bytes).unwrap();
+    }
+
+    #[test]
+    fn skips_sealed_entries_left_in_wal() {
+        let path: String = temp_store_path("skips_sealed_entries_left_in_wal");
+        let mut config: ChunkStoreWriterConfig = test_writer_config(4);
+        config.resume_latest_chunk = false;
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config.clone()).unwrap();
+        writer.append(entry_at(1_000)).unwrap();
+        writer.append(entry_at(2_000)).unwrap();
+        flush_and_crash(writer);
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config.clone()).unwrap();
+        assert!(writer.cache.is_empty());
+        // Entries appended after the crash are replayed, the sealed ones are not
+        writer.append(entry_at(3_000)).unwrap();
+        drop(writer);
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+        assert_eq!(writer.cache.entries.len(), 1);
+        writer.flush().unwrap();
+        assert_eq!(stored_timestamps(&writer.store), vec![1_000, 2_000, 3_000]);
+    }
+
+    #[test]
+    fn skips_sealed_entries_left_in_wal_when_resuming() {
+        let path: String = temp_store_path("skips_sealed_entries_left_in_wal_when_resuming");
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        writer.append(entry_at(1_000)).unwrap();
+        writer.append(entry_at(2_000)).unwrap();
+        flush_and_crash(writer);
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        assert!(writer.resumed_latest_chunk);
+        assert_eq!(writer.cache.entries.len(), 2);
+        writer.append(entry_at(3_000)).unwrap();
+        flush_and_crash(writer);
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        assert_eq!(writer.cache.entries.len(), 3);
+        assert_eq!(stored_timestamps(&writer.store), vec![1_000, 2_000, 3_000]);
+    }
+
+    #[test]
+    fn sizes_bloom_filters_by_configured_rate() {
+        let mut properties: Config = Config::new("unused.properties");
+        properties.properties.insert(String::from("store.chunk.bloom_false_positive_rate"), String::from("0.001"));
+        let config: ChunkStoreWriterConfig = ChunkStoreWriterConfig::from_config(&mut properties);
+        assert_eq!(config.bloom_false_positive_rate, 0.001);
+        assert_eq!(config.compaction.as_ref().unwrap().bloom_false_positive_rate, 0.001);
+        properties.properties.insert(String::from("store.chunk.bloom_false_positive_rate"), String::from("1.5"));
+        assert_eq!(ChunkStoreWriterConfig::from_config(&mut properties).bloom_false_positive_rate, ChunkBloomFilter::DEFAULT_FALSE_POSITIVE_RATE);
+
+        let mut filter_bits: Vec<usize> = Vec::new();
+        for rate in [0.1, 0.001] {
+            let path: String = temp_store_path(format!("sizes_bloom_filters_by_configured_rate_{}", rate).as_str());
+            let mut config: ChunkStoreWriterConfig = test_writer_config(4);
+            config.bloom_false_positive_rate = rate;
+            let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+            for timestamp in 0..4 {
+                let mut entry: ChunkEntry = entry_at(timestamp);
+                entry.target = format!("target-{}", timestamp).into_bytes();
+                writer.append(entry).unwrap();
+            }
+            let filter: ChunkBloomFilter = writer.store.read_chunk(0).unwrap()
+                .section(ChunkMetadataSection::BLOOM_FILTER)
+                .and_then(ChunkBloomFilter::from_section)
+                .unwrap();
+            filter_bits.push(filter.bits.len() * 8);
+        }
+        assert!(filter_bits[1] > filter_bits[0] * 2, "{:?}", filter_bits);
+    }
+}
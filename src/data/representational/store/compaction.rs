@@ -0,0 +1,288 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::ops::Range;
+use std::time::Duration;
+use crate::compression::compressor::CompressionCodec;
+use crate::configuration::config::Config;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use super::chunk_offsets::ChunkOffsets;
+use super::chunk_store::ChunkStore;
+use super::store_write_lock::StoreWriteLock;
+
+#[derive(Debug, Clone)]
+pub struct CompactionConfig {
+    /// Chunks occupying fewer bytes than this on disk are merged with their neighbours
+    pub small_chunk_bytes: u64,
+    /// Merged entries are split into a new chunk once they reach this many uncompressed bytes
+    pub target_chunk_bytes: usize,
+    /// False positive rate of each merged chunk's target and trigram bloom filters
+    pub bloom_false_positive_rate: f64,
+    /// How often the writer's maintenance compacts the store
+    pub interval: Duration,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        CompactionConfig {
+            small_chunk_bytes: 64 * 1024,
+            target_chunk_bytes: 1024 * 1024,
+            bloom_false_positive_rate: ChunkBloomFilter::DEFAULT_FALSE_POSITIVE_RATE,
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+impl CompactionConfig {
+    pub fn from_config(config: &mut Config) -> CompactionConfig {
+        let defaults: CompactionConfig = CompactionConfig::default();
+        CompactionConfig {
+            small_chunk_bytes: config.get_or_default("store.compaction.small_chunk_bytes", defaults.small_chunk_bytes),
+            target_chunk_bytes: config.get_or_default("store.compaction.target_chunk_bytes", defaults.target_chunk_bytes),
+            bloom_false_positive_rate: ChunkBloomFilter::false_positive_rate_from_config(config),
+            interval: Duration::from_millis(config.get_or_default(
+                "store.compaction.interval_ms",
+                defaults.interval.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CompactionSummary {
+    pub chunks_merged: usize,
+    pub chunks_written: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+///
+/// Run of adjacent small chunks merged into fewer chunks, waiting to be
+/// swapped in for the originals.
+///
+#[derive(Debug, Clone)]
+pub struct CompactedRun {
+    /// Where the original chunks sit, in order, identifying them however the chunk list has changed since
+    pub originals: Vec<ChunkOffsets>,
+    pub original_length: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+impl ChunkStore {
+    ///
+    /// Merge runs of adjacent small chunks into chunks of roughly the target size.
+    /// Replacement chunks are written to free space and swapped in with a single
+    /// header commit; the old chunks are retired rather than freed, so views taken
+    /// before the compaction can still finish reading them. The latest chunk is
+    /// left alone as a writer may be extending it.
+    ///
+    /// # Arguments
+    /// * config: Size thresholds for merging and splitting
+    ///
+    /// # Returns
+    /// `Result<CompactionSummary>`: What was merged and the space it occupied before and after
+    ///
+    pub fn compact(&mut self, config: &CompactionConfig) -> Result<CompactionSummary, Error> {
+        let runs: Vec<CompactedRun> = self.plan_compaction(config)?;
+        return self.apply_compaction(runs);
+    }
+    ///
+    /// Read and merge the runs of small chunks due for compaction without
+    /// changing the store. Runs are found from the lengths in each chunk's fixed
+    /// size prefix, so only the chunks merged are read whole. Like
+    /// `plan_recompression` this takes no lock, and the instance's lease keeps
+    /// the originals in place until the runs are applied.
+    ///
+    /// # Arguments
+    /// * config: Size thresholds for merging and splitting
+    ///
+    /// # Returns
+    /// `Result<Vec<CompactedRun>>`: Merged runs for `apply_compaction`, oldest first
+    ///
+    pub fn plan_compaction(&self, config: &CompactionConfig) -> Result<Vec<CompactedRun>, Error> {
+        let mut compacted: Vec<CompactedRun> = Vec::new();
+        let chunk_count: usize = self.header.chunk_offsets.len();
+        if chunk_count < 3 {
+            return Ok(compacted);
+        }
+        let mut file: File = File::open(self.path.as_str())?;
+        let mut lengths: Vec<u32> = Vec::with_capacity(chunk_count - 1);
+        for index in 0..(chunk_count - 1) {
+            lengths.push(self.read_chunk_length(&mut file, index)?);
+        }
+        let mut index: usize = 0;
+        while index < lengths.len() {
+            if (lengths[index] as u64) >= config.small_chunk_bytes {
+                index += 1;
+                continue;
+            }
+            let start: usize = index;
+            while index < lengths.len() && (lengths[index] as u64) < config.small_chunk_bytes {
+                index += 1;
+            }
+            if index - start < 2 {
+                continue;
+            }
+            if let Some(chunks) = self.merge_chunks(&mut file, start..index, config)? {
+                compacted.push(CompactedRun {
+                    originals: self.header.chunk_offsets[start..index].to_vec(),
+                    original_length: lengths[start..index].iter().map(|l: &u32| *l as u64).sum(),
+                    chunks,
+                });
+            }
+        }
+        return Ok(compacted);
+    }
+    ///
+    /// Swap merged runs in for their originals in a single header commit. Runs
+    /// whose originals are no longer adjacent in the store, or now include its
+    /// latest chunk, are skipped.
+    ///
+    /// # Arguments
+    /// * compacted: Merged runs planned by `plan_compaction`
+    ///
+    /// # Returns
+    /// `Result<CompactionSummary>`: What was merged and the space it occupied before and after
+    ///
+    pub fn apply_compaction(&mut self, compacted: Vec<CompactedRun>) -> Result<CompactionSummary, Error> {
+        let mut summary: CompactionSummary = CompactionSummary::default();
+        if compacted.is_empty() {
+            return Ok(summary);
+        }
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        for run in compacted.iter() {
+            let start: usize = match self.header.chunk_offsets.iter().position(|o: &ChunkOffsets| *o == run.originals[0]) {
+                Some(i) => i,
+                None => continue,
+            };
+            let end: usize = start + run.originals.len();
+            if end >= self.header.chunk_offsets.len() || self.header.chunk_offsets[start..end] != run.originals[..] {
+                continue;
+            }
+            let mut chunk_offsets: Vec<ChunkOffsets> = Vec::with_capacity(run.chunks.len());
+            for chunk in run.chunks.iter() {
+                chunk_offsets.push(self.write_chunk(&mut file, chunk)?);
+                summary.bytes_after += chunk.length as u64;
+            }
+            for index in start..end {
+                self.retire_chunk(&mut file, index)?;
+            }
+            self.header.chunk_offsets.splice(start..end, chunk_offsets);
+            summary.chunks_merged += run.originals.len();
+            summary.chunks_written += run.chunks.len();
+            summary.bytes_before += run.original_length;
+        }
+        if summary.chunks_merged == 0 {
+            return Ok(summary);
+        }
+        self.header.chunk_offsets_length = self.header.chunk_offsets.len() as u64;
+        self.header.chunk_count = self.header.chunk_offsets_length;
+        self.header.renumber_chunks();
+        self.commit_header(&mut file)?;
+        info!(
+            crate::LOGGER,
+            "Compacted {}: merged {} chunks into {} ({}B -> {}B)",
+            self.path,
+            summary.chunks_merged,
+            summary.chunks_written,
+            summary.bytes_before,
+            summary.bytes_after
+        );
+        return Ok(summary);
+    }
+    fn merge_chunks(&self, file: &mut File, run: Range<usize>, config: &CompactionConfig) -> Result<Option<Vec<Chunk>>, Error> {
+        let mut entries: Vec<ChunkEntry> = Vec::new();
+        let mut timestamp_from: u64 = u64::MAX;
+        let mut timestamp_to: u64 = u64::MIN;
+        let mut codec: CompressionCodec = CompressionCodec::default();
+        for index in run.clone() {
+            let chunk: Chunk = self.read_chunk_from(file, index)?;
+            if index == run.start {
+                codec = chunk.compression_codec().unwrap_or_default();
+            }
+            timestamp_from = timestamp_from.min(chunk.timestamp_from);
+            timestamp_to = timestamp_to.max(chunk.timestamp_to);
+            match chunk.decode_entries() {
+                Ok(mut v) => entries.append(&mut v),
+                Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+            };
+        }
+        let mut groups: Vec<Vec<ChunkEntry>> = vec![Vec::new()];
+        let mut group_bytes: usize = 0;
+        for entry in entries.into_iter() {
+            if group_bytes >= config.target_chunk_bytes {
+                groups.push(Vec::new());
+                group_bytes = 0;
+            }
+            group_bytes += entry.into_bytes().len();
+            groups.last_mut().unwrap().push(entry);
+        }
+        let group_count: usize = groups.len();
+        // Rewriting a run that would not shrink only churns the file
+        if group_count >= run.len() {
+            return Ok(None);
+        }
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(group_count);
+        for (i, group) in groups.iter().enumerate() {
+            let mut chunk: Chunk = match Chunk::seal_with(group.as_slice(), codec, config.bloom_false_positive_rate) {
+                Ok(c) => c,
+                Err(e) => return Err(Error::other(e.to_string())),
+            };
+            // The merged chunks together keep the time range of the originals
+            if i == 0 {
+                chunk.timestamp_from = timestamp_from;
+            }
+            if i == group_count - 1 {
+                chunk.timestamp_to = timestamp_to;
+            }
+            chunks.push(chunk);
+        }
+        return Ok(Some(chunks));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::{chunk_of, stored_timestamps, temp_store_path};
+
+    #[test]
+    fn applies_compaction_planned_before_appends() {
+        let path: String = temp_store_path("applies_compaction_planned_before_appends");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=5 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        let planner: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let compacted: Vec<CompactedRun> = planner.plan_compaction(&CompactionConfig::default()).unwrap();
+        assert_eq!(compacted.len(), 1);
+        assert_eq!(compacted[0].originals.len(), 4);
+
+        store.append_chunk(chunk_of(&[6])).unwrap();
+        let summary: CompactionSummary = store.apply_compaction(compacted).unwrap();
+        assert_eq!((summary.chunks_merged, summary.chunks_written), (4, 1));
+        assert_eq!(store.header.chunk_offsets.len(), 3);
+        assert_eq!(store.header.chunk_epoch, 1);
+        assert_eq!(stored_timestamps(&store), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn skips_runs_changed_since_planned() {
+        let path: String = temp_store_path("skips_runs_changed_since_planned");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=4 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        let planner: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let compacted: Vec<CompactedRun> = planner.plan_compaction(&CompactionConfig::default()).unwrap();
+        assert_eq!(compacted.len(), 1);
+        // Another instance compacts the same chunks first
+        let mut compactor: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(compactor.compact(&CompactionConfig::default()).unwrap().chunks_merged, 3);
+        let summary: CompactionSummary = store.apply_compaction(compacted).unwrap();
+        assert_eq!(summary.chunks_merged, 0);
+        assert_eq!(store.header.chunk_offsets.len(), 2);
+        assert_eq!(stored_timestamps(&store), vec![1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,440 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use super::archive::archive_reference::ArchiveReference;
+use super::archive::archive_store::ArchiveStore;
+use super::chunk_store::ChunkStore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScanDirection {
+    /// Oldest chunk first, entries in chunk order
+    FORWARD,
+    /// Newest chunk first, entries in reverse chunk order
+    BACKWARD,
+}
+
+///
+/// Position of the next entry of a scan, to resume it later. Chunks are
+/// numbered across both tiers, archived chunks first, then the store's own.
+/// The entry's timestamp is kept so that if chunks have since been compacted,
+/// archived or dropped, the scan resumes from the first entry at or past it
+/// in the scan direction instead.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryCursor {
+    pub chunk: u64,
+    pub entry: u32,
+    pub timestamp: u64,
+    pub direction: ScanDirection,
+}
+
+impl EntryCursor {
+    pub fn to_token(&self) -> String {
+        return serde_json::to_string(self).unwrap_or_default();
+    }
+    pub fn from_token(token: &str) -> Result<EntryCursor, Error> {
+        return serde_json::from_str::<EntryCursor>(token)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()));
+    }
+}
+
+///
+/// Lazy scan over the entries of a store and its archives. Only the chunk
+/// being read is held decompressed, so a scan costs memory for one chunk
+/// however large the store is. Within a chunk entries are in timestamp order;
+/// across chunks they are in chunk order, which is also timestamp order unless
+/// the store holds overlapping chunks. Only sealed chunks are scanned: entries
+/// still in a writer's open chunk are not visible until it is sealed.
+///
+pub struct EntryIterator<'a> {
+    store: &'a ChunkStore,
+    direction: ScanDirection,
+    chunk_count: usize,
+    chunk: usize,
+    entries: Option<Vec<ChunkEntry>>,
+    /// Position in the chunk of the first entry held, past zero only for a forward scan resumed part way into it
+    first: usize,
+    /// Next entry of those held, `usize::MAX` for the last entry of a chunk not yet loaded
+    entry: usize,
+    archive: Option<(usize, ArchiveStore)>,
+    done: bool,
+}
+
+impl<'a> EntryIterator<'a> {
+    const LAST_ENTRY: usize = usize::MAX;
+
+    fn new(store: &'a ChunkStore, direction: ScanDirection) -> EntryIterator<'a> {
+        let chunk_count: usize = store.header.archives.iter()
+            .map(|a: &ArchiveReference| a.chunk_count as usize)
+            .sum::<usize>() + store.header.chunk_offsets.len();
+        return EntryIterator {
+            store,
+            direction,
+            chunk_count,
+            chunk: match direction {
+                ScanDirection::FORWARD => 0,
+                ScanDirection::BACKWARD => chunk_count.saturating_sub(1),
+            },
+            entries: None,
+            first: 0,
+            entry: match direction {
+                ScanDirection::FORWARD => 0,
+                ScanDirection::BACKWARD => Self::LAST_ENTRY,
+            },
+            archive: None,
+            done: chunk_count == 0,
+        };
+    }
+    ///
+    /// Leave the store's latest chunk out of a scan that has not started yet,
+    /// for a writer holding that chunk open in memory.
+    ///
+    pub(crate) fn without_latest_chunk(mut self) -> EntryIterator<'a> {
+        if self.entries.is_some() || self.store.header.chunk_offsets.is_empty() {
+            return self;
+        }
+        // Archived chunks are numbered first, so the latest chunk is the last one
+        self.chunk_count -= 1;
+        if self.direction == ScanDirection::BACKWARD {
+            self.chunk = self.chunk_count.saturating_sub(1);
+        }
+        self.done = self.chunk_count == 0;
+        return self;
+    }
+    ///
+    /// Cursor for the entry the next call to `next` returns, `None` once the
+    /// scan is exhausted.
+    ///
+    pub fn cursor(&mut self) -> Result<Option<EntryCursor>, Error> {
+        if !self.settle()? {
+            return Ok(None);
+        }
+        let timestamp: u64 = self.entries.as_ref().map_or(0, |e: &Vec<ChunkEntry>| e[self.entry].timestamp);
+        return Ok(Some(EntryCursor {
+            chunk: self.chunk as u64,
+            entry: (self.first + self.entry) as u32,
+            timestamp,
+            direction: self.direction,
+        }));
+    }
+    ///
+    /// Move to the next entry to return, loading chunks as needed.
+    ///
+    /// # Returns
+    /// `Result<bool>`: `false` once every chunk has been scanned
+    ///
+    fn settle(&mut self) -> Result<bool, Error> {
+        while !self.done {
+            if self.entries.is_none() {
+                let entries: Vec<ChunkEntry> = match self.read_chunk(self.chunk)?.decode_entries() {
+                    Ok(v) => v,
+                    Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+                };
+                if self.entry == Self::LAST_ENTRY {
+                    self.entry = entries.len().wrapping_sub(1);
+                }
+                self.first = 0;
+                self.entries = Some(entries);
+            }
+            if self.entry < self.entries.as_ref().map_or(0, |e: &Vec<ChunkEntry>| e.len()) {
+                return Ok(true);
+            }
+            self.entries = None;
+            match self.direction {
+                ScanDirection::FORWARD if self.chunk + 1 < self.chunk_count => {
+                    self.chunk += 1;
+                    self.entry = 0;
+                },
+                ScanDirection::BACKWARD if self.chunk > 0 => {
+                    self.chunk -= 1;
+                    self.entry = Self::LAST_ENTRY;
+                },
+                _ => self.done = true,
+            }
+        }
+        return Ok(false);
+    }
+    fn read_chunk(&mut self, chunk: usize) -> Result<Chunk, Error> {
+        return match self.locate(chunk) {
+            Some((Some(archive_index), index)) => {
+                if self.archive.as_ref().is_none_or(|(a, _): &(usize, ArchiveStore)| *a != archive_index) {
+                    let reference: &ArchiveReference = &self.store.header.archives[archive_index];
+                    self.archive = Some((archive_index, ArchiveStore::open(self.store.archive_path(reference).as_str())?));
+                }
+                match &self.archive {
+                    Some((_, archive)) => archive.read_chunk(index),
+                    None => Err(Error::new(ErrorKind::NotFound, format!("No chunk at index {}", chunk))),
+                }
+            },
+            Some((None, index)) => self.store.read_chunk(index),
+            None => Err(Error::new(ErrorKind::NotFound, format!("No chunk at index {}", chunk))),
+        };
+    }
+    ///
+    /// Resolve a scan chunk number to the archive holding it, if any, and the
+    /// chunk's index within that archive or the store.
+    ///
+    fn locate(&self, chunk: usize) -> Option<(Option<usize>, usize)> {
+        let mut remaining: usize = chunk;
+        for (archive_index, reference) in self.store.header.archives.iter().enumerate() {
+            if remaining < reference.chunk_count as usize {
+                return Some((Some(archive_index), remaining));
+            }
+            remaining -= reference.chunk_count as usize;
+        }
+        return if remaining < self.store.header.chunk_offsets.len() {
+            Some((None, remaining))
+        } else {
+            None
+        };
+    }
+    ///
+    /// Decode a chunk's entries from a position on, parsing straight to it
+    /// through the chunk's entry index.
+    ///
+    fn read_entries_from(&mut self, chunk: usize, position: usize) -> Result<Vec<ChunkEntry>, Error> {
+        if let Some((None, index)) = self.locate(chunk) {
+            return self.store.read_entries_at(index, position, usize::MAX);
+        }
+        return match self.read_chunk(chunk)?.decode_entries_from(position, usize::MAX) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+    }
+    fn chunk_bounds(&mut self, file: &mut File, chunk: usize) -> Result<(u64, u64), Error> {
+        if let Some((None, index)) = self.locate(chunk) {
+            return self.store.read_chunk_bounds(file, index);
+        }
+        let chunk: Chunk = self.read_chunk(chunk)?;
+        return Ok((chunk.timestamp_from, chunk.timestamp_to));
+    }
+    ///
+    /// Position the scan on the entry a cursor names, or if that entry is no
+    /// longer where it was, on the first entry at or past its timestamp in the
+    /// scan direction. A forward scan only decodes the chunk from the entry it
+    /// resumes at.
+    ///
+    fn seek(&mut self, cursor: &EntryCursor) -> Result<(), Error> {
+        if (cursor.chunk as usize) < self.chunk_count {
+            let first: usize = match self.direction {
+                ScanDirection::FORWARD => cursor.entry as usize,
+                ScanDirection::BACKWARD => 0,
+            };
+            let entries: Vec<ChunkEntry> = self.read_entries_from(cursor.chunk as usize, first)?;
+            if entries.get(cursor.entry as usize - first).is_some_and(|e: &ChunkEntry| e.timestamp == cursor.timestamp) {
+                self.chunk = cursor.chunk as usize;
+                self.first = first;
+                self.entry = cursor.entry as usize - first;
+                self.entries = Some(entries);
+                return Ok(());
+            }
+        }
+        let mut file: File = File::open(self.store.path.as_str())?;
+        let mut found: Option<usize> = None;
+        match self.direction {
+            ScanDirection::FORWARD => for chunk in 0..self.chunk_count {
+                if self.chunk_bounds(&mut file, chunk)?.1 >= cursor.timestamp {
+                    found = Some(chunk);
+                    break;
+                }
+            },
+            ScanDirection::BACKWARD => for chunk in (0..self.chunk_count).rev() {
+                if self.chunk_bounds(&mut file, chunk)?.0 <= cursor.timestamp {
+                    found = Some(chunk);
+                    break;
+                }
+            },
+        }
+        let chunk: usize = match found {
+            Some(c) => c,
+            None => {
+                self.done = true;
+                return Ok(());
+            },
+        };
+        let sealed: Chunk = self.read_chunk(chunk)?;
+        let decoded = match self.direction {
+            ScanDirection::FORWARD => match sealed.position_at_or_after(cursor.timestamp) {
+                Ok(Some(position)) => sealed.decode_entries_from(position, usize::MAX).map(|e: Vec<ChunkEntry>| (position, e)),
+                Ok(None) => Ok((0, Vec::new())),
+                Err(e) => Err(e),
+            },
+            ScanDirection::BACKWARD => sealed.decode_entries().map(|e: Vec<ChunkEntry>| (0, e)),
+        };
+        let (first, entries): (usize, Vec<ChunkEntry>) = match decoded {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+        self.chunk = chunk;
+        self.first = first;
+        self.entry = match self.direction {
+            ScanDirection::FORWARD => 0,
+            ScanDirection::BACKWARD => entries.iter()
+                .rposition(|e: &ChunkEntry| e.timestamp <= cursor.timestamp)
+                .unwrap_or(Self::LAST_ENTRY),
+        };
+        self.entries = Some(entries);
+        return Ok(());
+    }
+}
+
+impl<'a> Iterator for EntryIterator<'a> {
+    type Item = Result<ChunkEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.settle() {
+            Ok(true) => {},
+            Ok(false) => return None,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            },
+        }
+        let entry: ChunkEntry = self.entries.as_ref()?[self.entry].clone();
+        self.entry = match self.direction {
+            ScanDirection::FORWARD => self.entry + 1,
+            ScanDirection::BACKWARD => self.entry.wrapping_sub(1),
+        };
+        return Some(Ok(entry));
+    }
+}
+
+impl ChunkStore {
+    ///
+    /// Scan every entry, archived chunks first, oldest chunk first.
+    ///
+    pub fn entries(&self) -> EntryIterator<'_> {
+        return EntryIterator::new(self, ScanDirection::FORWARD);
+    }
+    ///
+    /// Scan every entry newest first, ending with archived chunks.
+    ///
+    pub fn entries_rev(&self) -> EntryIterator<'_> {
+        return EntryIterator::new(self, ScanDirection::BACKWARD);
+    }
+    ///
+    /// Resume a scan from a cursor taken from an earlier scan, in the same
+    /// direction, starting with the entry the cursor names.
+    ///
+    /// # Arguments
+    /// * cursor: Position returned by `EntryIterator::cursor`
+    ///
+    /// # Returns
+    /// `Result<EntryIterator>`: Scan positioned on the cursor's entry
+    ///
+    pub fn entries_from(&self, cursor: &EntryCursor) -> Result<EntryIterator<'_>, Error> {
+        let mut iterator: EntryIterator = EntryIterator::new(self, cursor.direction);
+        if !iterator.done {
+            iterator.seek(cursor)?;
+        }
+        return Ok(iterator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::data::representational::store::archive::archival::ArchiveConfig;
+    use crate::data::representational::store::compaction::CompactionConfig;
+    use crate::utils::test_utils::{chunk_of, temp_store_path};
+
+    fn store_of(name: &str, chunks: &[&[u64]]) -> ChunkStore {
+        let mut store: ChunkStore = ChunkStore::create(temp_store_path(name).as_str(), 64, false).unwrap();
+        for timestamps in chunks.iter() {
+            store.append_chunk(chunk_of(timestamps)).unwrap();
+        }
+        return store;
+    }
+
+    fn timestamps(iterator: EntryIterator) -> Vec<u64> {
+        return iterator.map(|e: Result<ChunkEntry, Error>| e.unwrap().timestamp).collect();
+    }
+
+    ///
+    /// Store whose first two chunks have been moved to an archive.
+    ///
+    fn archived_store(name: &str) -> ChunkStore {
+        let mut store: ChunkStore = store_of(name, &[&[1, 2], &[3, 4], &[5, 6], &[7, 8]]);
+        let archive: ArchiveConfig = ArchiveConfig {
+            min_age: Duration::from_millis(1000),
+            ..ArchiveConfig::default()
+        };
+        assert_eq!(store.archive_aged(&archive, 1005).unwrap().chunks_archived, 2);
+        assert_eq!((store.header.archives.len(), store.header.chunk_offsets.len()), (1, 2));
+        return store;
+    }
+
+    #[test]
+    fn scans_across_archive_and_store() {
+        let store: ChunkStore = archived_store("scans_across_archive_and_store");
+        assert_eq!(timestamps(store.entries()), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(timestamps(store.entries_rev()), vec![8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn scans_past_empty_chunks() {
+        let store: ChunkStore = store_of("scans_past_empty_chunks", &[&[], &[1, 2], &[], &[], &[3], &[]]);
+        assert_eq!(timestamps(store.entries()), vec![1, 2, 3]);
+        assert_eq!(timestamps(store.entries_rev()), vec![3, 2, 1]);
+        let empty: ChunkStore = store_of("scans_past_empty_chunks_only", &[&[], &[]]);
+        assert!(empty.entries_rev().next().is_none());
+        assert!(empty.entries_rev().cursor().unwrap().is_none());
+    }
+
+    #[test]
+    fn resumes_from_token_in_either_direction() {
+        let store: ChunkStore = archived_store("resumes_from_token_in_either_direction");
+        let mut forward: EntryIterator = store.entries();
+        assert_eq!(forward.by_ref().take(3).map(|e| e.unwrap().timestamp).collect::<Vec<u64>>(), vec![1, 2, 3]);
+        let token: String = forward.cursor().unwrap().unwrap().to_token();
+        let cursor: EntryCursor = EntryCursor::from_token(token.as_str()).unwrap();
+        assert_eq!((cursor.chunk, cursor.entry, cursor.timestamp), (1, 1, 4));
+        assert_eq!(timestamps(store.entries_from(&cursor).unwrap()), vec![4, 5, 6, 7, 8]);
+        // The resumed scan holds the chunk from the cursor's entry on, but counts positions from its start
+        let mut resumed: EntryIterator = store.entries_from(&cursor).unwrap();
+        for _ in 0..4 {
+            assert_eq!(resumed.cursor().unwrap(), forward.cursor().unwrap());
+            assert_eq!(resumed.next().unwrap().unwrap().timestamp, forward.next().unwrap().unwrap().timestamp);
+        }
+        let cursor: EntryCursor = resumed.cursor().unwrap().unwrap();
+        assert_eq!((cursor.chunk, cursor.entry, cursor.timestamp), (3, 1, 8));
+        assert_eq!(timestamps(store.entries_from(&cursor).unwrap()), vec![8]);
+
+        let mut backward: EntryIterator = store.entries_rev();
+        assert_eq!(backward.by_ref().take(5).map(|e| e.unwrap().timestamp).collect::<Vec<u64>>(), vec![8, 7, 6, 5, 4]);
+        let cursor: EntryCursor = EntryCursor::from_token(backward.cursor().unwrap().unwrap().to_token().as_str()).unwrap();
+        assert_eq!(cursor.direction, ScanDirection::BACKWARD);
+        assert_eq!(timestamps(store.entries_from(&cursor).unwrap()), vec![3, 2, 1]);
+
+        // An exhausted scan has no cursor, and a malformed token is refused
+        let mut exhausted: EntryIterator = store.entries();
+        assert_eq!(exhausted.by_ref().count(), 8);
+        assert!(exhausted.cursor().unwrap().is_none());
+        assert_eq!(EntryCursor::from_token("{").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn resumes_by_timestamp_after_compaction_renumbers_chunks() {
+        let mut store: ChunkStore = store_of(
+            "resumes_by_timestamp_after_compaction_renumbers_chunks",
+            &[&[1], &[2], &[3], &[4], &[5], &[6]],
+        );
+        let mut forward: EntryIterator = store.entries();
+        forward.next();
+        let forward_cursor: EntryCursor = forward.cursor().unwrap().unwrap();
+        assert_eq!((forward_cursor.chunk, forward_cursor.timestamp), (1, 2));
+        let mut backward: EntryIterator = store.entries_rev();
+        backward.next();
+        let backward_cursor: EntryCursor = backward.cursor().unwrap().unwrap();
+        assert_eq!((backward_cursor.chunk, backward_cursor.timestamp), (4, 5));
+
+        assert_eq!(store.compact(&CompactionConfig::default()).unwrap().chunks_merged, 5);
+        assert_eq!(store.header.chunk_offsets.len(), 2);
+        // Chunk 1 now holds another entry and chunk 4 is gone, so both resume by timestamp
+        assert_eq!(timestamps(store.entries_from(&forward_cursor).unwrap()), vec![2, 3, 4, 5, 6]);
+        assert_eq!(timestamps(store.entries_from(&backward_cursor).unwrap()), vec![5, 4, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone,PartialEq)]
+    pub struct FreeSectorRange {
+        #[byte_size=8]
+        pub sector_index: u64,
+        #[byte_size=8]
+        pub sector_count: u64,
+    }
+}
+
+byte_layout!{
+    FreeSectorRange
+    value [sector_index, u64, Big]
+    value [sector_count, u64, Big]
+}
+
+impl FreeSectorRange {
+    #[inline]
+    pub fn end_sector(&self) -> u64 {
+        self.sector_index + self.sector_count
+    }
+}
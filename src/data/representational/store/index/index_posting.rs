@@ -0,0 +1,30 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
+    pub struct IndexPosting {
+        #[byte_size=4]
+        pub chunk: u32,
+        #[byte_size=4]
+        pub entry: u32,
+    }
+}
+
+byte_layout!{
+    IndexPosting
+    value [chunk, u32, Big]
+    value [entry, u32, Big]
+}
+
+///
+/// Position of an entry in a store as the index of its chunk and its position
+/// within the chunk's sorted entries. Postings order by chunk, then entry.
+///
+impl IndexPosting {
+    pub fn new(chunk: usize, entry: usize) -> IndexPosting {
+        IndexPosting {
+            chunk: chunk as u32,
+            entry: entry as u32,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use super::tokenizer;
+
+///
+/// Query over the inverted index. Terms are normalised with the same tokenizer
+/// as indexed messages, so matching is case insensitive.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexQuery {
+    /// Entries containing the token
+    TERM(String),
+    /// Entries matching every sub-query
+    AND(Vec<IndexQuery>),
+    /// Entries matching any sub-query
+    OR(Vec<IndexQuery>),
+    /// Entries containing the tokens consecutively, in order
+    PHRASE(Vec<String>),
+}
+
+impl IndexQuery {
+    ///
+    /// Build a query from free text: a single token becomes a term query, more
+    /// than one become a phrase.
+    ///
+    pub fn parse_text(text: &str) -> IndexQuery {
+        let mut tokens: Vec<String> = tokenizer::tokenize(text.as_bytes());
+        return if tokens.len() == 1 {
+            IndexQuery::TERM(tokens.remove(0))
+        } else {
+            IndexQuery::PHRASE(tokens)
+        };
+    }
+}
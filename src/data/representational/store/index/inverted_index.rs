@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use super::index_posting::IndexPosting;
+use super::index_query::IndexQuery;
+use super::inverted_index_header::InvertedIndexHeader;
+use super::inverted_index_term::InvertedIndexTerm;
+use super::tokenizer;
+
+///
+/// Inverted index from message tokens to the entries containing them, kept in
+/// a sidecar file next to the store. The index records the store header
+/// generation and chunk epoch it reflects. Commits that keep chunk indices
+/// and entries, such as recompression, only move the generation on; a commit
+/// that renumbers chunks, such as a compaction, changes the epoch and makes
+/// the index stale until it is rebuilt from the store. Only chunks in the
+/// store itself are indexed, not archived ones.
+///
+/// Sealed chunks are indexed in memory. Saving the index and rebuilding a
+/// stale one are left to `maintain`, so neither runs on the seal path.
+///
+pub struct InvertedIndex {
+    pub path: String,
+    pub generation: u64,
+    pub chunk_epoch: u64,
+    pub chunk_count: usize,
+    terms: BTreeMap<String, Vec<IndexPosting>>,
+    /// Chunks were indexed since the index was last saved
+    dirty: bool,
+    /// A seal could not be indexed in place, so the index needs a rebuild
+    stale: bool,
+}
+
+impl InvertedIndex {
+    pub fn path_for_store(store_path: &str) -> String {
+        format!("{}.idx", store_path)
+    }
+    ///
+    /// Load the index of a store for its writer, bringing it up to date as
+    /// `open_in_memory` does and saving the result if that changed it.
+    ///
+    /// # Arguments
+    /// * store: Store the index describes
+    ///
+    /// # Returns
+    /// `Result<InvertedIndex>`: Index current with the store
+    ///
+    pub fn open(store: &ChunkStore) -> Result<InvertedIndex, Error> {
+        let mut index: InvertedIndex = Self::open_in_memory(store)?;
+        if index.dirty {
+            index.save()?;
+            index.dirty = false;
+        }
+        return Ok(index);
+    }
+    ///
+    /// Load the index of a store without writing to its sidecar, for readers
+    /// that may not have write access or whose view of the store may be older
+    /// than the writer's. An index saved before chunks were appended or the
+    /// latest chunk was extended is caught up by indexing those chunks; one
+    /// that is missing, unreadable or from another chunk epoch is rebuilt.
+    ///
+    /// # Arguments
+    /// * store: Store the index describes
+    ///
+    /// # Returns
+    /// `Result<InvertedIndex>`: Index current with the store, held in memory
+    ///
+    pub fn open_in_memory(store: &ChunkStore) -> Result<InvertedIndex, Error> {
+        let path: String = Self::path_for_store(store.path.as_str());
+        match Self::load(path.as_str()) {
+            Ok(index) if index.generation == store.generation() && index.is_current(store) => return Ok(index),
+            Ok(mut index) if index.chunk_epoch == store.header.chunk_epoch
+                && index.chunk_count <= store.header.chunk_offsets.len() => {
+                index.catch_up(store)?;
+                return Ok(index);
+            },
+            Ok(_) => info!(crate::LOGGER, "Inverted index {} is stale, rebuilding", path),
+            Err(e) if e.kind() == ErrorKind::NotFound => info!(crate::LOGGER, "Inverted index {} is missing, rebuilding", path),
+            Err(e) => warn!(crate::LOGGER, "Unable to load inverted index {}, rebuilding: {}", path, e),
+        }
+        let mut index: InvertedIndex = InvertedIndex {
+            path,
+            generation: 0,
+            chunk_epoch: 0,
+            chunk_count: 0,
+            terms: BTreeMap::new(),
+            dirty: false,
+            stale: true,
+        };
+        index.rebuild(store)?;
+        return Ok(index);
+    }
+    fn load(path: &str) -> Result<InvertedIndex, Error> {
+        let mut bytes: Vec<u8> = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let mut header: InvertedIndexHeader = InvertedIndexHeader::default();
+        let mut tail: &[u8] = match header.parse_bytes::<&'_ [u8], nom::error::Error<_>>(bytes.as_slice()) {
+            Ok(t) if header.is_valid() => t,
+            _ => return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Inverted index {} has no valid header", path),
+            )),
+        };
+        let mut terms: BTreeMap<String, Vec<IndexPosting>> = BTreeMap::new();
+        for _ in 0..header.terms_length {
+            let mut term: InvertedIndexTerm = InvertedIndexTerm::default();
+            tail = match term.parse_bytes::<&'_ [u8], nom::error::Error<_>>(tail) {
+                Ok(t) => t,
+                Err(e) => return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    e.to_string(),
+                )),
+            };
+            terms.insert(String::from_utf8_lossy(term.token.as_slice()).into_owned(), term.postings);
+        }
+        return Ok(InvertedIndex {
+            path: String::from(path),
+            generation: header.generation,
+            chunk_epoch: header.chunk_epoch,
+            chunk_count: header.chunk_count as usize,
+            terms,
+            dirty: false,
+            stale: false,
+        });
+    }
+    ///
+    /// Write the index to its file. The file is assembled under a unique
+    /// temporary name and renamed into place, so concurrent savers never
+    /// interleave and readers never see a partial index.
+    ///
+    pub fn save(&self) -> Result<(), Error> {
+        let temp_path: String = format!("{}.{}.tmp", self.path, uuid::Uuid::new_v4());
+        let file: File = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(temp_path.as_str())?;
+        let mut writer: BufWriter<File> = BufWriter::new(file);
+        let header: InvertedIndexHeader = InvertedIndexHeader {
+            magic: InvertedIndexHeader::MAGIC,
+            format_version: InvertedIndexHeader::FORMAT_VERSION,
+            generation: self.generation,
+            chunk_epoch: self.chunk_epoch,
+            chunk_count: self.chunk_count as u64,
+            terms_length: self.terms.len() as u64,
+        };
+        writer.write_all(header.into_bytes().as_slice())?;
+        for (token, postings) in self.terms.iter() {
+            let term: InvertedIndexTerm = InvertedIndexTerm {
+                token_length: token.len() as u32,
+                token: token.as_bytes().to_vec(),
+                postings_length: postings.len() as u32,
+                postings: postings.clone(),
+            };
+            writer.write_all(term.into_bytes().as_slice())?;
+        }
+        let file: File = match writer.into_inner() {
+            Ok(f) => f,
+            Err(e) => return Err(e.into_error()),
+        };
+        file.sync_all()?;
+        std::fs::rename(temp_path.as_str(), self.path.as_str())?;
+        return Ok(());
+    }
+    pub fn is_current(&self, store: &ChunkStore) -> bool {
+        !self.stale
+            && self.chunk_epoch == store.header.chunk_epoch
+            && self.chunk_count == store.header.chunk_offsets.len()
+    }
+    ///
+    /// Discard the index and re-index every chunk in the store, in memory.
+    ///
+    pub fn rebuild(&mut self, store: &ChunkStore) -> Result<(), Error> {
+        self.terms.clear();
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        for chunk_index in 0..chunk_count {
+            self.add_chunk(chunk_index, &store.read_chunk(chunk_index)?)?;
+        }
+        self.generation = store.generation();
+        self.chunk_epoch = store.header.chunk_epoch;
+        self.chunk_count = chunk_count;
+        self.stale = false;
+        info!(
+            crate::LOGGER,
+            "Rebuilt inverted index {} over {} chunks with {} terms",
+            self.path,
+            chunk_count,
+            self.terms.len()
+        );
+        self.dirty = true;
+        return Ok(());
+    }
+    ///
+    /// Index the chunks the store gained since the index was saved, re-indexing
+    /// its last chunk in case a writer extended it, in memory.
+    ///
+    fn catch_up(&mut self, store: &ChunkStore) -> Result<(), Error> {
+        let from: usize = self.chunk_count.saturating_sub(1);
+        self.remove_chunks_from(from);
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        for chunk_index in from..chunk_count {
+            self.add_chunk(chunk_index, &store.read_chunk(chunk_index)?)?;
+        }
+        self.generation = store.generation();
+        self.chunk_count = chunk_count;
+        self.dirty = true;
+        return Ok(());
+    }
+    ///
+    /// Index a chunk a writer sealed, in memory only. The sealed chunk replaces
+    /// any postings for a chunk it extended. If the store renumbered its chunks
+    /// since the index was brought up to date, or the chunk does not follow the
+    /// indexed ones, the index is marked stale for `maintain` to rebuild.
+    ///
+    /// # Arguments
+    /// * store: Store the chunk was sealed into
+    /// * chunk_index: Index of the sealed chunk in the store
+    /// * chunk: The sealed chunk
+    ///
+    /// # Returns
+    /// `Result<()>`: Empty result
+    ///
+    pub fn chunk_sealed(&mut self, store: &ChunkStore, chunk_index: usize, chunk: &Chunk) -> Result<(), Error> {
+        if self.stale || self.chunk_epoch != store.header.chunk_epoch || chunk_index > self.chunk_count {
+            self.stale = true;
+            return Ok(());
+        }
+        self.remove_chunks_from(chunk_index);
+        if let Err(e) = self.add_chunk(chunk_index, chunk) {
+            self.stale = true;
+            return Err(e);
+        }
+        self.generation = store.generation();
+        self.chunk_count = store.header.chunk_offsets.len();
+        self.dirty = true;
+        return Ok(());
+    }
+    ///
+    /// Rebuild the index if it is stale or the store renumbered its chunks, and
+    /// save it if chunks were indexed since the last save. Called from the
+    /// writer's periodic housekeeping rather than on every seal.
+    ///
+    pub fn maintain(&mut self, store: &ChunkStore) -> Result<(), Error> {
+        if self.stale || self.chunk_epoch != store.header.chunk_epoch {
+            self.rebuild(store)?;
+        }
+        if !self.dirty {
+            return Ok(());
+        }
+        self.generation = store.generation();
+        self.save()?;
+        self.dirty = false;
+        return Ok(());
+    }
+    fn add_chunk(&mut self, chunk_index: usize, chunk: &Chunk) -> Result<(), Error> {
+        let entries: Vec<ChunkEntry> = match chunk.decode_entries() {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+        for (entry_index, entry) in entries.iter().enumerate() {
+            let tokens: BTreeSet<String> = tokenizer::tokenize(entry.message.as_slice()).into_iter().collect();
+            for token in tokens.into_iter() {
+                self.terms.entry(token).or_default().push(IndexPosting::new(chunk_index, entry_index));
+            }
+        }
+        return Ok(());
+    }
+    fn remove_chunks_from(&mut self, chunk_index: usize) {
+        for postings in self.terms.values_mut() {
+            let keep: usize = postings.partition_point(|p: &IndexPosting| (p.chunk as usize) < chunk_index);
+            postings.truncate(keep);
+        }
+        self.terms.retain(|_, postings: &mut Vec<IndexPosting>| !postings.is_empty());
+    }
+    pub fn postings(&self, token: &str) -> &[IndexPosting] {
+        self.terms.get(token).map_or(&[], |p: &Vec<IndexPosting>| p.as_slice())
+    }
+    ///
+    /// Find the entries matching a query. Term, AND and OR queries are answered
+    /// from the posting lists alone. Phrase queries narrow candidates to entries
+    /// holding every token, then read those entries to check the tokens appear
+    /// consecutively.
+    ///
+    /// # Arguments
+    /// * store: Store the index describes, read for phrase verification
+    /// * query: Query to evaluate
+    ///
+    /// # Returns
+    /// `Result<Vec<IndexPosting>>`: Matching entry positions in store order
+    ///
+    pub fn search(&self, store: &ChunkStore, query: &IndexQuery) -> Result<Vec<IndexPosting>, Error> {
+        return match query {
+            IndexQuery::TERM(token) => Ok(self.postings(token.as_str()).to_vec()),
+            IndexQuery::AND(queries) => {
+                let mut result: Option<Vec<IndexPosting>> = None;
+                for query in queries.iter() {
+                    let postings: Vec<IndexPosting> = self.search(store, query)?;
+                    result = Some(match result {
+                        Some(r) => Self::intersect(r.as_slice(), postings.as_slice()),
+                        None => postings,
+                    });
+                }
+                Ok(result.unwrap_or_default())
+            },
+            IndexQuery::OR(queries) => {
+                let mut result: BTreeSet<IndexPosting> = BTreeSet::new();
+                for query in queries.iter() {
+                    result.extend(self.search(store, query)?);
+                }
+                Ok(result.into_iter().collect())
+            },
+            IndexQuery::PHRASE(tokens) => self.search_phrase(store, tokens.as_slice()),
+        };
+    }
+    fn search_phrase(&self, store: &ChunkStore, tokens: &[String]) -> Result<Vec<IndexPosting>, Error> {
+        let mut candidates: Option<Vec<IndexPosting>> = None;
+        for token in tokens.iter() {
+            let postings: &[IndexPosting] = self.postings(token.as_str());
+            candidates = Some(match candidates {
+                Some(c) => Self::intersect(c.as_slice(), postings),
+                None => postings.to_vec(),
+            });
+        }
+        let candidates: Vec<IndexPosting> = candidates.unwrap_or_default();
+        if tokens.len() < 2 {
+            return Ok(candidates);
+        }
+        let entries: Vec<ChunkEntry> = Self::read_entries(store, candidates.as_slice())?;
+        let mut matches: Vec<IndexPosting> = Vec::new();
+        for (posting, entry) in candidates.into_iter().zip(entries.iter()) {
+            let message_tokens: Vec<String> = tokenizer::tokenize(entry.message.as_slice());
+            if message_tokens.windows(tokens.len()).any(|w: &[String]| w == tokens) {
+                matches.push(posting);
+            }
+        }
+        return Ok(matches);
+    }
+    fn intersect(left: &[IndexPosting], right: &[IndexPosting]) -> Vec<IndexPosting> {
+        let mut result: Vec<IndexPosting> = Vec::new();
+        let (mut i, mut j): (usize, usize) = (0, 0);
+        while i < left.len() && j < right.len() {
+            if left[i] < right[j] {
+                i += 1;
+            } else if right[j] < left[i] {
+                j += 1;
+            } else {
+                result.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+        return result;
+    }
+    ///
+    /// Read the entries at the given positions, decompressing each chunk once.
+    /// Postings must be grouped by chunk, as search results are.
+    ///
+    pub fn read_entries(store: &ChunkStore, postings: &[IndexPosting]) -> Result<Vec<ChunkEntry>, Error> {
+        let mut entries: Vec<ChunkEntry> = Vec::with_capacity(postings.len());
+        let mut current_chunk: Option<(u32, Vec<ChunkEntry>)> = None;
+        for posting in postings.iter() {
+            if current_chunk.as_ref().is_none_or(|(c, _): &(u32, Vec<ChunkEntry>)| *c != posting.chunk) {
+                let chunk_entries: Vec<ChunkEntry> = match store.read_chunk(posting.chunk as usize)?.decode_entries() {
+                    Ok(v) => v,
+                    Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+                };
+                current_chunk = Some((posting.chunk, chunk_entries));
+            }
+            match current_chunk.as_ref().and_then(|(_, e): &(u32, Vec<ChunkEntry>)| e.get(posting.entry as usize)) {
+                Some(entry) => entries.push(entry.clone()),
+                None => return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("No entry {} in chunk {}", posting.entry, posting.chunk),
+                )),
+            }
+        }
+        return Ok(entries);
+    }
+}
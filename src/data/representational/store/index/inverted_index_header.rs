@@ -0,0 +1,43 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct InvertedIndexHeader {
+        #[byte_size=4]
+        pub magic: u32,
+        #[byte_size=2]
+        pub format_version: u16,
+        #[byte_size=8]
+        pub generation: u64,
+        #[byte_size=8]
+        pub chunk_epoch: u64,
+        #[byte_size=8]
+        pub chunk_count: u64,
+        #[byte_size=8]
+        pub terms_length: u64,
+    }
+}
+
+byte_layout!{
+    InvertedIndexHeader
+    value [magic, u32, Big]
+    value [format_version, u16, Big]
+    value [generation, u64, Big]
+    value [chunk_epoch, u64, Big]
+    value [chunk_count, u64, Big]
+    value [terms_length, u64, Big]
+}
+
+///
+/// Leading block of an inverted index file, recording which store header
+/// generation and chunk epoch the index reflects so a stale index can be
+/// detected.
+///
+impl InvertedIndexHeader {
+    pub const MAGIC: u32 = 0x43484B49; // "CHKI"
+    pub const FORMAT_VERSION: u16 = 1;
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == Self::MAGIC && self.format_version == Self::FORMAT_VERSION
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{byte_layout, reify};
+use super::index_posting::IndexPosting;
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct InvertedIndexTerm {
+        #[byte_size=4]
+        pub token_length: u32,
+        pub token: Vec<u8>,
+        #[byte_size=4]
+        pub postings_length: u32,
+        pub postings: Vec<IndexPosting>,
+    }
+}
+
+byte_layout!{
+    InvertedIndexTerm
+    value [token_length, u32, Big]
+    bytes_vec [token, token_length]
+    value [postings_length, u32, Big]
+    composite_vec [postings, postings_length, IndexPosting]
+}
@@ -0,0 +1,8 @@
+pub mod index_posting;
+pub mod index_query;
+pub mod inverted_index;
+pub mod inverted_index_header;
+pub mod inverted_index_term;
+pub mod tokenizer;
+pub mod trigram;
+pub mod trigram_search;
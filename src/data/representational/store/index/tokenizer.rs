@@ -0,0 +1,21 @@
+/// Tokens longer than this are not indexed, as they are almost always ids or encoded payloads
+pub const MAX_TOKEN_LENGTH: usize = 64;
+
+///
+/// Split text into lower cased tokens on every character that is not
+/// alphanumeric. Invalid UTF-8 is replaced rather than rejected, as messages
+/// are arbitrary bytes.
+///
+/// # Arguments
+/// * text: Bytes to tokenize, such as an entry's message
+///
+/// # Returns
+/// `Vec<String>`: Tokens in the order they appear, including repeats
+///
+pub fn tokenize(text: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t: &&str| !t.is_empty() && t.len() <= MAX_TOKEN_LENGTH)
+        .map(|t: &str| t.to_lowercase())
+        .collect()
+}
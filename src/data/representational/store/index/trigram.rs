@@ -0,0 +1,111 @@
+use std::collections::BTreeSet;
+use regex_syntax::hir::{Hir, HirKind};
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+pub type Trigram = [u8; 3];
+
+///
+/// Every distinct trigram of the given text, as raw bytes.
+///
+pub fn trigrams_of(text: &[u8]) -> BTreeSet<Trigram> {
+    text.windows(3)
+        .map(|w: &[u8]| [w[0], w[1], w[2]])
+        .collect()
+}
+
+///
+/// Every distinct trigram across the messages of the given entries. Trigrams
+/// never span two entries.
+///
+pub fn entry_trigrams(entries: &[ChunkEntry]) -> BTreeSet<Trigram> {
+    let mut trigrams: BTreeSet<Trigram> = BTreeSet::new();
+    for entry in entries.iter() {
+        trigrams.extend(trigrams_of(entry.message.as_slice()));
+    }
+    return trigrams;
+}
+
+///
+/// Trigrams a message must contain for a regex to possibly match it, derived
+/// from the literals the regex requires. Parts of a regex that do not require
+/// a literal of at least three bytes, such as classes, optional repetitions or
+/// case insensitive text, require nothing, so the query only ever errs towards
+/// keeping a chunk.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrigramQuery {
+    /// Any text may match
+    ALL,
+    /// Text must contain every trigram
+    TRIGRAMS(Vec<Trigram>),
+    /// Text must satisfy every sub-query
+    AND(Vec<TrigramQuery>),
+    /// Text must satisfy at least one sub-query
+    OR(Vec<TrigramQuery>),
+}
+
+impl TrigramQuery {
+    ///
+    /// Derive the trigram query for a regex pattern.
+    ///
+    /// # Arguments
+    /// * pattern: Regex in the syntax of the `regex` crate
+    ///
+    /// # Returns
+    /// `Result<TrigramQuery>`: Required trigrams, or the parse error of an invalid pattern
+    ///
+    pub fn from_regex(pattern: &str) -> Result<TrigramQuery, Box<regex_syntax::Error>> {
+        let hir: Hir = regex_syntax::parse(pattern).map_err(Box::new)?;
+        return Ok(Self::from_hir(&hir));
+    }
+    pub fn from_substring(needle: &[u8]) -> TrigramQuery {
+        Self::from_literal(needle)
+    }
+    fn from_literal(literal: &[u8]) -> TrigramQuery {
+        let trigrams: BTreeSet<Trigram> = trigrams_of(literal);
+        return if trigrams.is_empty() {
+            TrigramQuery::ALL
+        } else {
+            TrigramQuery::TRIGRAMS(trigrams.into_iter().collect())
+        };
+    }
+    fn from_hir(hir: &Hir) -> TrigramQuery {
+        return match hir.kind() {
+            HirKind::Literal(literal) => Self::from_literal(&literal.0),
+            HirKind::Capture(capture) => Self::from_hir(&capture.sub),
+            HirKind::Repetition(repetition) if repetition.min > 0 => Self::from_hir(&repetition.sub),
+            HirKind::Concat(subs) => {
+                let queries: Vec<TrigramQuery> = subs.iter()
+                    .map(Self::from_hir)
+                    .filter(|q: &TrigramQuery| *q != TrigramQuery::ALL)
+                    .collect();
+                match queries.len() {
+                    0 => TrigramQuery::ALL,
+                    1 => queries.into_iter().next().unwrap_or(TrigramQuery::ALL),
+                    _ => TrigramQuery::AND(queries),
+                }
+            },
+            HirKind::Alternation(subs) => {
+                let queries: Vec<TrigramQuery> = subs.iter().map(Self::from_hir).collect();
+                if queries.contains(&TrigramQuery::ALL) {
+                    TrigramQuery::ALL
+                } else {
+                    TrigramQuery::OR(queries)
+                }
+            },
+            _ => TrigramQuery::ALL,
+        };
+    }
+    ///
+    /// Whether text whose trigrams are in the filter may satisfy the query.
+    ///
+    pub fn may_match(&self, filter: &ChunkBloomFilter) -> bool {
+        return match self {
+            TrigramQuery::ALL => true,
+            TrigramQuery::TRIGRAMS(trigrams) => trigrams.iter().all(|t: &Trigram| filter.might_contain(t)),
+            TrigramQuery::AND(queries) => queries.iter().all(|q: &TrigramQuery| q.may_match(filter)),
+            TrigramQuery::OR(queries) => queries.iter().any(|q: &TrigramQuery| q.may_match(filter)),
+        };
+    }
+}
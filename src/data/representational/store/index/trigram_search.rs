@@ -0,0 +1,131 @@
+use std::io::{Error, ErrorKind};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::query::query::{Query, QueryExpression, TimeBound};
+use crate::query::query_planner::{ChunkCondition, EntryPredicate, QueryPlan, QueryPlanner};
+use super::trigram::TrigramQuery;
+
+impl ChunkStore {
+    ///
+    /// Indices of the chunks overlapping a time range whose trigram filter does
+    /// not rule out the query. Filters are read from the chunks' metadata
+    /// sections, so pruned chunks are never decompressed.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    /// * query: Trigrams a matching message must contain
+    ///
+    /// # Returns
+    /// `Result<Vec<usize>>`: Candidate chunk indices in store order
+    ///
+    pub fn chunks_for_trigrams(&self, from: u64, to: u64, query: &TrigramQuery) -> Result<Vec<usize>, Error> {
+        let plan: QueryPlan = QueryPlan {
+            from,
+            to,
+            chunk_condition: ChunkCondition::AND(vec![
+                ChunkCondition::TIME(from, to),
+                ChunkCondition::TRIGRAMS(query.clone()),
+            ]),
+            index_query: None,
+            predicate: EntryPredicate::ALL,
+            stages: Vec::new(),
+        };
+        return plan.candidate_chunks(self);
+    }
+    ///
+    /// Find the entries in a time range whose message matches a regex. The
+    /// query executor prunes chunks by the trigrams the regex requires, then
+    /// confirms every entry of the remaining chunks against the regex itself.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    /// * pattern: Regex in the syntax of the `regex` crate
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkEntry>>`: Matching entries in timestamp order, or `InvalidInput` if the regex is invalid
+    ///
+    pub fn search_regex(&self, from: u64, to: u64, pattern: &str) -> Result<Vec<ChunkEntry>, Error> {
+        return self.search_messages(from, to, QueryExpression::REGEX(String::from(pattern)));
+    }
+    ///
+    /// Find the entries in a time range whose message contains the given text.
+    ///
+    pub fn search_substring(&self, from: u64, to: u64, needle: &str) -> Result<Vec<ChunkEntry>, Error> {
+        return self.search_messages(from, to, QueryExpression::TEXT(String::from(needle)));
+    }
+    fn search_messages(&self, from: u64, to: u64, expression: QueryExpression) -> Result<Vec<ChunkEntry>, Error> {
+        let query: Query = Query {
+            expression: Some(QueryExpression::AND(
+                Box::new(QueryExpression::TIME_RANGE(TimeBound::ABSOLUTE(from), TimeBound::ABSOLUTE(to))),
+                Box::new(expression),
+            )),
+            stages: Vec::new(),
+        };
+        let plan: QueryPlan = match QueryPlanner::plan(&query, 0) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.message)),
+        };
+        let mut matches: Vec<ChunkEntry> = Vec::new();
+        plan.for_each_match(self, &mut |entry: &ChunkEntry| {
+            matches.push(entry.clone());
+            return Ok(());
+        })?;
+        return Ok(matches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::bytes::Regex;
+    use super::*;
+    use crate::utils::test_utils::{chunk_with, temp_store_path};
+
+    fn brute_force(store: &ChunkStore, from: u64, to: u64, pattern: &str) -> Vec<u64> {
+        let regex: Regex = Regex::new(pattern).unwrap();
+        let mut timestamps: Vec<u64> = Vec::new();
+        for index in 0..store.header.chunk_offsets.len() {
+            for entry in store.read_chunk(index).unwrap().decode_entries().ok().expect("Could not decode entries") {
+                if entry.timestamp >= from && entry.timestamp <= to && regex.is_match(entry.message.as_slice()) {
+                    timestamps.push(entry.timestamp);
+                }
+            }
+        }
+        timestamps.sort();
+        return timestamps;
+    }
+
+    fn timestamps(entries: Vec<ChunkEntry>) -> Vec<u64> {
+        return entries.iter().map(|e: &ChunkEntry| e.timestamp).collect();
+    }
+
+    #[test]
+    fn prunes_chunks_without_missing_regex_matches() {
+        let path: String = temp_store_path("prunes_chunks_without_missing_regex_matches");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        store.append_chunk(chunk_with(&[(1, "connection refused by peer"), (2, "retrying in 5s")])).unwrap();
+        store.append_chunk(chunk_with(&[(3, "disk usage at 91%"), (4, "disk usage at 97%")])).unwrap();
+        store.append_chunk(chunk_with(&[(5, "connection reset"), (6, "user admin logged in")])).unwrap();
+        store.append_chunk(chunk_with(&[(7, "Connection refused (a.b)"), (8, "checksum ok")])).unwrap();
+
+        for pattern in ["connection (refused|reset)", "usage at 9[0-9]%", "(?i)connection", "logged", "nowhere", "^re"] {
+            assert_eq!(timestamps(store.search_regex(0, u64::MAX, pattern).unwrap()), brute_force(&store, 0, u64::MAX, pattern), "{}", pattern);
+            assert_eq!(timestamps(store.search_regex(2, 6, pattern).unwrap()), brute_force(&store, 2, 6, pattern), "{}", pattern);
+        }
+        for needle in ["refused", "(a.b)", "at 9", "%"] {
+            let pattern: String = regex::escape(needle);
+            assert_eq!(timestamps(store.search_substring(0, u64::MAX, needle).unwrap()), brute_force(&store, 0, u64::MAX, pattern.as_str()), "{}", needle);
+        }
+
+        // Only chunks whose trigram filter may hold the required text are read
+        let refused: TrigramQuery = TrigramQuery::from_regex("connection refused").unwrap();
+        assert_eq!(store.chunks_for_trigrams(0, u64::MAX, &refused).unwrap(), vec![0]);
+        let either: TrigramQuery = TrigramQuery::from_regex("connection (refused|reset)").unwrap();
+        assert_eq!(store.chunks_for_trigrams(0, u64::MAX, &either).unwrap(), vec![0, 2]);
+        assert_eq!(store.chunks_for_trigrams(3, u64::MAX, &either).unwrap(), vec![2]);
+        assert_eq!(store.chunks_for_trigrams(0, u64::MAX, &TrigramQuery::ALL).unwrap(), vec![0, 1, 2, 3]);
+
+        assert_eq!(store.search_regex(0, u64::MAX, "(unclosed").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
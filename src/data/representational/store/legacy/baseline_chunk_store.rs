@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use nom::AsBytes;
+use crate::{byte_layout, reify};
+use crate::compression::compressor::CompressionCodec;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+// Layout of the store files written before header slots existed: the header at
+// offset 0, then the chunk count and the chunks, with chunk offsets relative to
+// the end of the chunk count. Only read for migration to the current format.
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct BaselineChunkOffsets {
+        #[byte_size=4]
+        pub sector_index: u32,
+        #[byte_size=2]
+        pub sector_offset: u16,
+    }
+}
+
+byte_layout!{
+    BaselineChunkOffsets
+    value [sector_index, u32, Big]
+    value [sector_offset, u16, Big]
+}
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct BaselineChunkStoreHeader {
+        #[byte_size=8]
+        pub length: u64,
+        #[byte_size=2]
+        pub sector_size: u16,
+        #[byte_size=2]
+        pub chunk_count: u16,
+        #[byte_size=4]
+        pub chunk_offsets_length: u32,
+        pub chunk_offsets: Vec<BaselineChunkOffsets>,
+    }
+}
+
+byte_layout!{
+    BaselineChunkStoreHeader
+    value [length, u64, Big]
+    value [sector_size, u16, Big]
+    value [chunk_count, u16, Big]
+    value [chunk_offsets_length, u32, Big]
+    composite_vec [chunk_offsets, chunk_offsets_length, BaselineChunkOffsets]
+}
+
+reify!{
+    #[derive(Debug,Default,Clone)]
+    pub struct BaselineChunkPrefix {
+        #[byte_size=4]
+        pub length: u32,
+        #[byte_size=8]
+        pub timestamp_from: u64,
+        #[byte_size=8]
+        pub timestamp_to: u64,
+        #[byte_size=4]
+        pub entries_length: u32,
+    }
+}
+
+byte_layout!{
+    BaselineChunkPrefix
+    value [length, u32, Big]
+    value [timestamp_from, u64, Big]
+    value [timestamp_to, u64, Big]
+    value [entries_length, u32, Big]
+}
+
+///
+/// Contents of a baseline store, with its chunks converted to the current
+/// `Chunk` layout.
+///
+#[derive(Debug, Default)]
+pub struct BaselineChunkStore {
+    pub header: BaselineChunkStoreHeader,
+    pub chunks: Vec<Chunk>,
+}
+
+impl BaselineChunkStore {
+    pub const PREFIX_LENGTH: u64 = 24;
+    /// Bytes of the chunk count between the header and the first chunk
+    pub const CHUNKS_LENGTH_LENGTH: u64 = 8;
+
+    ///
+    /// Read a baseline store. The file is only taken to be one if its header
+    /// length, header and chunk count agree with each other and the file.
+    ///
+    /// # Arguments
+    /// * file: Store file, read from the start
+    ///
+    /// # Returns
+    /// `Result<Option<BaselineChunkStore>>`: Store and its chunks, `None` if the file is not a baseline store,
+    /// or `InvalidData` if it is one whose chunks cannot be read
+    ///
+    pub fn read_from_file(file: &mut File) -> Result<Option<BaselineChunkStore>, Error> {
+        let file_length: u64 = file.metadata()?.len();
+        let mut length_bytes: [u8; 8] = [0x00u8; 8];
+        file.seek(SeekFrom::Start(0))?;
+        if file_length < length_bytes.len() as u64 {
+            return Ok(None);
+        }
+        file.read_exact(&mut length_bytes)?;
+        let header_length: u64 = u64::from_be_bytes(length_bytes);
+        let data_offset: u64 = match header_length.checked_add(Self::CHUNKS_LENGTH_LENGTH) {
+            Some(v) if header_length >= 16 && v <= file_length => v,
+            _ => return Ok(None),
+        };
+        let mut header_bytes: Vec<u8> = vec![0x00u8; data_offset as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(header_bytes.as_mut_slice())?;
+        let mut store: BaselineChunkStore = BaselineChunkStore::default();
+        if store.header.parse_bytes::<&'_ [u8], nom::error::Error<_>>(&header_bytes[..header_length as usize]).is_err()
+            || store.header.into_bytes().len() as u64 != header_length {
+            return Ok(None);
+        }
+        let mut chunks_length: [u8; 8] = [0x00u8; 8];
+        chunks_length.copy_from_slice(&header_bytes[header_length as usize..]);
+        if u64::from_be_bytes(chunks_length) != store.header.chunk_offsets_length as u64 {
+            return Ok(None);
+        }
+        for (index, offsets) in store.header.chunk_offsets.iter().enumerate() {
+            let offset: u64 = data_offset
+                + offsets.sector_index as u64 * store.header.sector_size as u64
+                + offsets.sector_offset as u64;
+            store.chunks.push(Self::read_chunk(file, file_length, offset, index)?);
+        }
+        return Ok(Some(store));
+    }
+    ///
+    /// Read a chunk and convert it to the current layout. The baseline writer
+    /// stored the entry count in `entries_length`, so the entries are taken to
+    /// run to the end of the chunk instead. Only the entries are kept; they
+    /// are sealed into a new chunk, which sorts them and takes its bounds from
+    /// them.
+    ///
+    fn read_chunk(file: &mut File, file_length: u64, offset: u64, index: usize) -> Result<Chunk, Error> {
+        let overrun = || Error::new(
+            ErrorKind::InvalidData,
+            format!("Baseline chunk {} overruns the store", index),
+        );
+        if offset.saturating_add(Self::PREFIX_LENGTH) > file_length {
+            return Err(overrun());
+        }
+        let mut prefix_bytes: Vec<u8> = vec![0x00u8; Self::PREFIX_LENGTH as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(prefix_bytes.as_mut_slice())?;
+        let mut prefix: BaselineChunkPrefix = BaselineChunkPrefix::default();
+        if let Err(e) = prefix.parse_bytes::<&'_ [u8], nom::error::Error<_>>(prefix_bytes.as_bytes()) {
+            return Err(Error::new(ErrorKind::InvalidData, e.to_string()));
+        }
+        if (prefix.length as u64) < Self::PREFIX_LENGTH || offset + prefix.length as u64 > file_length {
+            return Err(overrun());
+        }
+        let mut entries: Vec<u8> = vec![0x00u8; prefix.length as usize - Self::PREFIX_LENGTH as usize];
+        file.read_exact(entries.as_mut_slice())?;
+        let baseline: Chunk = Chunk {
+            codec: CompressionCodec::ZLIB_DEFAULT.id(),
+            checksum: Chunk::checksum_of(&entries),
+            entries_length: entries.len() as u32,
+            entries,
+            ..Chunk::default()
+        };
+        let entries: Vec<ChunkEntry> = match baseline.decode_entries() {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Could not read entries of baseline chunk {}: {}", index, e),
+            )),
+        };
+        // Resealed rather than kept as written, as baseline entries were not
+        // sorted on seal and the prefix bounds were not taken from the entries
+        let chunk: Chunk = match Chunk::seal_with(&entries, CompressionCodec::ZLIB_DEFAULT, ChunkBloomFilter::DEFAULT_FALSE_POSITIVE_RATE) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Could not reseal baseline chunk {}: {}", index, e),
+            )),
+        };
+        return Ok(chunk);
+    }
+}
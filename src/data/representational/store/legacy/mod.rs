@@ -0,0 +1 @@
+pub mod baseline_chunk_store;
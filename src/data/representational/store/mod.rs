@@ -1,3 +1,22 @@
+pub mod archive;
+pub mod chunk_inspection;
 pub mod chunk_store;
 pub mod chunk_store_header;
+pub mod chunk_store_header_slot;
+pub mod chunk_store_view;
+pub mod chunk_store_writer;
 pub mod chunk_offsets;
+pub mod compaction;
+pub mod entry_iterator;
+pub mod free_sector_range;
+pub mod index;
+pub mod legacy;
+pub mod multi_store_reader;
+pub mod reader_registry;
+pub mod recompression;
+pub mod retention;
+pub mod segment;
+pub mod store_write_lock;
+pub mod subscription;
+pub mod writer_maintenance;
+pub mod retired_region;
@@ -0,0 +1,282 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::query::entry_merge::EntryMerge;
+use crate::query::query::{Query, QueryExpression, QueryStage, TimeBound};
+use crate::query::query_executor::{OpenArchive, PendingChunk};
+use crate::query::query_planner::{QueryPlan, QueryPlanner};
+use super::chunk_store::ChunkStore;
+use super::segment::segment_manifest::SegmentManifest;
+
+///
+/// Entry read through a `MultiStoreReader`, with the name of the source it
+/// came from.
+///
+#[derive(Debug, Clone)]
+pub struct SourcedEntry {
+    pub source: String,
+    pub entry: ChunkEntry,
+}
+
+///
+/// A store file, or the segments of a segment directory, read as one timeline.
+///
+pub struct StoreSource {
+    pub name: String,
+    pub stores: Vec<ChunkStore>,
+}
+
+///
+/// Reader presenting several stores, typically one per service, as a single
+/// timeline. Results from every source are merged by entry timestamp and
+/// tagged with the source they came from. Only sealed chunks are visible; the
+/// open chunk of a store being written to is not.
+///
+#[derive(Default)]
+pub struct MultiStoreReader {
+    pub sources: Vec<StoreSource>,
+}
+
+impl MultiStoreReader {
+    pub fn new() -> MultiStoreReader {
+        return MultiStoreReader { sources: Vec::new() };
+    }
+    ///
+    /// Open each path as a source, as a segment directory if it is a directory
+    /// and as a single store file otherwise. Sources are named by their path.
+    ///
+    /// # Arguments
+    /// * paths: Store files and segment directories to read
+    ///
+    /// # Returns
+    /// `Result<MultiStoreReader>`: Reader over every path
+    ///
+    pub fn open(paths: &[&str]) -> Result<MultiStoreReader, Error> {
+        let mut reader: MultiStoreReader = MultiStoreReader::new();
+        for path in paths.iter() {
+            if Path::new(path).is_dir() {
+                reader.add_segment_directory(path)?;
+            } else {
+                reader.add_store(path, ChunkStore::read_from_file(path)?);
+            }
+        }
+        return Ok(reader);
+    }
+    pub fn add_store(&mut self, name: &str, store: ChunkStore) {
+        self.sources.push(StoreSource {
+            name: String::from(name),
+            stores: vec![store],
+        });
+    }
+    pub fn add_segment_directory(&mut self, directory: &str) -> Result<(), Error> {
+        let manifest: SegmentManifest = SegmentManifest::load(directory)?;
+        let mut stores: Vec<ChunkStore> = Vec::with_capacity(manifest.segments.len());
+        for segment in manifest.segments.iter() {
+            let path: String = Path::new(directory).join(segment.file_name.as_str()).to_string_lossy().into_owned();
+            // The manifest may lag behind the active segment, so it is read even
+            // when recorded as empty, unless it has not been created yet
+            if Path::new(path.as_str()).exists() {
+                stores.push(ChunkStore::read_from_file(path.as_str())?);
+            }
+        }
+        self.sources.push(StoreSource {
+            name: String::from(directory),
+            stores,
+        });
+        return Ok(());
+    }
+    ///
+    /// Entries of every source within a time range, in timestamp order.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    ///
+    /// # Returns
+    /// `Result<MergedEntries>`: Lazy merge of the sources
+    ///
+    pub fn range(&self, from: u64, to: u64) -> Result<MergedEntries<'_>, Error> {
+        let query: Query = Query {
+            expression: Some(QueryExpression::TIME_RANGE(TimeBound::ABSOLUTE(from), TimeBound::ABSOLUTE(to))),
+            stages: Vec::new(),
+        };
+        return match QueryPlanner::plan(&query, 0) {
+            Ok(plan) => self.merge(plan),
+            Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+    }
+    ///
+    /// Entries of every source matching a query, in timestamp order. The query
+    /// may end in `| limit n` but cannot aggregate.
+    ///
+    /// # Arguments
+    /// * text: Query text
+    ///
+    /// # Returns
+    /// `Result<MergedEntries>`: Lazy merge of the matches, or `InvalidInput` if the query is invalid
+    ///
+    pub fn query(&self, text: &str) -> Result<MergedEntries<'_>, Error> {
+        let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        return match QueryPlanner::plan_text(text, now) {
+            Ok(plan) => self.merge(plan),
+            Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+    }
+    fn merge(&self, plan: QueryPlan) -> Result<MergedEntries<'_>, Error> {
+        let mut limit: usize = usize::MAX;
+        for stage in plan.stages.iter() {
+            match stage {
+                QueryStage::LIMIT(n) => limit = limit.min(*n),
+                QueryStage::AGGREGATE(_) => return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Merged queries cannot aggregate",
+                )),
+            }
+        }
+        let mut chunks: Vec<(u64, usize, (usize, usize, PendingChunk))> = Vec::new();
+        for (source, store_source) in self.sources.iter().enumerate() {
+            for (store, chunk_store) in store_source.stores.iter().enumerate() {
+                for pending in plan.pending_chunks(chunk_store)?.into_iter() {
+                    chunks.push((pending.timestamp_from, source, (source, store, pending)));
+                }
+            }
+        }
+        return Ok(MergedEntries {
+            reader: self,
+            plan,
+            merge: EntryMerge::new(chunks),
+            remaining: limit,
+            archive: None,
+            failed: false,
+        });
+    }
+}
+
+///
+/// K-way merge by timestamp over the candidate chunks of every source. A chunk
+/// is only decompressed once the merge reaches its first timestamp, so the
+/// chunks held at once are those overlapping in time, not every chunk in the
+/// range. Entries with equal timestamps come out in source order, then in the
+/// order they were written.
+///
+pub struct MergedEntries<'a> {
+    reader: &'a MultiStoreReader,
+    plan: QueryPlan,
+    /// Chunks ranked by source, located by source, store and chunk
+    merge: EntryMerge<(usize, usize, PendingChunk)>,
+    remaining: usize,
+    archive: OpenArchive,
+    failed: bool,
+}
+
+impl<'a> Iterator for MergedEntries<'a> {
+    type Item = Result<SourcedEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.remaining == 0 {
+            return None;
+        }
+        let sources: &[StoreSource] = self.reader.sources.as_slice();
+        let plan: &QueryPlan = &self.plan;
+        let archive: &mut OpenArchive = &mut self.archive;
+        let next: Result<Option<(usize, ChunkEntry)>, Error> = self.merge.next_entry(
+            &mut |(source, store, pending): (usize, usize, PendingChunk)| plan.chunk_matches(&sources[source].stores[store], pending, archive),
+        );
+        let (source, entry): (usize, ChunkEntry) = match next {
+            Ok(next) => next?,
+            Err(e) => {
+                self.failed = true;
+                return Some(Err(e));
+            },
+        };
+        self.remaining -= 1;
+        return Some(Ok(SourcedEntry {
+            source: self.reader.sources[source].name.clone(),
+            entry,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk::Chunk;
+    use std::time::Duration;
+    use super::super::segment::segmented_store::{SegmentedStore, SegmentedStoreConfig};
+    use crate::utils::test_utils::{entry_at, temp_store_path};
+
+    fn entry_of(target: &str, timestamp: u64) -> ChunkEntry {
+        return ChunkEntry {
+            target: target.as_bytes().to_vec(),
+            message: format!("{} at {}", target, timestamp).into_bytes(),
+            ..entry_at(timestamp)
+        };
+    }
+
+    fn store_of(name: &str, target: &str, chunks: &[&[u64]]) -> String {
+        let path: String = temp_store_path(name);
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamps in chunks.iter() {
+            let entries: Vec<ChunkEntry> = timestamps.iter().map(|t: &u64| entry_of(target, *t)).collect();
+            store.append_chunk(Chunk::seal(entries.as_slice()).ok().expect("Could not seal chunk")).unwrap();
+        }
+        return path;
+    }
+
+    fn segment_directory_of(name: &str, target: &str, timestamps: &[u64]) -> String {
+        let directory: String = temp_store_path(name);
+        let mut config: SegmentedStoreConfig = SegmentedStoreConfig::default();
+        config.writer.sector_size = 64;
+        config.writer.max_chunk_entries = 2;
+        config.writer.recompression = None;
+        config.writer.compaction = None;
+        config.segment_window = Some(Duration::from_millis(10));
+        let mut store: SegmentedStore = SegmentedStore::open(directory.as_str(), config).unwrap();
+        for timestamp in timestamps.iter() {
+            store.append(entry_of(target, *timestamp)).unwrap();
+        }
+        store.flush().unwrap();
+        assert!(store.manifest.segments.len() > 1);
+        return directory;
+    }
+
+    fn sourced(entries: MergedEntries) -> Vec<(u64, String)> {
+        return entries
+            .map(|e: Result<SourcedEntry, Error>| {
+                let sourced: SourcedEntry = e.unwrap();
+                // Every entry names its store in its target, so tags can be checked against it
+                assert!(sourced.source.contains(String::from_utf8_lossy(sourced.entry.target.as_slice()).as_ref()));
+                (sourced.entry.timestamp, String::from_utf8(sourced.entry.target).unwrap())
+            })
+            .collect();
+    }
+
+    #[test]
+    fn merges_stores_and_segments_by_timestamp() {
+        let first: String = store_of("merges_stores_and_segments_by_timestamp_alpha", "alpha", &[&[1, 4, 7], &[10, 13]]);
+        // Overlapping chunks within a store are merged too
+        let second: String = store_of("merges_stores_and_segments_by_timestamp_beta", "beta", &[&[2, 5, 8], &[4, 11]]);
+        let segments: String = segment_directory_of("merges_stores_and_segments_by_timestamp_gamma", "gamma", &[3, 6, 9, 12, 15, 4]);
+        let reader: MultiStoreReader = MultiStoreReader::open(&[first.as_str(), second.as_str(), segments.as_str()]).unwrap();
+        assert_eq!(reader.sources.len(), 3);
+        assert!(reader.sources[2].stores.len() > 1);
+
+        let expected: Vec<(u64, &str)> = vec![
+            (1, "alpha"), (2, "beta"), (3, "gamma"),
+            // Equal timestamps come out in source order
+            (4, "alpha"), (4, "beta"), (4, "gamma"),
+            (5, "beta"), (6, "gamma"), (7, "alpha"), (8, "beta"), (9, "gamma"),
+            (10, "alpha"), (11, "beta"), (12, "gamma"), (13, "alpha"), (15, "gamma"),
+        ];
+        let merged: Vec<(u64, String)> = sourced(reader.range(0, u64::MAX).unwrap());
+        assert_eq!(merged, expected.iter().map(|(t, s): &(u64, &str)| (*t, String::from(*s))).collect::<Vec<(u64, String)>>());
+
+        let window: Vec<u64> = sourced(reader.range(5, 10).unwrap()).iter().map(|(t, _): &(u64, String)| *t).collect();
+        assert_eq!(window, vec![5, 6, 7, 8, 9, 10]);
+        assert_eq!(
+            sourced(reader.query("target:gamma | limit 3").unwrap()),
+            vec![(3, String::from("gamma")), (4, String::from("gamma")), (6, String::from("gamma"))],
+        );
+        assert_eq!(reader.query("| count").err().unwrap().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs::{File, OpenOptions};
+use std::io::Error;
+use std::path::PathBuf;
+use fs2::FileExt;
+
+///
+/// Tracks the header generations that open readers of a store were taken at,
+/// so space they may still read is not reused or truncated underneath them.
+/// Each reader holds a lease file in a directory beside the store, named by
+/// its generation and exclusively locked for as long as the lease is held.
+/// Leases are shared by every instance and process opening the store, and the
+/// lease of a process that died without releasing it is recognised by its
+/// lock being free and removed.
+///
+#[derive(Debug, Default, Clone)]
+pub struct ReaderRegistry {
+    directory: Option<PathBuf>,
+}
+
+impl ReaderRegistry {
+    const PENDING_SUFFIX: &'static str = ".pending";
+
+    pub fn for_store(path: &str) -> ReaderRegistry {
+        return ReaderRegistry {
+            directory: Some(PathBuf::from(format!("{}.readers", path))),
+        };
+    }
+    ///
+    /// Take a lease on a generation. The lease file is locked under a pending
+    /// name before it is renamed into place, so it is never seen unlocked.
+    ///
+    pub fn acquire(&self, generation: u64) -> Result<ReadLease, Error> {
+        let directory: &PathBuf = match &self.directory {
+            Some(d) => d,
+            None => return Ok(ReadLease {
+                generation,
+                path: None,
+                _file: None,
+            }),
+        };
+        std::fs::create_dir_all(directory)?;
+        let name: String = format!("{:020}.{}", generation, uuid::Uuid::new_v4());
+        let pending_path: PathBuf = directory.join(format!("{}{}", name, Self::PENDING_SUFFIX));
+        let file: File = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&pending_path)?;
+        file.lock_exclusive()?;
+        let path: PathBuf = directory.join(name);
+        std::fs::rename(&pending_path, &path)?;
+        return Ok(ReadLease {
+            generation,
+            path: Some(path),
+            _file: Some(file),
+        });
+    }
+    ///
+    /// Oldest generation leased by any live reader, removing leases whose
+    /// holder has gone.
+    ///
+    pub fn oldest_generation(&self) -> Result<Option<u64>, Error> {
+        let directory: &PathBuf = match &self.directory {
+            Some(d) if d.exists() => d,
+            _ => return Ok(None),
+        };
+        let mut oldest: Option<u64> = None;
+        for dir_entry in std::fs::read_dir(directory)? {
+            let path: PathBuf = dir_entry?.path();
+            let name: String = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+            if name.ends_with(Self::PENDING_SUFFIX) {
+                continue;
+            }
+            let generation: u64 = match name.split('.').next().and_then(|g: &str| g.parse::<u64>().ok()) {
+                Some(g) => g,
+                None => continue,
+            };
+            if oldest.is_some_and(|o: u64| o <= generation) {
+                continue;
+            }
+            let file: File = match File::open(&path) {
+                Ok(f) => f,
+                // Released since the directory was listed
+                Err(_) => continue,
+            };
+            if file.try_lock_exclusive().is_ok() {
+                warn!(crate::LOGGER, "Removing abandoned read lease {}", path.display());
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            oldest = Some(generation);
+        }
+        return Ok(oldest);
+    }
+    ///
+    /// Remove the lease directory if no leases remain in it, for stores that
+    /// are being deleted or renamed.
+    ///
+    pub fn remove_directory(&self) {
+        if let Some(directory) = &self.directory {
+            let _ = std::fs::remove_dir(directory);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadLease {
+    pub generation: u64,
+    path: Option<PathBuf>,
+    _file: Option<File>,
+}
+
+impl Drop for ReadLease {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
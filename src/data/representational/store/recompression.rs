@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use crate::compression::compressor::CompressionCodec;
+use crate::configuration::config::Config;
+use crate::data::representational::chunk::Chunk;
+use super::chunk_offsets::ChunkOffsets;
+use super::chunk_store::ChunkStore;
+use super::store_write_lock::StoreWriteLock;
+
+#[derive(Debug, Clone)]
+pub struct RecompressionConfig {
+    /// Chunks whose newest entry is older than this are recompressed
+    pub min_age: Duration,
+    /// Codec aged chunks are recompressed with
+    pub codec: CompressionCodec,
+    /// Upper bound on chunks rewritten per run, bounding how long the writer is held to swap them in
+    pub max_chunks_per_run: usize,
+    /// How often a writer's maintenance thread runs recompression
+    pub interval: Duration,
+}
+
+impl Default for RecompressionConfig {
+    fn default() -> Self {
+        RecompressionConfig {
+            min_age: Duration::from_millis(24 * 60 * 60 * 1000),
+            codec: CompressionCodec::ZLIB_BEST,
+            max_chunks_per_run: 64,
+            interval: Duration::from_millis(60 * 1000),
+        }
+    }
+}
+
+impl RecompressionConfig {
+    pub fn from_config(config: &mut Config) -> RecompressionConfig {
+        let defaults: RecompressionConfig = RecompressionConfig::default();
+        let codec: CompressionCodec = match CompressionCodec::from_name(
+            config.get_or_default("store.recompression.codec", String::from("zlib_best")).as_str(),
+        ) {
+            Some(c) => c,
+            None => {
+                warn!(crate::LOGGER, "Unknown recompression codec, defaulting to zlib_best");
+                defaults.codec
+            },
+        };
+        RecompressionConfig {
+            min_age: Duration::from_millis(config.get_or_default(
+                "store.recompression.min_age_ms",
+                defaults.min_age.as_millis() as u64,
+            )),
+            codec,
+            max_chunks_per_run: config.get_or_default("store.recompression.max_chunks_per_run", defaults.max_chunks_per_run),
+            interval: Duration::from_millis(config.get_or_default(
+                "store.recompression.interval_ms",
+                defaults.interval.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RecompressionSummary {
+    pub chunks_recompressed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+///
+/// Aged chunk re-encoded with the recompression codec, waiting to be swapped
+/// in for the original.
+///
+#[derive(Debug, Clone)]
+pub struct RecompressedChunk {
+    /// Where the original chunk sits, identifying it however the chunk list has changed since
+    pub original: ChunkOffsets,
+    pub original_length: u64,
+    pub chunk: Chunk,
+}
+
+impl ChunkStore {
+    ///
+    /// Re-encode chunks older than the configured age with the configured codec,
+    /// oldest first. Each replacement is written to free space and swapped in by
+    /// a single header commit, retiring the original, so readers see the same
+    /// entries before and after. The latest chunk is left alone as a writer may
+    /// be extending it.
+    ///
+    /// # Arguments
+    /// * config: Age threshold, target codec and per-run limit
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<RecompressionSummary>`: Chunks rewritten and their size before and after
+    ///
+    pub fn recompress_aged(&mut self, config: &RecompressionConfig, now: u64) -> Result<RecompressionSummary, Error> {
+        let recompressed: Vec<RecompressedChunk> = self.plan_recompression(config, now)?;
+        return self.apply_recompression(recompressed, config);
+    }
+    ///
+    /// Read and re-encode the chunks due for recompression without changing the
+    /// store. Chunks are picked from the bounds and codec in their fixed size
+    /// prefix, so only the chunks re-encoded are read whole. This takes no
+    /// lock on the store, so a writer can keep appending while it runs against
+    /// another instance of the store, whose lease keeps the originals in place
+    /// until they are applied.
+    ///
+    /// # Arguments
+    /// * config: Age threshold, target codec and per-run limit
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<Vec<RecompressedChunk>>`: Replacements for `apply_recompression`, oldest first
+    ///
+    pub fn plan_recompression(&self, config: &RecompressionConfig, now: u64) -> Result<Vec<RecompressedChunk>, Error> {
+        let mut recompressed: Vec<RecompressedChunk> = Vec::new();
+        let chunk_count: usize = self.header.chunk_offsets.len();
+        if chunk_count < 2 {
+            return Ok(recompressed);
+        }
+        let mut file: File = File::open(self.path.as_str())?;
+        let cutoff: u64 = now.saturating_sub(config.min_age.as_millis() as u64);
+        for index in 0..(chunk_count - 1) {
+            if recompressed.len() >= config.max_chunks_per_run {
+                break;
+            }
+            let (_, timestamp_to): (u64, u64) = self.read_chunk_bounds(&mut file, index)?;
+            if timestamp_to >= cutoff || self.read_chunk_codec(&mut file, index)? == config.codec.id() {
+                continue;
+            }
+            let chunk: Chunk = self.read_chunk_from(&mut file, index)?;
+            recompressed.push(RecompressedChunk {
+                original: self.header.chunk_offsets[index].clone(),
+                original_length: chunk.length as u64,
+                chunk: match chunk.recompress(config.codec) {
+                    Ok(c) => c,
+                    Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+                },
+            });
+        }
+        return Ok(recompressed);
+    }
+    ///
+    /// Swap re-encoded chunks in for their originals in a single header commit.
+    /// Originals no longer in the store, or now its latest chunk, are skipped.
+    ///
+    /// # Arguments
+    /// * recompressed: Replacements planned by `plan_recompression`
+    /// * config: Recompression the replacements were planned with
+    ///
+    /// # Returns
+    /// `Result<RecompressionSummary>`: Chunks rewritten and their size before and after
+    ///
+    pub fn apply_recompression(&mut self, recompressed: Vec<RecompressedChunk>, config: &RecompressionConfig) -> Result<RecompressionSummary, Error> {
+        let mut summary: RecompressionSummary = RecompressionSummary::default();
+        if recompressed.is_empty() {
+            return Ok(summary);
+        }
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        for replacement in recompressed.iter() {
+            let latest: usize = self.header.chunk_offsets.len().saturating_sub(1);
+            let index: usize = match self.header.chunk_offsets.iter().position(|o: &ChunkOffsets| *o == replacement.original) {
+                Some(i) if i < latest => i,
+                _ => continue,
+            };
+            let chunk_offset: ChunkOffsets = self.write_chunk(&mut file, &replacement.chunk)?;
+            self.retire_chunk(&mut file, index)?;
+            self.header.chunk_offsets[index] = chunk_offset;
+            summary.chunks_recompressed += 1;
+            summary.bytes_before += replacement.original_length;
+            summary.bytes_after += replacement.chunk.length as u64;
+        }
+        if summary.chunks_recompressed == 0 {
+            return Ok(summary);
+        }
+        self.commit_header(&mut file)?;
+        info!(
+            crate::LOGGER,
+            "Recompressed {} aged chunks of {} with {:?} ({}B -> {}B)",
+            summary.chunks_recompressed,
+            self.path,
+            config.codec,
+            summary.bytes_before,
+            summary.bytes_after
+        );
+        return Ok(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk_entry::ChunkEntry;
+    use crate::utils::test_utils::{chunk_of, temp_store_path};
+
+    #[test]
+    fn applies_recompression_planned_by_another_instance() {
+        let path: String = temp_store_path("applies_recompression_planned_by_another_instance");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        for timestamp in 1..=4 {
+            store.append_chunk(chunk_of(&[timestamp])).unwrap();
+        }
+        let config: RecompressionConfig = RecompressionConfig {
+            min_age: Duration::from_millis(100),
+            ..RecompressionConfig::default()
+        };
+        let planner: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let recompressed: Vec<RecompressedChunk> = planner.plan_recompression(&config, 1000).unwrap();
+        assert_eq!(recompressed.len(), 3);
+
+        store.append_chunk(chunk_of(&[5])).unwrap();
+        let summary: RecompressionSummary = store.apply_recompression(recompressed, &config).unwrap();
+        assert_eq!(summary.chunks_recompressed, 3);
+        let mut file: File = File::open(path.as_str()).unwrap();
+        for index in 0..5 {
+            let expected_codec: CompressionCodec = if index < 3 { config.codec } else { CompressionCodec::default() };
+            assert_eq!(store.read_chunk_codec(&mut file, index).unwrap(), expected_codec.id());
+            let entries: Vec<ChunkEntry> = store.read_chunk(index).unwrap().decode_entries().ok().expect("Could not decode entries");
+            assert_eq!(entries[0].timestamp, index as u64 + 1);
+        }
+        // Only the chunk that stopped being the latest is left to recompress
+        let reopened: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        assert_eq!(reopened.plan_recompression(&config, 1000).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,330 @@
+use std::fs;
+use std::fs::File;
+use std::io::Error;
+use std::time::Duration;
+use crate::configuration::config::Config;
+use super::archive::archive_reference::ArchiveReference;
+use super::chunk_offsets::ChunkOffsets;
+use super::chunk_store::ChunkStore;
+use super::store_write_lock::StoreWriteLock;
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// Drop chunks whose newest entry is older than this
+    pub max_age: Option<Duration>,
+    /// Drop the oldest chunks while the chunks in the store total more than this many bytes
+    pub max_bytes: Option<u64>,
+    /// Drop the oldest chunks while the store holds more than this many chunks
+    pub max_chunks: Option<u64>,
+    /// How often the writer's `tick` enforces the limits
+    pub interval: Duration,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_age: None,
+            max_bytes: None,
+            max_chunks: None,
+            interval: Duration::from_millis(60 * 1000),
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn from_config(config: &mut Config) -> RetentionConfig {
+        let defaults: RetentionConfig = RetentionConfig::default();
+        // A limit of 0 disables that policy
+        let non_zero = |value: u64| if value == 0 { None } else { Some(value) };
+        RetentionConfig {
+            max_age: non_zero(config.get_or_default("store.retention.max_age_ms", 0u64))
+                .map(Duration::from_millis),
+            max_bytes: non_zero(config.get_or_default("store.retention.max_bytes", 0u64)),
+            max_chunks: non_zero(config.get_or_default("store.retention.max_chunks", 0u64)),
+            interval: Duration::from_millis(config.get_or_default(
+                "store.retention.interval_ms",
+                defaults.interval.as_millis() as u64,
+            )),
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_bytes.is_some() || self.max_chunks.is_some()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RetentionSummary {
+    pub chunks_expired: usize,
+    pub chunks_over_size: usize,
+    pub chunks_over_count: usize,
+    pub bytes_reclaimed: u64,
+    pub chunks_remaining: usize,
+}
+
+impl ChunkStore {
+    ///
+    /// Remove chunks that fall outside the retention policy. Archives and chunks
+    /// past the maximum age are dropped wherever they sit, then the remaining
+    /// archives and chunks with the oldest newest entry are dropped until the
+    /// store is within its size and count limits. The latest chunk is always kept as a writer may be
+    /// extending it. Dropped chunks are retired, so their space is reclaimed once
+    /// no reader still holds a view of them; dropped archives are deleted once
+    /// the header no longer references them.
+    ///
+    /// # Arguments
+    /// * config: Limits to enforce
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<RetentionSummary>`: Chunks dropped by each policy and bytes reclaimed
+    ///
+    pub fn enforce_retention(&mut self, config: &RetentionConfig, now: u64) -> Result<RetentionSummary, Error> {
+        let summary: RetentionSummary = self.drop_outside_retention(config, now)?;
+        info!(
+            crate::LOGGER,
+            "Retention on {}: dropped {} expired, {} over size and {} over count chunks, reclaiming {}B with {} chunks remaining",
+            self.path,
+            summary.chunks_expired,
+            summary.chunks_over_size,
+            summary.chunks_over_count,
+            summary.bytes_reclaimed,
+            summary.chunks_remaining
+        );
+        return Ok(summary);
+    }
+    fn drop_outside_retention(&mut self, config: &RetentionConfig, now: u64) -> Result<RetentionSummary, Error> {
+        let mut summary: RetentionSummary = RetentionSummary::default();
+        if !config.is_enabled() {
+            summary.chunks_remaining = self.header.chunk_offsets.len();
+            return Ok(summary);
+        }
+        let (mut file, _lock): (File, StoreWriteLock) = self.open_for_write()?;
+        let chunk_count: usize = self.header.chunk_offsets.len();
+        let archive_count: usize = self.header.archives.len();
+        summary.chunks_remaining = chunk_count;
+        if chunk_count + archive_count < 2 {
+            return Ok(summary);
+        }
+        let mut lengths: Vec<u64> = Vec::with_capacity(chunk_count);
+        for index in 0..chunk_count {
+            lengths.push(self.read_chunk_length(&mut file, index)? as u64);
+        }
+        let mut archive_lengths: Vec<u64> = Vec::with_capacity(archive_count);
+        for reference in self.header.archives.iter() {
+            archive_lengths.push(match fs::metadata(self.archive_path(reference)) {
+                Ok(m) => m.len(),
+                Err(_) => 0,
+            });
+        }
+        // Candidates are archives, then chunks skipping the latest. Late entries
+        // can leave a chunk in the store older than an archive, so the oldest
+        // data is found by each candidate's newest entry rather than its tier.
+        let candidate_count: usize = archive_count + chunk_count.saturating_sub(1);
+        let candidate_bytes = |i: usize| if i < archive_count { archive_lengths[i] } else { lengths[i - archive_count] };
+        let candidate_chunks = |i: usize| if i < archive_count { self.header.archives[i].chunk_count } else { 1 };
+        let mut newest: Vec<u64> = Vec::with_capacity(candidate_count);
+        for i in 0..candidate_count {
+            newest.push(if i < archive_count {
+                self.header.archives[i].timestamp_to
+            } else {
+                self.read_chunk_bounds(&mut file, i - archive_count)?.1
+            });
+        }
+        let mut oldest_first: Vec<usize> = (0..candidate_count).collect();
+        oldest_first.sort_by_key(|i: &usize| newest[*i]);
+        let mut dropped: Vec<bool> = vec![false; candidate_count];
+        if let Some(max_age) = config.max_age {
+            let cutoff: u64 = now.saturating_sub(max_age.as_millis() as u64);
+            for i in 0..candidate_count {
+                if newest[i] < cutoff {
+                    dropped[i] = true;
+                    summary.chunks_expired += candidate_chunks(i) as usize;
+                }
+            }
+        }
+        let mut remaining_bytes: u64 = lengths.iter().sum::<u64>() + archive_lengths.iter().sum::<u64>();
+        let mut remaining_chunks: u64 = chunk_count as u64
+            + self.header.archives.iter().map(|a: &ArchiveReference| a.chunk_count).sum::<u64>();
+        for (i, _) in dropped.iter().enumerate().filter(|(_, d): &(usize, &bool)| **d) {
+            remaining_bytes -= candidate_bytes(i);
+            remaining_chunks -= candidate_chunks(i);
+        }
+        for i in oldest_first.into_iter() {
+            if dropped[i] {
+                continue;
+            }
+            let over_size: bool = config.max_bytes.is_some_and(|max: u64| remaining_bytes > max);
+            let over_count: bool = config.max_chunks.is_some_and(|max: u64| remaining_chunks > max);
+            if !over_size && !over_count {
+                break;
+            }
+            if over_size {
+                summary.chunks_over_size += candidate_chunks(i) as usize;
+            } else {
+                summary.chunks_over_count += candidate_chunks(i) as usize;
+            }
+            dropped[i] = true;
+            remaining_bytes -= candidate_bytes(i);
+            remaining_chunks -= candidate_chunks(i);
+        }
+        if !dropped.contains(&true) {
+            return Ok(summary);
+        }
+        let mut archives: Vec<ArchiveReference> = Vec::with_capacity(archive_count);
+        let mut dropped_archives: Vec<String> = Vec::new();
+        for (index, archive) in self.header.archives.iter().enumerate() {
+            if dropped[index] {
+                dropped_archives.push(self.archive_path(archive));
+                summary.bytes_reclaimed += archive_lengths[index];
+            } else {
+                archives.push(archive.clone());
+            }
+        }
+        let mut chunk_offsets: Vec<ChunkOffsets> = Vec::with_capacity(chunk_count);
+        for (index, length) in lengths.iter().enumerate() {
+            if dropped.get(archive_count + index).copied().unwrap_or(false) {
+                self.retire_chunk(&mut file, index)?;
+                summary.bytes_reclaimed += length;
+            } else {
+                chunk_offsets.push(self.header.chunk_offsets[index].clone());
+            }
+        }
+        summary.chunks_remaining = chunk_offsets.len();
+        self.header.chunk_offsets = chunk_offsets;
+        self.header.chunk_offsets_length = self.header.chunk_offsets.len() as u64;
+        self.header.chunk_count = self.header.chunk_offsets_length;
+        self.header.archives = archives;
+        self.header.archives_length = self.header.archives.len() as u64;
+        self.header.renumber_chunks();
+        self.commit_header(&mut file)?;
+        for path in dropped_archives.iter() {
+            if let Err(e) = fs::remove_file(path.as_str()) {
+                warn!(crate::LOGGER, "Unable to delete expired archive {}: {}", path, e);
+            }
+        }
+        return Ok(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk::Chunk;
+    use crate::data::representational::chunk_entry::ChunkEntry;
+    use crate::utils::test_utils::{entry_at, temp_store_path};
+    use super::super::archive::archival::ArchiveConfig;
+
+    fn incompressible_chunk_at(timestamp: u64) -> Chunk {
+        // Incompressible, so each chunk spans more sectors than a header
+        let mut state: u64 = timestamp.wrapping_add(1);
+        let entry: ChunkEntry = ChunkEntry {
+            message: (0..1024)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    (state >> 56) as u8
+                })
+                .collect(),
+            ..entry_at(timestamp)
+        };
+        return Chunk::seal(&[entry]).ok().expect("Could not seal chunk");
+    }
+
+    fn store_with(name: &str, timestamps: &[u64]) -> ChunkStore {
+        let mut store: ChunkStore = ChunkStore::create(temp_store_path(name).as_str(), 64, true).unwrap();
+        for timestamp in timestamps.iter() {
+            store.append_chunk(incompressible_chunk_at(*timestamp)).unwrap();
+        }
+        return store;
+    }
+
+    fn timestamps(store: &ChunkStore) -> Vec<u64> {
+        return (0..store.header.chunk_offsets.len())
+            .map(|i: usize| store.read_chunk(i).unwrap().timestamp_to)
+            .collect();
+    }
+
+    ///
+    /// Check that once the next commit frees the dropped chunks, as no other
+    /// reader holds them, chunks appended after it are written into their space
+    /// rather than at the end of the file.
+    ///
+    fn assert_reuses_space(store: &mut ChunkStore, dropped: &[ChunkOffsets]) {
+        assert!(!store.header.retired_regions.is_empty());
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        for timestamp in 0..=dropped.len() as u64 {
+            store.append_chunk(incompressible_chunk_at(2000 + timestamp)).unwrap();
+        }
+        assert!(store.header.retired_regions.is_empty());
+        let sectors: u64 = (incompressible_chunk_at(0).into_bytes().len() as u64).div_ceil(store.header.sector_size as u64);
+        let overlaps_dropped = |a: &ChunkOffsets| dropped.iter()
+            .any(|d: &ChunkOffsets| a.sector_index < d.sector_index + sectors && d.sector_index < a.sector_index + sectors);
+        assert!(store.header.chunk_offsets[chunk_count..].iter().any(overlaps_dropped));
+    }
+
+    #[test]
+    fn drops_chunks_past_max_age() {
+        let mut store: ChunkStore = store_with("drops_chunks_past_max_age", &[1, 2, 3, 4, 950, 10]);
+        let dropped: Vec<ChunkOffsets> = store.header.chunk_offsets[..4].to_vec();
+        let config: RetentionConfig = RetentionConfig {
+            max_age: Some(Duration::from_millis(100)),
+            ..RetentionConfig::default()
+        };
+        let summary: RetentionSummary = store.enforce_retention(&config, 1000).unwrap();
+        assert_eq!((summary.chunks_expired, summary.chunks_remaining), (4, 2));
+        // The latest chunk is kept however old it is
+        assert_eq!(timestamps(&store), vec![950, 10]);
+        assert_eq!(store.header.chunk_count, 2);
+        assert_reuses_space(&mut store, dropped.as_slice());
+    }
+
+    #[test]
+    fn drops_oldest_chunks_over_max_bytes() {
+        let mut store: ChunkStore = store_with("drops_oldest_chunks_over_max_bytes", &[1, 2, 3, 4, 5, 6]);
+        let dropped: Vec<ChunkOffsets> = store.header.chunk_offsets[..3].to_vec();
+        let chunk_length: u64 = incompressible_chunk_at(1).into_bytes().len() as u64;
+        let config: RetentionConfig = RetentionConfig {
+            max_bytes: Some(3 * chunk_length),
+            ..RetentionConfig::default()
+        };
+        let summary: RetentionSummary = store.enforce_retention(&config, 1000).unwrap();
+        assert_eq!(summary.chunks_over_size, 3);
+        assert_eq!(summary.bytes_reclaimed, 3 * chunk_length);
+        assert_eq!(timestamps(&store), vec![4, 5, 6]);
+        assert_reuses_space(&mut store, dropped.as_slice());
+    }
+
+    #[test]
+    fn drops_oldest_chunks_over_max_chunks() {
+        let mut store: ChunkStore = store_with("drops_oldest_chunks_over_max_chunks", &[1, 2, 3, 4, 5, 6]);
+        let dropped: Vec<ChunkOffsets> = store.header.chunk_offsets[..4].to_vec();
+        let config: RetentionConfig = RetentionConfig {
+            max_chunks: Some(2),
+            ..RetentionConfig::default()
+        };
+        let summary: RetentionSummary = store.enforce_retention(&config, 1000).unwrap();
+        assert_eq!((summary.chunks_over_count, summary.chunks_remaining), (4, 2));
+        assert_eq!(timestamps(&store), vec![5, 6]);
+        assert_reuses_space(&mut store, dropped.as_slice());
+    }
+
+    #[test]
+    fn drops_late_chunk_before_newer_archive() {
+        let mut store: ChunkStore = store_with("drops_late_chunk_before_newer_archive", &[100, 200, 300]);
+        let archive: ArchiveConfig = ArchiveConfig {
+            min_age: Duration::from_millis(750),
+            ..ArchiveConfig::default()
+        };
+        assert_eq!(store.archive_aged(&archive, 1000).unwrap().chunks_archived, 2);
+        // A late chunk lands in the store older than everything archived
+        store.append_chunk(incompressible_chunk_at(5)).unwrap();
+        store.append_chunk(incompressible_chunk_at(400)).unwrap();
+        let config: RetentionConfig = RetentionConfig {
+            max_chunks: Some(4),
+            ..RetentionConfig::default()
+        };
+        let summary: RetentionSummary = store.enforce_retention(&config, 1000).unwrap();
+        assert_eq!(summary.chunks_over_count, 1);
+        assert_eq!(timestamps(&store), vec![300, 400]);
+        assert_eq!(store.header.archives.len(), 1);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{byte_layout, reify};
+
+reify!{
+    #[derive(Debug,Default,Clone,PartialEq)]
+    pub struct RetiredRegion {
+        #[byte_size=8]
+        pub generation: u64,
+        #[byte_size=8]
+        pub offset: u64,
+        #[byte_size=8]
+        pub length: u64,
+    }
+}
+
+byte_layout!{
+    RetiredRegion
+    value [generation, u64, Big]
+    value [offset, u64, Big]
+    value [length, u64, Big]
+}
+
+impl RetiredRegion {
+    /// A region retired in generation `g` is still referenced by the headers and
+    /// views of earlier generations, so it may only be freed once generation `g`
+    /// is committed and no view of an earlier generation remains.
+    #[inline]
+    pub fn is_releasable(&self, committed_generation: u64, oldest_reader_generation: Option<u64>) -> bool {
+        self.generation <= committed_generation && match oldest_reader_generation {
+            Some(generation) => generation >= self.generation,
+            None => true,
+        }
+    }
+}
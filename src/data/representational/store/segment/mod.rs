@@ -0,0 +1,2 @@
+pub mod segment_manifest;
+pub mod segmented_store;
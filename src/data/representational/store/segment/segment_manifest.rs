@@ -0,0 +1,118 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    /// File name of the segment's store, relative to the store directory
+    pub file_name: String,
+    /// Start of the time window this segment was opened for, if rotating by time
+    pub window_start: Option<u64>,
+    /// Earliest `timestamp_from` of any sealed chunk, `u64::MAX` while empty
+    pub timestamp_from: u64,
+    /// Latest `timestamp_to` of any sealed chunk
+    pub timestamp_to: u64,
+    pub chunk_count: u64,
+    pub bytes: u64,
+}
+
+impl SegmentInfo {
+    pub fn new(file_name: String, window_start: Option<u64>) -> SegmentInfo {
+        SegmentInfo {
+            file_name,
+            window_start,
+            timestamp_from: u64::MAX,
+            timestamp_to: u64::MIN,
+            chunk_count: 0,
+            bytes: 0,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.chunk_count == 0
+    }
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        !self.is_empty() && self.timestamp_from <= to && self.timestamp_to >= from
+    }
+    pub fn extend_range(&mut self, timestamp_from: u64, timestamp_to: u64) {
+        self.timestamp_from = self.timestamp_from.min(timestamp_from);
+        self.timestamp_to = self.timestamp_to.max(timestamp_to);
+    }
+}
+
+///
+/// Index of the segment files making up a store directory, ordered from oldest
+/// to newest. The last segment is the one being written to. Saved as JSON next
+/// to the segments and replaced atomically, so a crash leaves either the old or
+/// the new manifest in place.
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SegmentManifest {
+    pub next_segment_id: u64,
+    pub segments: Vec<SegmentInfo>,
+}
+
+impl SegmentManifest {
+    pub const FILE_NAME: &'static str = "manifest.json";
+
+    pub fn path_in(directory: &str) -> PathBuf {
+        Path::new(directory).join(Self::FILE_NAME)
+    }
+    ///
+    /// Read the manifest from a store directory, or start an empty one if the
+    /// directory has none yet.
+    ///
+    /// # Arguments
+    /// * directory: Store directory containing the manifest
+    ///
+    /// # Returns
+    /// `Result<SegmentManifest>`: Parsed manifest
+    ///
+    pub fn load(directory: &str) -> Result<SegmentManifest, Error> {
+        let path: PathBuf = Self::path_in(directory);
+        if !path.exists() {
+            return Ok(SegmentManifest::default());
+        }
+        let file: File = File::open(&path)?;
+        return match serde_json::from_reader(file) {
+            Ok(manifest) => Ok(manifest),
+            Err(e) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid segment manifest {}: {}", path.display(), e),
+            )),
+        };
+    }
+    pub fn save(&self, directory: &str) -> Result<(), Error> {
+        let path: PathBuf = Self::path_in(directory);
+        let temp_path: PathBuf = path.with_extension("json.tmp");
+        let bytes: Vec<u8> = match serde_json::to_vec_pretty(self) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+        };
+        let mut file: File = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_path)?;
+        file.write_all(bytes.as_slice())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &path)?;
+        return Ok(());
+    }
+    pub fn allocate_file_name(&mut self) -> String {
+        let file_name: String = format!("segment-{:08}.bin", self.next_segment_id);
+        self.next_segment_id += 1;
+        return file_name;
+    }
+    pub fn active_segment(&self) -> Option<&SegmentInfo> {
+        self.segments.last()
+    }
+    pub fn active_segment_mut(&mut self) -> Option<&mut SegmentInfo> {
+        self.segments.last_mut()
+    }
+    pub fn overlapping(&self, from: u64, to: u64) -> Vec<&SegmentInfo> {
+        self.segments.iter()
+            .filter(|s: &&SegmentInfo| s.overlaps(from, to))
+            .collect()
+    }
+}
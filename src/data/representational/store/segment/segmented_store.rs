@@ -0,0 +1,569 @@
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::configuration::config::Config;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::data::representational::store::chunk_store_writer::{ChunkStoreWriter, ChunkStoreWriterConfig};
+use crate::data::representational::store::index::inverted_index::InvertedIndex;
+use crate::data::representational::store::reader_registry::ReaderRegistry;
+use crate::data::representational::store::store_write_lock::StoreWriteLock;
+use crate::data::representational::store::retention::RetentionConfig;
+use crate::data::representational::store::writer_maintenance::WriterMaintenance;
+use crate::cache::write_ahead_log::WriteAheadLog;
+use super::segment_manifest::{SegmentInfo, SegmentManifest};
+
+#[derive(Debug, Clone)]
+pub struct SegmentedStoreConfig {
+    pub writer: ChunkStoreWriterConfig,
+    /// Start a new segment once the active one reaches this many bytes
+    pub max_segment_bytes: Option<u64>,
+    /// Start a new segment for each window of this length, e.g. hourly or daily
+    pub segment_window: Option<Duration>,
+    /// How often the active segment's writer maintenance runs
+    pub maintenance_interval: Duration,
+}
+
+impl Default for SegmentedStoreConfig {
+    fn default() -> Self {
+        SegmentedStoreConfig {
+            writer: ChunkStoreWriterConfig::default(),
+            max_segment_bytes: None,
+            segment_window: None,
+            maintenance_interval: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl SegmentedStoreConfig {
+    pub fn from_config(config: &mut Config) -> SegmentedStoreConfig {
+        let defaults: SegmentedStoreConfig = SegmentedStoreConfig::default();
+        // A limit of 0 disables rotation on that condition
+        let max_segment_bytes: u64 = config.get_or_default("store.segment.max_bytes", 0u64);
+        let segment_window_ms: u64 = config.get_or_default("store.segment.window_ms", 0u64);
+        SegmentedStoreConfig {
+            writer: ChunkStoreWriterConfig::from_config(config),
+            max_segment_bytes: if max_segment_bytes == 0 { None } else { Some(max_segment_bytes) },
+            segment_window: if segment_window_ms == 0 { None } else { Some(Duration::from_millis(segment_window_ms)) },
+            maintenance_interval: Duration::from_millis(config.get_or_default(
+                "store.segment.maintenance_interval_ms",
+                defaults.maintenance_interval.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SegmentRetentionSummary {
+    pub segments_dropped: usize,
+    pub bytes_reclaimed: u64,
+    pub segments_remaining: usize,
+}
+
+///
+/// A store directory made up of a sequence of `ChunkStore` segment files and a
+/// manifest recording each segment's time range. Writes go to the newest
+/// segment, which is rotated out once it exceeds its size cap or an entry
+/// arrives for a later time window. Readers use the manifest to open only the
+/// segments overlapping the range they are interested in, and retention drops
+/// whole segment files. The active segment's writer is maintained on its own
+/// thread, so recompression, compaction and archival run off the appending
+/// thread.
+///
+pub struct SegmentedStore {
+    pub directory: String,
+    pub config: SegmentedStoreConfig,
+    pub manifest: SegmentManifest,
+    writer: Arc<Mutex<ChunkStoreWriter>>,
+    maintenance: Option<WriterMaintenance>,
+    /// Store generation last recorded in the manifest for the active segment
+    observed_generation: u64,
+    /// Chunks the active segment held when the manifest was last recorded
+    observed_chunk_count: usize,
+    last_retention: Instant,
+}
+
+impl SegmentedStore {
+    ///
+    /// Open the store directory, creating it and its first segment if needed, and
+    /// resume writing to the newest segment.
+    ///
+    /// # Arguments
+    /// * directory: Directory holding the manifest and segment files
+    /// * config: Segment rotation and writer settings
+    ///
+    /// # Returns
+    /// `Result<SegmentedStore>`: Store writing to its newest segment
+    ///
+    pub fn open(directory: &str, config: SegmentedStoreConfig) -> Result<SegmentedStore, Error> {
+        fs::create_dir_all(directory)?;
+        let mut manifest: SegmentManifest = SegmentManifest::load(directory)?;
+        if manifest.segments.is_empty() {
+            let file_name: String = manifest.allocate_file_name();
+            manifest.segments.push(SegmentInfo::new(file_name, None));
+            manifest.save(directory)?;
+        }
+        let active_path: String = Self::path_of(directory, manifest.active_segment().unwrap());
+        let writer: Arc<Mutex<ChunkStoreWriter>> = Arc::new(Mutex::new(
+            ChunkStoreWriter::open(active_path.as_str(), Self::segment_writer_config(&config))?,
+        ));
+        let mut store: SegmentedStore = SegmentedStore {
+            directory: String::from(directory),
+            maintenance: Some(WriterMaintenance::start(writer.clone(), config.maintenance_interval)),
+            config,
+            manifest,
+            writer,
+            observed_generation: 0,
+            observed_chunk_count: 0,
+            last_retention: Instant::now(),
+        };
+        store.rescan_active_segment()?;
+        return Ok(store);
+    }
+    ///
+    /// Writer settings for a segment. Retention drops whole segments rather than
+    /// chunks within the active one, so it is left to the segmented store.
+    ///
+    fn segment_writer_config(config: &SegmentedStoreConfig) -> ChunkStoreWriterConfig {
+        let mut writer: ChunkStoreWriterConfig = config.writer.clone();
+        writer.retention = RetentionConfig::default();
+        return writer;
+    }
+    fn lock(&self) -> Result<MutexGuard<'_, ChunkStoreWriter>, Error> {
+        return self.writer.lock()
+            .map_err(|_| Error::other("Chunk store writer lock poisoned"));
+    }
+    fn path_of(directory: &str, segment: &SegmentInfo) -> String {
+        Path::new(directory).join(segment.file_name.as_str()).to_string_lossy().into_owned()
+    }
+    pub fn segment_path(&self, segment: &SegmentInfo) -> String {
+        Self::path_of(self.directory.as_str(), segment)
+    }
+    ///
+    /// Rebuild the active segment's manifest entry from its store, in case the
+    /// process stopped between sealing a chunk and saving the manifest.
+    ///
+    fn rescan_active_segment(&mut self) -> Result<(), Error> {
+        let writer: MutexGuard<ChunkStoreWriter> = self.lock()?;
+        let store: &ChunkStore = &writer.store;
+        let mut info: SegmentInfo = SegmentInfo::new(
+            self.manifest.active_segment().unwrap().file_name.clone(),
+            self.manifest.active_segment().unwrap().window_start,
+        );
+        for index in 0..store.header.chunk_offsets.len() {
+            let chunk: Chunk = store.read_chunk(index)?;
+            info.extend_range(chunk.timestamp_from, chunk.timestamp_to);
+        }
+        info.chunk_count = store.header.chunk_count;
+        info.bytes = fs::metadata(store.path.as_str())?.len();
+        let generation: u64 = store.generation();
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        drop(writer);
+        *self.manifest.active_segment_mut().unwrap() = info;
+        self.observed_generation = generation;
+        self.observed_chunk_count = chunk_count;
+        return self.manifest.save(self.directory.as_str());
+    }
+    ///
+    /// Record newly sealed chunks of the active segment in the manifest. The
+    /// maintenance thread can seal chunks between syncs, so the ranges of every
+    /// chunk added since the last sync are merged into the segment's range,
+    /// along with the chunk that was latest then, which a resumed chunk replaces.
+    ///
+    fn sync_manifest(&mut self) -> Result<(), Error> {
+        let writer: MutexGuard<ChunkStoreWriter> = self.lock()?;
+        let store: &ChunkStore = &writer.store;
+        let generation: u64 = store.generation();
+        if generation == self.observed_generation {
+            return Ok(());
+        }
+        let bytes: u64 = fs::metadata(store.path.as_str())?.len();
+        let chunk_count: usize = store.header.chunk_offsets.len();
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        if chunk_count > 0 {
+            let mut file: File = File::open(store.path.as_str())?;
+            for index in self.observed_chunk_count.min(chunk_count).saturating_sub(1)..chunk_count {
+                ranges.push(store.read_chunk_bounds(&mut file, index)?);
+            }
+        }
+        let header_chunk_count: u64 = store.header.chunk_count;
+        drop(writer);
+        let active: &mut SegmentInfo = self.manifest.active_segment_mut().unwrap();
+        for (timestamp_from, timestamp_to) in ranges.into_iter() {
+            active.extend_range(timestamp_from, timestamp_to);
+        }
+        active.chunk_count = header_chunk_count;
+        active.bytes = bytes;
+        self.observed_generation = generation;
+        self.observed_chunk_count = chunk_count;
+        return self.manifest.save(self.directory.as_str());
+    }
+    fn window_start_of(&self, timestamp: u64) -> Option<u64> {
+        let window_ms: u64 = self.config.segment_window?.as_millis() as u64;
+        if window_ms == 0 {
+            return None;
+        }
+        return Some(timestamp - timestamp % window_ms);
+    }
+    ///
+    /// Whether the entry should start a new segment. Entries arriving late for an
+    /// earlier window stay in the active segment, whose range widens to cover
+    /// them, rather than reopening an old segment.
+    ///
+    fn is_rotation_due(&self, entry: &ChunkEntry) -> Result<bool, Error> {
+        let active: &SegmentInfo = self.manifest.active_segment().unwrap();
+        if active.is_empty() && self.lock()?.cache.is_empty() {
+            return Ok(false);
+        }
+        let window_due: bool = match (self.window_start_of(entry.timestamp), active.window_start) {
+            (Some(entry_window), Some(active_window)) => entry_window > active_window,
+            _ => false,
+        };
+        let size_due: bool = self.config.max_segment_bytes
+            .is_some_and(|max: u64| active.bytes >= max);
+        return Ok(window_due || size_due);
+    }
+    ///
+    /// Seal the active segment and start writing to a new one.
+    ///
+    pub fn rotate(&mut self) -> Result<(), Error> {
+        if let Some(maintenance) = self.maintenance.take() {
+            maintenance.stop();
+        }
+        self.lock()?.flush()?;
+        self.sync_manifest()?;
+        let file_name: String = self.manifest.allocate_file_name();
+        let info: SegmentInfo = SegmentInfo::new(file_name, None);
+        let path: String = self.segment_path(&info);
+        self.manifest.segments.push(info);
+        self.manifest.save(self.directory.as_str())?;
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), Self::segment_writer_config(&self.config))?;
+        self.observed_generation = writer.store.generation();
+        self.observed_chunk_count = writer.store.header.chunk_offsets.len();
+        self.writer = Arc::new(Mutex::new(writer));
+        self.maintenance = Some(WriterMaintenance::start(self.writer.clone(), self.config.maintenance_interval));
+        info!(
+            crate::LOGGER,
+            "Rotated {} to new segment {}",
+            self.directory,
+            path
+        );
+        return Ok(());
+    }
+    pub fn append(&mut self, entry: ChunkEntry) -> Result<(), Error> {
+        if self.is_rotation_due(&entry)? {
+            self.rotate()?;
+        }
+        let window_start: Option<u64> = self.window_start_of(entry.timestamp);
+        let active: &mut SegmentInfo = self.manifest.active_segment_mut().unwrap();
+        if active.window_start.is_none() {
+            active.window_start = window_start;
+        }
+        self.lock()?.append(entry)?;
+        return self.sync_manifest();
+    }
+    ///
+    /// Periodic housekeeping: record chunks the active segment's maintenance
+    /// sealed in the manifest, and enforce segment retention once the writer's
+    /// retention interval has elapsed.
+    ///
+    pub fn tick(&mut self) -> Result<(), Error> {
+        self.sync_manifest()?;
+        let retention: RetentionConfig = self.config.writer.retention.clone();
+        if retention.is_enabled() && self.last_retention.elapsed() >= retention.interval {
+            let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            self.enforce_retention(&retention, now)?;
+            self.last_retention = Instant::now();
+        }
+        return Ok(());
+    }
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.lock()?.flush()?;
+        return self.sync_manifest();
+    }
+    ///
+    /// Paths of the segments holding chunks that overlap the given time range,
+    /// oldest first.
+    ///
+    /// # Arguments
+    /// * from: Start of the range in milliseconds since the epoch, inclusive
+    /// * to: End of the range in milliseconds since the epoch, inclusive
+    ///
+    /// # Returns
+    /// `Vec<String>`: Segment file paths
+    ///
+    pub fn segments_overlapping(&self, from: u64, to: u64) -> Vec<String> {
+        self.manifest.overlapping(from, to)
+            .into_iter()
+            .map(|s: &SegmentInfo| self.segment_path(s))
+            .collect()
+    }
+    pub fn open_segments(&self, from: u64, to: u64) -> Result<Vec<ChunkStore>, Error> {
+        let mut stores: Vec<ChunkStore> = Vec::new();
+        for path in self.segments_overlapping(from, to).iter() {
+            stores.push(ChunkStore::read_from_file(path.as_str())?);
+        }
+        return Ok(stores);
+    }
+    ///
+    /// Delete whole segments that fall outside the retention policy. Segments
+    /// whose newest chunk is past the maximum age are dropped first, then the
+    /// oldest segments until the directory is within its size and chunk count
+    /// limits. The active segment is never dropped.
+    ///
+    /// # Arguments
+    /// * config: Limits to enforce
+    /// * now: Current time in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<SegmentRetentionSummary>`: Segments dropped and bytes reclaimed
+    ///
+    pub fn enforce_retention(&mut self, config: &RetentionConfig, now: u64) -> Result<SegmentRetentionSummary, Error> {
+        let mut summary: SegmentRetentionSummary = SegmentRetentionSummary::default();
+        let segment_count: usize = self.manifest.segments.len();
+        let mut dropped: Vec<bool> = vec![false; segment_count];
+        if let Some(max_age) = config.max_age {
+            let cutoff: u64 = now.saturating_sub(max_age.as_millis() as u64);
+            let closed: &[SegmentInfo] = &self.manifest.segments[..segment_count - 1];
+            for (drop, segment) in dropped.iter_mut().zip(closed.iter()) {
+                *drop = segment.is_empty() || segment.timestamp_to < cutoff;
+            }
+        }
+        let mut remaining_bytes: u64 = 0;
+        let mut remaining_chunks: u64 = 0;
+        for (index, segment) in self.manifest.segments.iter().enumerate() {
+            if !dropped[index] {
+                remaining_bytes += segment.bytes;
+                remaining_chunks += segment.chunk_count;
+            }
+        }
+        let closed: &[SegmentInfo] = &self.manifest.segments[..segment_count - 1];
+        for (drop, segment) in dropped.iter_mut().zip(closed.iter()) {
+            if *drop {
+                continue;
+            }
+            let over_size: bool = config.max_bytes.is_some_and(|max: u64| remaining_bytes > max);
+            let over_count: bool = config.max_chunks.is_some_and(|max: u64| remaining_chunks > max);
+            if !over_size && !over_count {
+                break;
+            }
+            *drop = true;
+            remaining_bytes -= segment.bytes;
+            remaining_chunks -= segment.chunk_count;
+        }
+        if !dropped.contains(&true) {
+            summary.segments_remaining = segment_count;
+            return Ok(summary);
+        }
+        let mut removed: Vec<SegmentInfo> = Vec::new();
+        let mut kept: Vec<SegmentInfo> = Vec::new();
+        for (index, segment) in self.manifest.segments.drain(..).enumerate() {
+            if dropped[index] {
+                removed.push(segment);
+            } else {
+                kept.push(segment);
+            }
+        }
+        self.manifest.segments = kept;
+        // Forget the segments before deleting them so a crash never leaves the
+        // manifest pointing at missing files
+        self.manifest.save(self.directory.as_str())?;
+        for segment in removed.iter() {
+            let path: String = self.segment_path(segment);
+            let mut files: Vec<String> = vec![
+                path.clone(),
+                WriteAheadLog::path_for_store(path.as_str()),
+                InvertedIndex::path_for_store(path.as_str()),
+                StoreWriteLock::path_for_store(path.as_str()),
+            ];
+            match ChunkStore::read_from_file(path.as_str()) {
+                Ok(store) => files.extend(store.header.archives.iter().map(|a| store.archive_path(a))),
+                Err(e) => warn!(crate::LOGGER, "Unable to read archives of segment {}: {}", path, e),
+            };
+            for file in files {
+                match fs::remove_file(file.as_str()) {
+                    Ok(_) => {},
+                    Err(e) if e.kind() == ErrorKind::NotFound => {},
+                    Err(e) => return Err(e),
+                };
+            }
+            ReaderRegistry::for_store(path.as_str()).remove_directory();
+            summary.segments_dropped += 1;
+            summary.bytes_reclaimed += segment.bytes;
+        }
+        summary.segments_remaining = self.manifest.segments.len();
+        info!(
+            crate::LOGGER,
+            "Retention on {}: dropped {} segments, reclaiming {}B with {} segments remaining",
+            self.directory,
+            summary.segments_dropped,
+            summary.bytes_reclaimed,
+            summary.segments_remaining
+        );
+        return Ok(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::compressor::CompressionCodec;
+    use crate::data::representational::store::recompression::RecompressionConfig;
+    use crate::utils::test_utils::{entry_at, temp_store_path};
+
+    fn test_config() -> SegmentedStoreConfig {
+        let mut config: SegmentedStoreConfig = SegmentedStoreConfig::default();
+        config.writer.sector_size = 64;
+        config.writer.max_chunk_entries = 1;
+        config.writer.recompression = None;
+        config.writer.compaction = None;
+        config.maintenance_interval = Duration::from_millis(10);
+        return config;
+    }
+
+    fn open_store(name: &str, config: SegmentedStoreConfig) -> SegmentedStore {
+        return SegmentedStore::open(temp_store_path(name).as_str(), config).unwrap();
+    }
+
+    fn segment_ranges(store: &SegmentedStore) -> Vec<(u64, u64)> {
+        store.manifest.segments.iter()
+            .map(|s: &SegmentInfo| (s.timestamp_from, s.timestamp_to))
+            .collect()
+    }
+
+    #[test]
+    fn rotates_once_segment_reaches_max_bytes() {
+        let mut config: SegmentedStoreConfig = test_config();
+        config.max_segment_bytes = Some(1024);
+        let mut store: SegmentedStore = open_store("rotates_once_segment_reaches_max_bytes", config);
+        for timestamp in 0..40 {
+            store.append(entry_at(timestamp)).unwrap();
+        }
+        let segments: &Vec<SegmentInfo> = &store.manifest.segments;
+        assert!(segments.len() > 2);
+        // Each sealed segment was rotated out on the first append after it reached the cap
+        for segment in segments[..segments.len() - 1].iter() {
+            assert!(segment.bytes >= 1024);
+            assert_eq!(segment.bytes, fs::metadata(store.segment_path(segment)).unwrap().len());
+        }
+        let chunks: u64 = segments.iter().map(|s: &SegmentInfo| s.chunk_count).sum();
+        assert_eq!(chunks, 40);
+        let reopened: SegmentManifest = SegmentManifest::load(store.directory.as_str()).unwrap();
+        assert_eq!(reopened.segments.len(), segments.len());
+    }
+
+    #[test]
+    fn rotates_per_time_window_and_widens_for_late_entries() {
+        let mut config: SegmentedStoreConfig = test_config();
+        config.segment_window = Some(Duration::from_millis(1000));
+        let mut store: SegmentedStore = open_store("rotates_per_time_window_and_widens_for_late_entries", config);
+        for timestamp in [100, 900, 1100, 1500, 2500] {
+            store.append(entry_at(timestamp)).unwrap();
+        }
+        assert_eq!(segment_ranges(&store), vec![(100, 900), (1100, 1500), (2500, 2500)]);
+        let windows: Vec<Option<u64>> = store.manifest.segments.iter().map(|s: &SegmentInfo| s.window_start).collect();
+        assert_eq!(windows, vec![Some(0), Some(1000), Some(2000)]);
+        // A late entry stays in the active segment rather than reopening its window
+        store.append(entry_at(1200)).unwrap();
+        assert_eq!(store.manifest.segments.len(), 3);
+        assert_eq!(segment_ranges(&store)[2], (1200, 2500));
+    }
+
+    #[test]
+    fn records_every_chunk_sealed_between_syncs() {
+        let mut store: SegmentedStore = open_store("records_every_chunk_sealed_between_syncs", test_config());
+        // Seal two chunks without syncing, as the maintenance thread sealing an
+        // aged chunk before the next append seals another would
+        {
+            let mut writer: MutexGuard<ChunkStoreWriter> = store.lock().unwrap();
+            writer.append(entry_at(100)).unwrap();
+            writer.append(entry_at(200)).unwrap();
+        }
+        store.tick().unwrap();
+        assert_eq!(segment_ranges(&store), vec![(100, 200)]);
+        assert_eq!(store.manifest.active_segment().unwrap().chunk_count, 2);
+        assert_eq!(store.segments_overlapping(50, 150).len(), 1);
+    }
+
+    #[test]
+    fn opens_only_segments_overlapping_range() {
+        let mut config: SegmentedStoreConfig = test_config();
+        config.segment_window = Some(Duration::from_millis(1000));
+        let mut store: SegmentedStore = open_store("opens_only_segments_overlapping_range", config);
+        for timestamp in [100, 900, 1100, 1500, 2500] {
+            store.append(entry_at(timestamp)).unwrap();
+        }
+        let paths: Vec<String> = store.manifest.segments.iter().map(|s: &SegmentInfo| store.segment_path(s)).collect();
+        assert_eq!(store.segments_overlapping(1000, 1999), vec![paths[1].clone()]);
+        assert_eq!(store.segments_overlapping(950, 1050), Vec::<String>::new());
+        assert_eq!(store.segments_overlapping(800, 1200), vec![paths[0].clone(), paths[1].clone()]);
+        let segments: Vec<ChunkStore> = store.open_segments(1000, 1999).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].path, paths[1]);
+        assert_eq!(segments[0].header.chunk_count, 2);
+        assert!(store.open_segments(3000, 4000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn retention_deletes_whole_segments() {
+        let mut config: SegmentedStoreConfig = test_config();
+        config.segment_window = Some(Duration::from_millis(1000));
+        let mut store: SegmentedStore = open_store("retention_deletes_whole_segments", config);
+        for timestamp in [100, 900, 1100, 1500, 2500, 3500] {
+            store.append(entry_at(timestamp)).unwrap();
+        }
+        let paths: Vec<String> = store.manifest.segments.iter().map(|s: &SegmentInfo| store.segment_path(s)).collect();
+        let retention: RetentionConfig = RetentionConfig {
+            max_age: Some(Duration::from_millis(1000)),
+            ..RetentionConfig::default()
+        };
+        let summary: SegmentRetentionSummary = store.enforce_retention(&retention, 2600).unwrap();
+        assert_eq!((summary.segments_dropped, summary.segments_remaining), (2, 2));
+        for path in paths[..2].iter() {
+            assert!(!Path::new(path.as_str()).exists());
+            assert!(!Path::new(WriteAheadLog::path_for_store(path.as_str()).as_str()).exists());
+            assert!(!Path::new(StoreWriteLock::path_for_store(path.as_str()).as_str()).exists());
+            assert!(!Path::new(format!("{}.readers", path).as_str()).exists());
+        }
+        assert_eq!(segment_ranges(&store), vec![(2500, 2500), (3500, 3500)]);
+
+        // The active segment is kept whatever the limits
+        let retention: RetentionConfig = RetentionConfig {
+            max_chunks: Some(1),
+            ..RetentionConfig::default()
+        };
+        assert_eq!(store.enforce_retention(&retention, 2600).unwrap().segments_dropped, 1);
+        assert_eq!(store.enforce_retention(&retention, 1_000_000).unwrap().segments_remaining, 1);
+        assert!(Path::new(paths[3].as_str()).exists());
+        assert_eq!(SegmentManifest::load(store.directory.as_str()).unwrap().segments.len(), 1);
+    }
+
+    #[test]
+    fn recompresses_active_segment_on_maintenance_thread() {
+        let mut config: SegmentedStoreConfig = test_config();
+        let recompression: RecompressionConfig = RecompressionConfig {
+            min_age: Duration::from_millis(1000),
+            interval: Duration::ZERO,
+            ..RecompressionConfig::default()
+        };
+        config.writer.recompression = Some(recompression);
+        let mut store: SegmentedStore = open_store("recompresses_active_segment_on_maintenance_thread", config);
+        for timestamp in 0..3 {
+            store.append(entry_at(timestamp)).unwrap();
+        }
+        let recompressed = |store: &SegmentedStore| (0..2)
+            .all(|i: usize| store.lock().unwrap().store.read_chunk(i).unwrap().codec == CompressionCodec::ZLIB_BEST.id());
+        let started: Instant = Instant::now();
+        while !recompressed(&store) && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(recompressed(&store));
+        // The latest chunk is left for the writer to resume
+        assert_ne!(store.lock().unwrap().store.read_chunk(2).unwrap().codec, CompressionCodec::ZLIB_BEST.id());
+        store.tick().unwrap();
+        assert_eq!(store.manifest.active_segment().unwrap().chunk_count, 3);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::{File, OpenOptions};
+use std::io::Error;
+use fs2::FileExt;
+
+///
+/// Exclusive lock on changing a store, shared by every instance and process
+/// opening it. A change allocates space from the instance's in-memory header,
+/// so two instances changing the store from the same header would hand out the
+/// same space and each commit would drop the other's chunks. Holding this lock
+/// from reloading the newest header to committing the change prevents that.
+/// The lock is held on a file beside the store, and released when the guard is
+/// dropped or its process dies.
+///
+#[derive(Debug)]
+pub struct StoreWriteLock {
+    _file: File,
+}
+
+impl StoreWriteLock {
+    pub fn path_for_store(path: &str) -> String {
+        format!("{}.lock", path)
+    }
+    ///
+    /// Take the write lock on a store, waiting for any other holder to release it.
+    ///
+    /// # Arguments
+    /// * path: Location of the store file
+    ///
+    /// # Returns
+    /// `Result<StoreWriteLock>`: Lock held until the guard is dropped
+    ///
+    pub fn acquire(path: &str) -> Result<StoreWriteLock, Error> {
+        let file: File = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(Self::path_for_store(path))?;
+        file.lock_exclusive()?;
+        return Ok(StoreWriteLock {
+            _file: file,
+        });
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::query::query::{Query, QueryExpression, TimeBound};
+use crate::query::query_parser;
+use crate::query::query_planner::{EntryPredicate, QueryPlan, QueryPlanner};
+use super::chunk_store_writer::ChunkStoreWriter;
+use super::entry_iterator::EntryIterator;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscriptionStart {
+    /// Only entries appended after subscribing
+    NOW,
+    /// The newest n matching entries already in the store, oldest first, then new ones
+    LAST(usize),
+}
+
+///
+/// Receiving end of a subscription. Entries arrive in the order the writer
+/// accepted them. Dropping the subscription unsubscribes it.
+///
+pub struct Subscription {
+    pub id: u64,
+    pub receiver: Receiver<ChunkEntry>,
+}
+
+pub(crate) struct Subscriber {
+    id: u64,
+    predicate: EntryPredicate,
+    sender: SyncSender<ChunkEntry>,
+    dropped: u64,
+}
+
+impl Subscriber {
+    ///
+    /// Offer an entry to the subscriber without blocking the writer. An entry
+    /// that does not fit in a full channel is dropped.
+    ///
+    /// # Returns
+    /// `bool`: `false` once the receiving end has been dropped
+    ///
+    fn publish(&mut self, entry: &ChunkEntry) -> bool {
+        if !self.predicate.matches(entry) {
+            return true;
+        }
+        return match self.sender.try_send(entry.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                // Warn on the first drop and then only occasionally, a stalled
+                // subscriber would otherwise flood the log
+                if self.dropped.is_power_of_two() {
+                    warn!(
+                        crate::LOGGER,
+                        "Subscription {} is not keeping up, {} entries dropped",
+                        self.id,
+                        self.dropped
+                    );
+                }
+                true
+            },
+            Err(TrySendError::Disconnected(_)) => false,
+        };
+    }
+}
+
+impl ChunkStoreWriter {
+    ///
+    /// Follow entries as they are appended, pushing those that match a filter
+    /// to a channel. Entries are published once they are in the write-ahead
+    /// log, before they are sealed into a chunk.
+    ///
+    /// Relative time bounds such as `time:[now-1h TO now]` are rejected, as they
+    /// would stay fixed at the time of subscribing rather than follow the
+    /// entries; absolute bounds are allowed.
+    ///
+    /// # Arguments
+    /// * filter: Query text without stages selecting the entries to follow, empty for all
+    /// * start: Whether to begin with the newest matching entries already written
+    ///
+    /// # Returns
+    /// `Result<Subscription>`: Subscription to receive from, or `InvalidInput` if the filter is invalid
+    ///
+    pub fn subscribe(&mut self, filter: &str, start: SubscriptionStart) -> Result<Subscription, Error> {
+        let query: Query = match query_parser::parse_query(filter) {
+            Ok(q) => q,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+        if !query.stages.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Subscription filters cannot have stages"));
+        }
+        if query.expression.as_ref().is_some_and(has_relative_time_bound) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Subscription filters cannot have relative time bounds"));
+        }
+        // Without relative bounds the plan does not depend on the time it is made
+        let plan: QueryPlan = match QueryPlanner::plan(&query, 0) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+        let backlog: Vec<ChunkEntry> = match start {
+            SubscriptionStart::NOW => Vec::new(),
+            SubscriptionStart::LAST(count) => self.latest_matching(&plan.predicate, count)?,
+        };
+        let (sender, receiver): (SyncSender<ChunkEntry>, Receiver<ChunkEntry>) =
+            mpsc::sync_channel(self.config.subscription_buffer.max(1) + backlog.len());
+        for entry in backlog.into_iter().rev() {
+            // Cannot fail, the channel was sized for the backlog
+            let _ = sender.try_send(entry);
+        }
+        let id: u64 = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        self.subscribers.push(Subscriber {
+            id,
+            predicate: plan.predicate,
+            sender,
+            dropped: 0,
+        });
+        return Ok(Subscription { id, receiver });
+    }
+    pub fn unsubscribe(&mut self, id: u64) {
+        self.subscribers.retain(|s: &Subscriber| s.id != id);
+    }
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+    pub(crate) fn publish(&mut self, entry: &ChunkEntry) {
+        self.subscribers.retain_mut(|s: &mut Subscriber| s.publish(entry));
+    }
+    ///
+    /// Newest entries matching a predicate, newest first, from the open chunk
+    /// and then the store, reading one chunk at a time.
+    ///
+    fn latest_matching(&self, predicate: &EntryPredicate, count: usize) -> Result<Vec<ChunkEntry>, Error> {
+        let mut latest: Vec<ChunkEntry> = self.cache.entries.iter()
+            .rev()
+            .filter(|e: &&ChunkEntry| predicate.matches(e))
+            .take(count)
+            .cloned()
+            .collect();
+        let mut sealed: EntryIterator = self.store.entries_rev();
+        if self.resumed_latest_chunk {
+            // A resumed latest chunk is also held in the open chunk, which was read first
+            sealed = sealed.without_latest_chunk();
+        }
+        for entry in sealed {
+            if latest.len() >= count {
+                break;
+            }
+            let entry: ChunkEntry = entry?;
+            if predicate.matches(&entry) {
+                latest.push(entry);
+            }
+        }
+        return Ok(latest);
+    }
+}
+
+fn has_relative_time_bound(expression: &QueryExpression) -> bool {
+    return match expression {
+        QueryExpression::TIME_RANGE(from, to) => matches!(from, TimeBound::RELATIVE(_)) || matches!(to, TimeBound::RELATIVE(_)),
+        QueryExpression::AND(left, right) | QueryExpression::OR(left, right) => {
+            has_relative_time_bound(left) || has_relative_time_bound(right)
+        },
+        QueryExpression::NOT(inner) => has_relative_time_bound(inner),
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::store::chunk_store_writer::ChunkStoreWriterConfig;
+    use crate::utils::test_utils::{entry_at, temp_store_path, test_writer_config};
+
+    fn open_writer(name: &str, config: ChunkStoreWriterConfig) -> ChunkStoreWriter {
+        return ChunkStoreWriter::open(temp_store_path(name).as_str(), config).unwrap();
+    }
+
+    fn received(subscription: &Subscription) -> Vec<u64> {
+        return subscription.receiver.try_iter().map(|e: ChunkEntry| e.timestamp).collect();
+    }
+
+    #[test]
+    fn rejects_relative_time_bounds() {
+        let mut writer: ChunkStoreWriter = open_writer("rejects_relative_time_bounds", test_writer_config(4));
+        for filter in ["time:[now-1h TO now]", "target:api AND NOT time:[* TO now-5m]"] {
+            let error: Error = writer.subscribe(filter, SubscriptionStart::NOW).err().expect("Relative bounds were accepted");
+            assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        }
+        assert!(writer.subscribe("time:[1000 TO *] AND target:api", SubscriptionStart::NOW).is_ok());
+        assert_eq!(writer.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn from_now_delivers_only_later_appends() {
+        let mut writer: ChunkStoreWriter = open_writer("from_now_delivers_only_later_appends", test_writer_config(4));
+        for timestamp in 1..=5 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        let subscription: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        assert!(received(&subscription).is_empty());
+        // Delivered whether the entry stays open or seals the chunk
+        for timestamp in 6..=9 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        assert_eq!(received(&subscription), vec![6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn last_starts_with_newest_entries_oldest_first() {
+        let mut writer: ChunkStoreWriter = open_writer("last_starts_with_newest_entries_oldest_first", test_writer_config(4));
+        // Two sealed chunks and one entry still open
+        for timestamp in 1..=9 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        let subscription: Subscription = writer.subscribe("", SubscriptionStart::LAST(6)).unwrap();
+        writer.append(entry_at(10)).unwrap();
+        assert_eq!(received(&subscription), vec![4, 5, 6, 7, 8, 9, 10]);
+        let everything: Subscription = writer.subscribe("", SubscriptionStart::LAST(100)).unwrap();
+        assert_eq!(received(&everything), (1..=10).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn last_reads_resumed_latest_chunk_once() {
+        let path: String = temp_store_path("last_reads_resumed_latest_chunk_once");
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        for timestamp in 1..=6 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4)).unwrap();
+        assert!(writer.resumed_latest_chunk);
+        writer.append(entry_at(7)).unwrap();
+        let subscription: Subscription = writer.subscribe("", SubscriptionStart::LAST(4)).unwrap();
+        assert_eq!(received(&subscription), vec![4, 5, 6, 7]);
+        let everything: Subscription = writer.subscribe("", SubscriptionStart::LAST(100)).unwrap();
+        assert_eq!(received(&everything), (1..=7).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn delivers_only_matching_entries() {
+        let mut writer: ChunkStoreWriter = open_writer("delivers_only_matching_entries", test_writer_config(4));
+        let api_entry_at = |timestamp: u64| -> ChunkEntry {
+            return ChunkEntry {
+                target: b"api".to_vec(),
+                ..entry_at(timestamp)
+            };
+        };
+        for timestamp in 1..=6 {
+            let entry: ChunkEntry = if timestamp % 2 == 0 { api_entry_at(timestamp) } else { entry_at(timestamp) };
+            writer.append(entry).unwrap();
+        }
+        let subscription: Subscription = writer.subscribe("target:api", SubscriptionStart::LAST(2)).unwrap();
+        for timestamp in 7..=10 {
+            let entry: ChunkEntry = if timestamp % 2 == 0 { api_entry_at(timestamp) } else { entry_at(timestamp) };
+            writer.append(entry).unwrap();
+        }
+        assert_eq!(received(&subscription), vec![4, 6, 8, 10]);
+    }
+
+    #[test]
+    fn removes_unsubscribed_and_dropped_subscriptions() {
+        let mut writer: ChunkStoreWriter = open_writer("removes_unsubscribed_and_dropped_subscriptions", test_writer_config(4));
+        let kept: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        let unsubscribed: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        let dropped: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        assert_eq!(writer.subscriber_count(), 3);
+        writer.unsubscribe(unsubscribed.id);
+        assert_eq!(writer.subscriber_count(), 2);
+        drop(dropped);
+        // A dropped subscription is only noticed on the next publish
+        assert_eq!(writer.subscriber_count(), 2);
+        writer.append(entry_at(1)).unwrap();
+        assert_eq!(writer.subscriber_count(), 1);
+        assert_eq!(received(&kept), vec![1]);
+        assert!(received(&unsubscribed).is_empty());
+    }
+
+    #[test]
+    fn drops_entries_for_a_full_subscription() {
+        let mut config: ChunkStoreWriterConfig = test_writer_config(4);
+        config.subscription_buffer = 2;
+        let mut writer: ChunkStoreWriter = open_writer("drops_entries_for_a_full_subscription", config);
+        let subscription: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        for timestamp in 1..=5 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        // The writer is never blocked, and the subscription stays once drained
+        assert_eq!(received(&subscription), vec![1, 2]);
+        assert_eq!(writer.subscriber_count(), 1);
+        writer.append(entry_at(6)).unwrap();
+        assert_eq!(received(&subscription), vec![6]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Error;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::archive::archival::{ArchiveConfig, PlannedArchive};
+use super::chunk_store::ChunkStore;
+use super::chunk_store_writer::ChunkStoreWriter;
+use super::compaction::{CompactedRun, CompactionConfig};
+use super::recompression::{RecompressedChunk, RecompressionConfig};
+
+///
+/// Background thread running a shared writer's periodic work away from the
+/// threads appending to it. Every poll interval it runs the writer's tick,
+/// and when recompression, compaction or archival is due it reads and
+/// rewrites chunks through its own instance of the store, holding the writer
+/// only to swap them in.
+///
+pub struct WriterMaintenance {
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WriterMaintenance {
+    ///
+    /// Start maintaining a writer.
+    ///
+    /// # Arguments
+    /// * writer: Writer shared with the threads appending to it
+    /// * poll_interval: How often to run the writer's tick
+    ///
+    /// # Returns
+    /// `WriterMaintenance`: Running maintenance, stopped when dropped
+    ///
+    pub fn start(writer: Arc<Mutex<ChunkStoreWriter>>, poll_interval: Duration) -> WriterMaintenance {
+        let shutdown: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let thread_shutdown: Arc<AtomicBool> = shutdown.clone();
+        let thread: JoinHandle<()> = thread::spawn(move || {
+            while !thread_shutdown.load(Ordering::SeqCst) {
+                // Parked rather than asleep, so stopping does not wait out the interval
+                thread::park_timeout(poll_interval);
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Err(e) = Self::maintain(&writer) {
+                    warn!(crate::LOGGER, "Writer maintenance failed: {}", e);
+                }
+            }
+        });
+        return WriterMaintenance {
+            shutdown,
+            thread: Some(thread),
+        };
+    }
+    ///
+    /// Stop maintaining the writer, waiting for any run in progress to finish.
+    ///
+    pub fn stop(mut self) {
+        self.join();
+    }
+    fn join(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+    fn lock(writer: &Mutex<ChunkStoreWriter>) -> Result<MutexGuard<'_, ChunkStoreWriter>, Error> {
+        return writer.lock()
+            .map_err(|_| Error::other("Chunk store writer lock poisoned"));
+    }
+    fn maintain(writer: &Mutex<ChunkStoreWriter>) -> Result<(), Error> {
+        let (path, recompression, compaction, archive): (String, Option<RecompressionConfig>, Option<CompactionConfig>, Option<ArchiveConfig>) = {
+            let mut writer: MutexGuard<ChunkStoreWriter> = Self::lock(writer)?;
+            writer.tick()?;
+            (
+                writer.path.clone(),
+                writer.take_due_recompression(),
+                writer.take_due_compaction(),
+                writer.take_due_archival(),
+            )
+        };
+        // Each run is planned from a fresh instance of the store, whose lease
+        // keeps the planned originals in place until applied
+        if let Some(config) = recompression {
+            let store: ChunkStore = ChunkStore::read_from_file(path.as_str())?;
+            let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            let recompressed: Vec<RecompressedChunk> = store.plan_recompression(&config, now)?;
+            if !recompressed.is_empty() {
+                Self::lock(writer)?.apply_recompression(recompressed, &config)?;
+            }
+        }
+        if let Some(config) = compaction {
+            let store: ChunkStore = ChunkStore::read_from_file(path.as_str())?;
+            let compacted: Vec<CompactedRun> = store.plan_compaction(&config)?;
+            if !compacted.is_empty() {
+                Self::lock(writer)?.apply_compaction(compacted)?;
+            }
+        }
+        if let Some(config) = archive {
+            let store: ChunkStore = ChunkStore::read_from_file(path.as_str())?;
+            let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+            let planned: Option<PlannedArchive> = store.plan_archival(&config, now)?;
+            if let Some(planned) = planned {
+                Self::lock(writer)?.apply_archival(planned)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Drop for WriterMaintenance {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk_entry::ChunkEntry;
+    use crate::data::representational::store::chunk_store_writer::ChunkStoreWriterConfig;
+    use crate::utils::test_utils::{entry_at, temp_store_path, test_writer_config};
+
+    #[test]
+    fn compacts_small_chunks_off_the_writer() {
+        let path: String = temp_store_path("compacts_small_chunks_off_the_writer");
+        let mut config: ChunkStoreWriterConfig = test_writer_config(1);
+        config.compaction.as_mut().unwrap().interval = Duration::ZERO;
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+        for timestamp in 0..6 {
+            writer.append(entry_at(timestamp)).unwrap();
+        }
+        assert_eq!(writer.store.header.chunk_offsets.len(), 6);
+        let writer: Mutex<ChunkStoreWriter> = Mutex::new(writer);
+        WriterMaintenance::maintain(&writer).unwrap();
+        let writer: ChunkStoreWriter = writer.into_inner().unwrap();
+        // The latest chunk is left for the writer to resume
+        assert_eq!(writer.store.header.chunk_offsets.len(), 2);
+        assert_eq!(writer.store.header.chunk_count, 2);
+        let entries: Vec<ChunkEntry> = writer.store.read_chunk(0).unwrap().decode_entries().ok().expect("Could not decode entries");
+        assert_eq!(entries.iter().map(|e: &ChunkEntry| e.timestamp).collect::<Vec<u64>>(), vec![0, 1, 2, 3, 4]);
+    }
+}
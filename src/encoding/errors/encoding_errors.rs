@@ -7,7 +7,7 @@ pub struct EncoderError<T> {
 }
 
 impl<T> EncoderError<T> {
-    fn new(message: &str) -> EncoderError<T> {
+    pub fn new(message: &str) -> EncoderError<T> {
         EncoderError{
             message: message.to_string(),
             phantom: PhantomData,
@@ -33,7 +33,7 @@ pub struct DecoderError<T> {
 }
 
 impl<T> DecoderError<T> {
-    fn new(message: &str) -> DecoderError<T> {
+    pub fn new(message: &str) -> DecoderError<T> {
         DecoderError{
             message: message.to_string(),
             phantom: PhantomData,
@@ -59,7 +59,7 @@ pub struct TranscoderError<T> {
 }
 
 impl<T> TranscoderError<T> {
-    fn new(message: &str) -> TranscoderError<T> {
+    pub fn new(message: &str) -> TranscoderError<T> {
         TranscoderError{
             message: message.to_string(),
             phantom: PhantomData,
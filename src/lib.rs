@@ -0,0 +1,31 @@
+// Explicit returns and upper case enum variants are the house style
+#![allow(clippy::needless_return, non_camel_case_types)]
+
+pub mod compression;
+pub mod data;
+pub mod cache;
+pub mod logging;
+pub mod configuration;
+pub mod macros;
+pub mod encoding;
+pub mod compiler;
+pub mod utils;
+pub mod query;
+
+#[macro_use]
+extern crate slog;
+extern crate slog_term;
+extern crate slog_async;
+extern crate slog_json;
+extern crate lazy_static;
+extern crate regex;
+extern crate chrono;
+extern crate core;
+
+use lazy_static::lazy_static;
+use slog::Logger;
+use crate::logging::logging::initialize_logging;
+
+lazy_static! {
+    pub static ref LOGGER: Logger = initialize_logging(String::from("chunky_logs_"));
+}
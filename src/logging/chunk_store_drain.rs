@@ -0,0 +1,316 @@
+use std::fmt;
+use std::io::Error;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use slog::{Drain, Key, Level, OwnedKVList, Record, Serializer, KV};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store_writer::ChunkStoreWriter;
+use crate::data::representational::store::writer_maintenance::WriterMaintenance;
+
+///
+/// Action recorded for entries logged at a level. Levels map to their slog
+/// severity, from 1 for critical to 6 for trace.
+///
+pub fn level_action(level: Level) -> u8 {
+    return level.as_usize() as u8;
+}
+
+///
+/// Inverse of `level_action`, `None` for actions that are not a slog level.
+///
+pub fn action_level(action: u8) -> Option<Level> {
+    return Level::from_usize(action as usize);
+}
+
+///
+/// Inverse of `level_action`, for levels written by name as in slog-json output.
+///
+pub fn level_from_name(name: &str) -> Option<Level> {
+    return match name.to_ascii_uppercase().as_str() {
+        "CRIT" | "CRITICAL" => Some(Level::Critical),
+        "ERRO" | "ERROR" => Some(Level::Error),
+        "WARN" | "WARNING" => Some(Level::Warning),
+        "INFO" => Some(Level::Info),
+        "DEBG" | "DEBUG" => Some(Level::Debug),
+        "TRCE" | "TRACE" => Some(Level::Trace),
+        _ => None,
+    };
+}
+
+///
+/// Entry message for a log record: the record's message followed by its
+/// fields as `key=value` pairs, the form field comparisons in queries read.
+/// Values holding whitespace or quotes are double quoted, with `"` and `\`
+/// escaped by a backslash.
+///
+/// # Arguments
+/// * message: Formatted record message
+/// * fields: Record and logger key value pairs, in the order to write them
+///
+/// # Returns
+/// `Vec<u8>`: Message bytes for the entry
+///
+pub fn format_message(message: &str, fields: &[(String, String)]) -> Vec<u8> {
+    let mut formatted: String = String::from(message);
+    for (key, value) in fields.iter() {
+        if !formatted.is_empty() {
+            formatted.push(' ');
+        }
+        formatted.push_str(key.as_str());
+        formatted.push('=');
+        if value.is_empty() || value.chars().any(|c: char| c.is_whitespace() || c == '"') {
+            formatted.push('"');
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    formatted.push('\\');
+                }
+                formatted.push(c);
+            }
+            formatted.push('"');
+        } else {
+            formatted.push_str(value.as_str());
+        }
+    }
+    return formatted.into_bytes();
+}
+
+///
+/// Collects the key value pairs of a record as text.
+///
+#[derive(Default)]
+struct FieldCollector {
+    fields: Vec<(String, String)>,
+}
+
+impl Serializer for FieldCollector {
+    fn emit_arguments(&mut self, key: Key, value: &fmt::Arguments) -> slog::Result {
+        self.fields.push((key.to_string(), value.to_string()));
+        return Ok(());
+    }
+}
+
+///
+/// slog drain writing records into a chunk store, so services can log straight
+/// into ChunkyLogs. Each record becomes an entry stamped with the time it was
+/// logged, its level as the action, its module as the target, and its message
+/// and fields as the message. Records are appended through a buffered writer,
+/// so they are durable once logged and sealed into chunks as the writer's
+/// limits are reached. The writer's periodic work runs on a maintenance
+/// thread rather than on the threads logging.
+///
+/// The writer logs its own housekeeping to `crate::LOGGER`, so that logger must
+/// not itself feed this drain.
+///
+pub struct ChunkStoreDrain {
+    writer: Arc<Mutex<ChunkStoreWriter>>,
+    maintenance: Option<WriterMaintenance>,
+    /// Timestamp of the last record appended, only read and written under the writer lock
+    last_timestamp: AtomicU64,
+}
+
+impl ChunkStoreDrain {
+    ///
+    /// Drain into a writer, maintaining it in the background.
+    ///
+    /// # Arguments
+    /// * writer: Writer of the store to log into
+    /// * maintenance_interval: How often to run the writer's periodic work
+    ///
+    /// # Returns
+    /// `ChunkStoreDrain`: Drain owning the writer
+    ///
+    pub fn new(writer: ChunkStoreWriter, maintenance_interval: Duration) -> ChunkStoreDrain {
+        let writer: Arc<Mutex<ChunkStoreWriter>> = Arc::new(Mutex::new(writer));
+        return ChunkStoreDrain {
+            maintenance: Some(WriterMaintenance::start(writer.clone(), maintenance_interval)),
+            writer,
+            last_timestamp: AtomicU64::new(0),
+        };
+    }
+    ///
+    /// Seal any buffered records into a chunk.
+    ///
+    pub fn seal(&self) -> Result<(), Error> {
+        return self.lock()?.flush();
+    }
+    pub fn into_writer(mut self) -> ChunkStoreWriter {
+        if let Some(maintenance) = self.maintenance.take() {
+            maintenance.stop();
+        }
+        let writer: Mutex<ChunkStoreWriter> = match Arc::try_unwrap(self.writer) {
+            Ok(w) => w,
+            Err(_) => unreachable!("Only the stopped maintenance thread shares the writer"),
+        };
+        return match writer.into_inner() {
+            Ok(w) => w,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+    }
+    fn lock(&self) -> Result<MutexGuard<'_, ChunkStoreWriter>, Error> {
+        return self.writer.lock()
+            .map_err(|_| Error::other("Chunk store writer lock poisoned"));
+    }
+    ///
+    /// Entry for a record, left unstamped until the writer lock is held.
+    ///
+    fn entry_for(record: &Record, values: &OwnedKVList) -> ChunkEntry {
+        let mut collector: FieldCollector = FieldCollector::default();
+        // Serialising into the collector cannot fail
+        let _ = record.kv().serialize(record, &mut collector);
+        let _ = values.serialize(record, &mut collector);
+        return ChunkEntry {
+            timestamp: 0,
+            action: level_action(record.level()),
+            target: record.module().as_bytes().to_vec(),
+            message: format_message(record.msg().to_string().as_str(), collector.fields.as_slice()),
+        };
+    }
+}
+
+// The writer is only reached through its mutex, which recovers a writer
+// poisoned by a panic, so the drain can be shared by loggers directly
+impl UnwindSafe for ChunkStoreDrain {}
+impl RefUnwindSafe for ChunkStoreDrain {}
+
+impl Drain for ChunkStoreDrain {
+    type Ok = ();
+    type Err = Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let mut entry: ChunkEntry = Self::entry_for(record, values);
+        let mut writer: MutexGuard<ChunkStoreWriter> = self.lock()?;
+        // Stamped under the lock and never behind the last record, so a record
+        // cannot fall behind a chunk another thread sealed while it waited
+        let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        entry.timestamp = now.max(self.last_timestamp.load(Ordering::Relaxed));
+        self.last_timestamp.store(entry.timestamp, Ordering::Relaxed);
+        writer.append(entry)?;
+        // Sealing an aged chunk here saves waiting for the maintenance thread;
+        // the rest of the writer's periodic work is left to that thread
+        if writer.is_seal_due() {
+            writer.flush()?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::Logger;
+    use crate::data::representational::store::chunk_store_writer::{ChunkStoreWriterConfig, LateEntryPolicy};
+    use crate::utils::test_utils::{temp_store_path, test_writer_config};
+
+    ///
+    /// Log through a logger over a drain into a new store and take the writer
+    /// back once the logger is dropped.
+    ///
+    fn log_into_store(name: &str, config: ChunkStoreWriterConfig, log: impl FnOnce(&Logger)) -> ChunkStoreWriter {
+        let writer: ChunkStoreWriter = ChunkStoreWriter::open(temp_store_path(name).as_str(), config).unwrap();
+        let drain: Arc<ChunkStoreDrain> = Arc::new(ChunkStoreDrain::new(writer, Duration::from_secs(60)));
+        let logger: Logger = Logger::root(drain.clone().fuse(), o!("service" => "api", "region" => "eu west"));
+        log(&logger);
+        drop(logger);
+        return match Arc::try_unwrap(drain) {
+            Ok(d) => d.into_writer(),
+            Err(_) => panic!("Logger still holds the drain"),
+        };
+    }
+
+    fn all_entries(writer: &ChunkStoreWriter) -> Vec<ChunkEntry> {
+        let mut entries: Vec<ChunkEntry> = writer.store.entries()
+            .map(|e: Result<ChunkEntry, Error>| e.unwrap())
+            .collect();
+        entries.extend(writer.cache.entries.iter().cloned());
+        return entries;
+    }
+
+    #[test]
+    fn maps_levels_to_actions() {
+        let levels: [Level; 6] = [Level::Critical, Level::Error, Level::Warning, Level::Info, Level::Debug, Level::Trace];
+        for (level, action) in levels.iter().zip(1..=6) {
+            assert_eq!(level_action(*level), action);
+            assert_eq!(action_level(action), Some(*level));
+            assert_eq!(level_from_name(level.as_short_str()), Some(*level));
+            assert_eq!(level_from_name(level.as_str()), Some(*level));
+        }
+        assert_eq!(action_level(0), None);
+        assert_eq!(action_level(7), None);
+        assert_eq!(level_from_name("verbose"), None);
+    }
+
+    #[test]
+    fn writes_records_with_level_module_and_fields() {
+        let writer: ChunkStoreWriter = log_into_store("writes_records_with_level_module_and_fields", test_writer_config(3), |logger: &Logger| {
+            error!(logger, "request failed"; "status" => 503, "path" => "/a b");
+            warn!(logger, "slow request"; "latency" => 1.5);
+            debug!(logger, "cache {}", "miss");
+        });
+        let entries: Vec<ChunkEntry> = all_entries(&writer);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries.iter().map(|e: &ChunkEntry| e.action).collect::<Vec<u8>>(),
+            vec![level_action(Level::Error), level_action(Level::Warning), level_action(Level::Debug)],
+        );
+        for entry in entries.iter() {
+            assert_eq!(entry.target, module_path!().as_bytes());
+        }
+        // Record fields come before the logger's, each group last field first as slog serializes them
+        assert_eq!(
+            String::from_utf8(entries[0].message.clone()).unwrap(),
+            "request failed path=\"/a b\" status=503 region=\"eu west\" service=api",
+        );
+        assert_eq!(
+            String::from_utf8(entries[2].message.clone()).unwrap(),
+            "cache miss region=\"eu west\" service=api",
+        );
+        assert!(entries.windows(2).all(|w: &[ChunkEntry]| w[0].timestamp <= w[1].timestamp));
+    }
+
+    #[test]
+    fn seals_once_the_writer_is_due() {
+        let writer: ChunkStoreWriter = log_into_store("seals_once_the_writer_is_due", test_writer_config(3), |logger: &Logger| {
+            for i in 0..7 {
+                info!(logger, "record {}", i);
+            }
+        });
+        // Two full chunks sealed while logging, the last record left open
+        assert_eq!(writer.store.header.chunk_offsets.len(), 2);
+        assert_eq!(writer.cache.entries.len(), 1);
+        let messages: Vec<Vec<u8>> = all_entries(&writer).into_iter().map(|e: ChunkEntry| e.message).collect();
+        assert_eq!(messages[6], format_message("record 6", &[
+            (String::from("region"), String::from("eu west")),
+            (String::from("service"), String::from("api")),
+        ]));
+    }
+
+    #[test]
+    fn stamps_records_from_concurrent_loggers_in_order() {
+        let config: ChunkStoreWriterConfig = ChunkStoreWriterConfig {
+            late_entry_policy: LateEntryPolicy::REJECT,
+            ..test_writer_config(2)
+        };
+        // A record stamped before waiting for the writer could fall behind a
+        // chunk sealed meanwhile, be rejected and panic its logging thread
+        let writer: ChunkStoreWriter = log_into_store("stamps_records_from_concurrent_loggers_in_order", config, |logger: &Logger| {
+            let threads: Vec<std::thread::JoinHandle<()>> = (0..4)
+                .map(|t: usize| {
+                    let logger: Logger = logger.clone();
+                    std::thread::spawn(move || {
+                        for i in 0..100 {
+                            info!(logger, "record {} from {}", i, t);
+                        }
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().expect("Logging thread panicked");
+            }
+        });
+        let entries: Vec<ChunkEntry> = all_entries(&writer);
+        assert_eq!(entries.len(), 400);
+        assert!(entries.windows(2).all(|w: &[ChunkEntry]| w[0].timestamp <= w[1].timestamp));
+    }
+}
@@ -0,0 +1,270 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+use serde_json::{Map, Value};
+use slog::Level;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store_writer::ChunkStoreWriter;
+use super::chunk_store_drain::{format_message, level_action, level_from_name};
+
+#[derive(Debug, Default, Clone)]
+pub struct JsonLogImportSummary {
+    pub files: usize,
+    pub records_imported: u64,
+    pub lines_skipped: u64,
+    pub records_rejected: u64,
+    pub timestamp_from: Option<u64>,
+    pub timestamp_to: Option<u64>,
+}
+
+///
+/// Milliseconds since the epoch of a record timestamp, the unit
+/// `epoch_to_datetime` reads. slog-json writes RFC 3339 strings; numbers are
+/// taken as milliseconds already.
+///
+fn parse_timestamp(value: &Value) -> Option<u64> {
+    return match value {
+        Value::String(text) => match chrono::DateTime::parse_from_rfc3339(text.as_str()) {
+            Ok(datetime) if datetime.timestamp_millis() >= 0 => Some(datetime.timestamp_millis() as u64),
+            _ => None,
+        },
+        Value::Number(number) => number.as_u64(),
+        _ => None,
+    };
+}
+
+///
+/// Convert one line of slog-json output into an entry, in the form
+/// `ChunkStoreDrain` would have written the record: its level as the action,
+/// its module as the target, and its message followed by the remaining keys
+/// as the message. Keys are written in name order, as slog-json does not
+/// keep the order they were logged in.
+///
+/// # Arguments
+/// * line: JSON object holding `msg`, `level` and `ts`, and optionally `module`
+///
+/// # Returns
+/// `Result<ChunkEntry>`: Entry for the record, or `InvalidData` if the line is not a record
+///
+pub fn parse_json_record(line: &str) -> Result<ChunkEntry, Error> {
+    let mut record: Map<String, Value> = match serde_json::from_str::<Value>(line) {
+        Ok(Value::Object(o)) => o,
+        Ok(_) => return Err(Error::new(ErrorKind::InvalidData, "Record is not a JSON object")),
+        Err(e) => return Err(Error::new(ErrorKind::InvalidData, e.to_string())),
+    };
+    let timestamp: u64 = match record.remove("ts").as_ref().and_then(parse_timestamp) {
+        Some(t) => t,
+        None => return Err(Error::new(ErrorKind::InvalidData, "Record has no valid timestamp")),
+    };
+    let level: Level = match record.remove("level") {
+        Some(Value::String(name)) => match level_from_name(name.as_str()) {
+            Some(l) => l,
+            None => return Err(Error::new(ErrorKind::InvalidData, format!("Unknown level {}", name))),
+        },
+        _ => return Err(Error::new(ErrorKind::InvalidData, "Record has no level")),
+    };
+    let message: String = match record.remove("msg") {
+        Some(Value::String(m)) => m,
+        Some(other) => other.to_string(),
+        None => String::new(),
+    };
+    let module: String = match record.remove("module") {
+        Some(Value::String(m)) => m,
+        _ => String::new(),
+    };
+    let fields: Vec<(String, String)> = record.into_iter()
+        .map(|(key, value): (String, Value)| match value {
+            Value::String(s) => (key, s),
+            other => (key, other.to_string()),
+        })
+        .collect();
+    return Ok(ChunkEntry {
+        timestamp,
+        action: level_action(level),
+        target: module.into_bytes(),
+        message: format_message(message.as_str(), fields.as_slice()),
+    });
+}
+
+///
+/// Bulk load newline delimited slog-json files, such as those written by
+/// `initialize_logging`, into a store. Records are appended in file order and
+/// sealed into chunks by the writer's limits, with the remainder sealed once
+/// every file is read. Lines that are not records, such as one cut short by a
+/// crash, are skipped with a warning.
+///
+/// Files should be given oldest first: records older than a chunk already
+/// sealed are handled by the writer's late entry policy, and those it rejects
+/// are counted and skipped with a warning. The log files of
+/// `initialize_logging` sort oldest first by name.
+///
+/// # Arguments
+/// * writer: Writer of the store to load into
+/// * paths: Log files to import, oldest first
+///
+/// # Returns
+/// `Result<JsonLogImportSummary>`: Records imported, lines skipped and records rejected
+///
+pub fn import_json_logs(writer: &mut ChunkStoreWriter, paths: &[&str]) -> Result<JsonLogImportSummary, Error> {
+    let mut summary: JsonLogImportSummary = JsonLogImportSummary::default();
+    for path in paths.iter() {
+        let mut reader: BufReader<File> = BufReader::new(File::open(path)?);
+        let mut line: Vec<u8> = Vec::new();
+        let mut line_number: u64 = 0;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            line_number += 1;
+            let text: String = String::from_utf8_lossy(line.as_slice()).into_owned();
+            if text.trim().is_empty() {
+                continue;
+            }
+            let entry: ChunkEntry = match parse_json_record(text.as_str()) {
+                Ok(e) => e,
+                Err(e) => {
+                    warn!(crate::LOGGER, "Skipping line {} of {}: {}", line_number, path, e);
+                    summary.lines_skipped += 1;
+                    continue;
+                },
+            };
+            let timestamp: u64 = entry.timestamp;
+            match writer.append(entry) {
+                Ok(()) => {},
+                Err(e) if e.kind() == ErrorKind::InvalidInput => {
+                    warn!(crate::LOGGER, "Skipping record on line {} of {}: {}", line_number, path, e);
+                    summary.records_rejected += 1;
+                    continue;
+                },
+                Err(e) => return Err(e),
+            }
+            summary.timestamp_from = Some(summary.timestamp_from.map_or(timestamp, |t: u64| t.min(timestamp)));
+            summary.timestamp_to = Some(summary.timestamp_to.map_or(timestamp, |t: u64| t.max(timestamp)));
+            summary.records_imported += 1;
+        }
+        summary.files += 1;
+        info!(crate::LOGGER, "Imported {} to {}", path, writer.path);
+    }
+    writer.flush()?;
+    return Ok(summary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use slog::{Drain, Logger};
+    use slog_json::Json;
+    use crate::data::representational::store::chunk_store::ChunkStore;
+    use crate::data::representational::store::chunk_store_writer::{ChunkStoreWriterConfig, LateEntryPolicy};
+    use crate::utils::test_utils::{temp_store_path, test_writer_config};
+
+    ///
+    /// Lines slog-json writes for two records, as `initialize_logging` writes
+    /// its log files, with the time before and after they were logged.
+    ///
+    fn slog_json_lines(path: &str) -> (Vec<String>, u64, u64) {
+        let before: u64 = chrono::Utc::now().timestamp_millis() as u64;
+        {
+            let file: File = File::create(path).unwrap();
+            let log: Logger = Logger::root(Mutex::new(Json::default(file)).fuse(), o!("module" => "ingest"));
+            warn!(log, "disk full"; "free" => 0, "device" => "sda");
+            error!(log, "write failed"; "path" => "/var/log/a b", "retry" => true);
+        }
+        let after: u64 = chrono::Utc::now().timestamp_millis() as u64;
+        let lines: Vec<String> = std::fs::read_to_string(path).unwrap().lines().map(String::from).collect();
+        return (lines, before, after);
+    }
+
+    #[test]
+    fn parses_slog_json_records() {
+        let path: String = temp_store_path("parses_slog_json_records");
+        let (lines, before, after): (Vec<String>, u64, u64) = slog_json_lines(format!("{}.log", path).as_str());
+        assert_eq!(lines.len(), 2);
+
+        let warning: ChunkEntry = parse_json_record(lines[0].as_str()).unwrap();
+        assert!(warning.timestamp >= before - 1 && warning.timestamp <= after, "{} not in {}..{}", warning.timestamp, before, after);
+        assert_eq!(warning.action, level_action(Level::Warning));
+        assert_eq!(warning.target, b"ingest".to_vec());
+        // Extra keys follow the message in name order
+        assert_eq!(String::from_utf8(warning.message).unwrap(), "disk full device=sda free=0");
+        let failure: ChunkEntry = parse_json_record(lines[1].as_str()).unwrap();
+        assert_eq!(failure.action, level_action(Level::Error));
+        assert_eq!(String::from_utf8(failure.message).unwrap(), "write failed path=\"/var/log/a b\" retry=true");
+        assert!(failure.timestamp >= warning.timestamp);
+
+        // RFC 3339 offsets are honoured to the millisecond
+        let record: ChunkEntry = parse_json_record(
+            r#"{"ts":"2024-05-01T12:00:00.250+02:00","level":"DEBG","msg":"nested","z":1,"a":{"b":[2,3]}}"#,
+        ).unwrap();
+        assert_eq!(record.timestamp, 1_714_557_600_250);
+        assert_eq!(record.action, level_action(Level::Debug));
+        assert!(record.target.is_empty());
+        assert_eq!(String::from_utf8(record.message).unwrap(), "nested a=\"{\\\"b\\\":[2,3]}\" z=1");
+
+        for bad in [
+            r#"{"ts":"2024-05-01T12:00:00+02:00","level":"INFO","ms"#,
+            r#"{"ts":"yesterday","level":"INFO","msg":"m"}"#,
+            r#"{"ts":"2024-05-01T12:00:00Z","level":"LOUD","msg":"m"}"#,
+            r#"{"level":"INFO","msg":"m"}"#,
+            r#"["not", "a", "record"]"#,
+        ] {
+            assert_eq!(parse_json_record(bad).unwrap_err().kind(), ErrorKind::InvalidData, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn imports_slog_json_files_skipping_bad_lines() {
+        let path: String = temp_store_path("imports_slog_json_files_skipping_bad_lines");
+        let log_path: String = format!("{}.log", path);
+        let (lines, _, _): (Vec<String>, u64, u64) = slog_json_lines(log_path.as_str());
+        // A line cut short by a crash sits between the two records
+        std::fs::write(log_path.as_str(), format!("{}\n{{\"ts\":\"2024-\n\n{}\n", lines[0], lines[1])).unwrap();
+
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), test_writer_config(4096)).unwrap();
+        let summary: JsonLogImportSummary = import_json_logs(&mut writer, &[log_path.as_str()]).unwrap();
+        assert_eq!((summary.files, summary.records_imported, summary.lines_skipped), (1, 2, 1));
+        let expected: Vec<ChunkEntry> = lines.iter().map(|l: &String| parse_json_record(l.as_str()).unwrap()).collect();
+        assert_eq!(summary.timestamp_from, Some(expected[0].timestamp));
+        assert_eq!(summary.timestamp_to, Some(expected[1].timestamp));
+        drop(writer);
+
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let imported: Vec<ChunkEntry> = store.entries().map(|e: Result<ChunkEntry, Error>| e.unwrap()).collect();
+        assert_eq!(imported.len(), 2);
+        for (entry, expected) in imported.iter().zip(expected.iter()) {
+            assert_eq!((entry.timestamp, entry.action, &entry.target, &entry.message), (expected.timestamp, expected.action, &expected.target, &expected.message));
+        }
+    }
+
+    #[test]
+    fn skips_records_the_late_entry_policy_rejects() {
+        let path: String = temp_store_path("skips_records_the_late_entry_policy_rejects");
+        let newer_path: String = format!("{}.1.log", path);
+        let older_path: String = format!("{}.0.log", path);
+        std::fs::write(newer_path.as_str(), concat!(
+            r#"{"ts":"2024-05-01T12:00:00Z","level":"INFO","msg":"first"}"#, "\n",
+            r#"{"ts":"2024-05-01T12:00:01Z","level":"INFO","msg":"second"}"#, "\n",
+        )).unwrap();
+        std::fs::write(older_path.as_str(), concat!(
+            r#"{"ts":"2024-05-01T11:00:00Z","level":"INFO","msg":"late"}"#, "\n",
+            r#"{"ts":"2024-05-01T12:00:02Z","level":"INFO","msg":"third"}"#, "\n",
+        )).unwrap();
+
+        let config: ChunkStoreWriterConfig = ChunkStoreWriterConfig {
+            late_entry_policy: LateEntryPolicy::REJECT,
+            ..test_writer_config(2)
+        };
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(path.as_str(), config).unwrap();
+        // Given out of order, so the older file's first record lands behind a sealed chunk
+        let summary: JsonLogImportSummary = import_json_logs(&mut writer, &[newer_path.as_str(), older_path.as_str()]).unwrap();
+        assert_eq!((summary.files, summary.records_imported, summary.lines_skipped, summary.records_rejected), (2, 3, 0, 1));
+        assert_eq!(summary.timestamp_from, Some(1_714_564_800_000));
+        assert_eq!(summary.timestamp_to, Some(1_714_564_802_000));
+        drop(writer);
+
+        let store: ChunkStore = ChunkStore::read_from_file(path.as_str()).unwrap();
+        let messages: Vec<Vec<u8>> = store.entries().map(|e: Result<ChunkEntry, Error>| e.unwrap().message).collect();
+        assert_eq!(messages, vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]);
+    }
+}
@@ -0,0 +1,292 @@
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use chrono::SecondsFormat;
+use serde_json::Value;
+use slog::Level;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::query::entry_fields::split_message;
+use crate::query::query::{Query, QueryExpression, TimeBound};
+use crate::query::query_planner::{QueryPlan, QueryPlanner};
+use crate::utils::datetime_utils::epoch_to_datetime;
+use super::chunk_store_drain::action_level;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// One slog-json record per line, readable by `import_json_logs`
+    NDJSON,
+    /// Header row then `timestamp,level,module,message` rows, quoted as in RFC 4180
+    CSV,
+    /// Lines as written to the terminal by `initialize_logging`
+    TEXT,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        return match name.to_ascii_lowercase().as_str() {
+            "ndjson" | "jsonl" | "json" => Some(ExportFormat::NDJSON),
+            "csv" => Some(ExportFormat::CSV),
+            "text" | "txt" => Some(ExportFormat::TEXT),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExportSummary {
+    pub entries_exported: u64,
+    pub bytes_written: u64,
+}
+
+///
+/// Short level name of an entry's action as slog writes it, or the action
+/// number for actions that are not a level.
+///
+fn level_name(action: u8) -> String {
+    return action_level(action).map_or(action.to_string(), |l: Level| String::from(l.as_short_str()));
+}
+
+///
+/// Field value as JSON: numbers and booleans written as such by slog-json are
+/// restored, anything else is a string.
+///
+#[allow(clippy::cmp_owned)]
+fn json_value(value: &str) -> String {
+    // Only restored if written back exactly as read, so "1.0" stays a string
+    return match serde_json::from_str::<Value>(value) {
+        Ok(v @ Value::Number(_)) | Ok(v @ Value::Bool(_)) if v.to_string() == value => String::from(value),
+        _ => Value::String(String::from(value)).to_string(),
+    };
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    return String::from(value);
+}
+
+fn format_entry(format: ExportFormat, entry: &ChunkEntry) -> String {
+    let module: String = String::from_utf8_lossy(entry.target.as_slice()).into_owned();
+    let message: String = String::from_utf8_lossy(entry.message.as_slice()).into_owned();
+    return match format {
+        ExportFormat::NDJSON => {
+            let (msg, fields): (String, Vec<(String, String)>) = split_message(message.as_str());
+            // Written by hand to keep slog-json's key order of msg, level, ts
+            let mut line: String = format!(
+                "{{\"msg\":{},\"level\":{},\"ts\":{}",
+                Value::String(msg),
+                Value::String(level_name(entry.action)),
+                Value::String(epoch_to_datetime(entry.timestamp).to_rfc3339_opts(SecondsFormat::Millis, true)),
+            );
+            if !module.is_empty() {
+                line.push_str(format!(",\"module\":{}", Value::String(module)).as_str());
+            }
+            for (key, value) in fields.iter() {
+                line.push_str(format!(",{}:{}", Value::String(key.clone()), json_value(value.as_str())).as_str());
+            }
+            line.push_str("}\n");
+            line
+        },
+        ExportFormat::CSV => format!(
+            "{},{},{},{}\n",
+            epoch_to_datetime(entry.timestamp).to_rfc3339_opts(SecondsFormat::Millis, true),
+            level_name(entry.action),
+            csv_field(module.as_str()),
+            csv_field(message.as_str()),
+        ),
+        ExportFormat::TEXT => {
+            let (msg, fields): (String, Vec<(String, String)>) = split_message(message.as_str());
+            let mut line: String = format!(
+                "[{}] [{}] {}: {}",
+                epoch_to_datetime(entry.timestamp).format("%Y-%m-%d %H:%M:%S%.3f"),
+                module.rsplit("::").next().unwrap_or(""),
+                level_name(entry.action),
+                msg,
+            );
+            for (key, value) in fields.iter() {
+                line.push_str(format!(", {}: {}", key, value).as_str());
+            }
+            line.push('\n');
+            line
+        },
+    };
+}
+
+///
+/// Stream the entries matched by a plan to a writer. Only one chunk is held
+/// at a time, so stores larger than memory can be exported.
+///
+fn export_plan(store: &ChunkStore, plan: &QueryPlan, format: ExportFormat, out: &mut dyn Write) -> Result<ExportSummary, Error> {
+    let mut summary: ExportSummary = ExportSummary::default();
+    let mut writer: BufWriter<&mut dyn Write> = BufWriter::new(out);
+    if format == ExportFormat::CSV {
+        let header: &str = "timestamp,level,module,message\n";
+        writer.write_all(header.as_bytes())?;
+        summary.bytes_written += header.len() as u64;
+    }
+    summary.entries_exported = plan.for_each_match(store, &mut |entry: &ChunkEntry| {
+        let line: String = format_entry(format, entry);
+        writer.write_all(line.as_bytes())?;
+        summary.bytes_written += line.len() as u64;
+        return Ok(());
+    })?;
+    writer.flush()?;
+    return Ok(summary);
+}
+
+///
+/// Export the entries of a store matching a query, archived chunks first, then
+/// in store order. The query may end in `| limit n` but cannot aggregate.
+///
+/// # Arguments
+/// * store: Store to export from
+/// * text: Query text selecting the entries, empty for all
+/// * format: Format to write entries in
+/// * out: Destination of the export
+///
+/// # Returns
+/// `Result<ExportSummary>`: Entries and bytes written, or `InvalidInput` if the query is invalid
+///
+pub fn export_query(store: &ChunkStore, text: &str, format: ExportFormat, out: &mut dyn Write) -> Result<ExportSummary, Error> {
+    let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    return match QueryPlanner::plan_text(text, now) {
+        Ok(plan) => export_plan(store, &plan, format, out),
+        Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+    };
+}
+
+///
+/// Export the entries of a store within a time range.
+///
+/// # Arguments
+/// * store: Store to export from
+/// * from: Start of the range in milliseconds since the epoch, inclusive
+/// * to: End of the range in milliseconds since the epoch, inclusive
+/// * format: Format to write entries in
+/// * out: Destination of the export
+///
+/// # Returns
+/// `Result<ExportSummary>`: Entries and bytes written
+///
+pub fn export_range(store: &ChunkStore, from: u64, to: u64, format: ExportFormat, out: &mut dyn Write) -> Result<ExportSummary, Error> {
+    let query: Query = Query {
+        expression: Some(QueryExpression::TIME_RANGE(TimeBound::ABSOLUTE(from), TimeBound::ABSOLUTE(to))),
+        stages: Vec::new(),
+    };
+    return match QueryPlanner::plan(&query, 0) {
+        Ok(plan) => export_plan(store, &plan, format, out),
+        Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::compressor::CompressionCodec;
+    use crate::data::representational::chunk::Chunk;
+    use crate::data::representational::store::chunk_store_writer::ChunkStoreWriter;
+    use crate::logging::chunk_store_drain::{format_message, level_action};
+    use crate::logging::json_log_import::{import_json_logs, JsonLogImportSummary};
+    use crate::utils::test_utils::{temp_store_path, test_writer_config};
+
+    fn entry(timestamp: u64, level: Level, target: &str, message: &str, fields: &[(&str, &str)]) -> ChunkEntry {
+        let fields: Vec<(String, String)> = fields.iter().map(|(k, v): &(&str, &str)| (String::from(*k), String::from(*v))).collect();
+        return ChunkEntry {
+            timestamp,
+            action: level_action(level),
+            target: target.as_bytes().to_vec(),
+            message: format_message(message, fields.as_slice()),
+        };
+    }
+
+    ///
+    /// Store of three records as `ChunkStoreDrain` writes them, with messages
+    /// holding commas, quotes and newlines. Fields are in name order, as
+    /// `import_json_logs` writes them back.
+    ///
+    fn sample_store(path: &str) -> (ChunkStore, Vec<ChunkEntry>) {
+        let entries: Vec<ChunkEntry> = vec![
+            entry(1_714_557_600_250, Level::Warning, "chunky_logs::ingest", "disk full, retrying", &[("device", "sda"), ("free", "0")]),
+            entry(1_714_557_601_250, Level::Error, "chunky_logs::ingest", "write \"a\" failed\nsecond line", &[("path", "/var/log/a b"), ("retry", "true")]),
+            entry(1_714_557_602_000, Level::Info, "main", "started", &[]),
+        ];
+        let mut store: ChunkStore = ChunkStore::create(path, 64, false).unwrap();
+        store.append_chunk(Chunk::seal_with(&entries[..2], CompressionCodec::ZLIB_FAST, 0.01).ok().expect("Could not seal chunk")).unwrap();
+        store.append_chunk(Chunk::seal_with(&entries[2..], CompressionCodec::ZLIB_FAST, 0.01).ok().expect("Could not seal chunk")).unwrap();
+        return (store, entries);
+    }
+
+    fn export(store: &ChunkStore, format: ExportFormat) -> (String, ExportSummary) {
+        let mut out: Vec<u8> = Vec::new();
+        let summary: ExportSummary = export_range(store, 0, u64::MAX, format, &mut out).unwrap();
+        assert_eq!(summary.bytes_written, out.len() as u64);
+        return (String::from_utf8(out).unwrap(), summary);
+    }
+
+    #[test]
+    fn exports_ndjson_that_imports_back() {
+        let path: String = temp_store_path("exports_ndjson_that_imports_back");
+        let (store, entries): (ChunkStore, Vec<ChunkEntry>) = sample_store(path.as_str());
+        let (text, summary): (String, ExportSummary) = export(&store, ExportFormat::NDJSON);
+        assert_eq!(summary.entries_exported, 3);
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(
+            text.lines().next().unwrap(),
+            r#"{"msg":"disk full, retrying","level":"WARN","ts":"2024-05-01T10:00:00.250Z","module":"chunky_logs::ingest","device":"sda","free":0}"#,
+        );
+
+        let export_path: String = format!("{}.ndjson", path);
+        std::fs::write(export_path.as_str(), text.as_bytes()).unwrap();
+        let import_path: String = temp_store_path("exports_ndjson_that_imports_back_import");
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(import_path.as_str(), test_writer_config(4096)).unwrap();
+        let imported: JsonLogImportSummary = import_json_logs(&mut writer, &[export_path.as_str()]).unwrap();
+        assert_eq!((imported.records_imported, imported.lines_skipped), (3, 0));
+        drop(writer);
+
+        let store: ChunkStore = ChunkStore::read_from_file(import_path.as_str()).unwrap();
+        let loaded: Vec<ChunkEntry> = store.entries().map(|e: Result<ChunkEntry, Error>| e.unwrap()).collect();
+        assert_eq!(loaded.len(), entries.len());
+        for (entry, expected) in loaded.iter().zip(entries.iter()) {
+            assert_eq!(
+                (entry.timestamp, entry.action, String::from_utf8_lossy(&entry.target), String::from_utf8_lossy(&entry.message)),
+                (expected.timestamp, expected.action, String::from_utf8_lossy(&expected.target), String::from_utf8_lossy(&expected.message)),
+            );
+        }
+    }
+
+    #[test]
+    fn exports_csv_with_quoted_fields() {
+        let path: String = temp_store_path("exports_csv_with_quoted_fields");
+        let (store, _): (ChunkStore, Vec<ChunkEntry>) = sample_store(path.as_str());
+        let (text, summary): (String, ExportSummary) = export(&store, ExportFormat::CSV);
+        assert_eq!(summary.entries_exported, 3);
+        assert_eq!(text, concat!(
+            "timestamp,level,module,message\n",
+            "2024-05-01T10:00:00.250Z,WARN,chunky_logs::ingest,\"disk full, retrying device=sda free=0\"\n",
+            "2024-05-01T10:00:01.250Z,ERRO,chunky_logs::ingest,\"write \"\"a\"\" failed\nsecond line path=\"\"/var/log/a b\"\" retry=true\"\n",
+            "2024-05-01T10:00:02.000Z,INFO,main,started\n",
+        ));
+        assert_eq!(csv_field("a\rb"), "\"a\rb\"");
+        assert_eq!(csv_field("plain text"), "plain text");
+    }
+
+    #[test]
+    fn exports_text_as_terminal_lines() {
+        let path: String = temp_store_path("exports_text_as_terminal_lines");
+        let (store, _): (ChunkStore, Vec<ChunkEntry>) = sample_store(path.as_str());
+        let (text, summary): (String, ExportSummary) = export(&store, ExportFormat::TEXT);
+        assert_eq!(summary.entries_exported, 3);
+        assert_eq!(text, concat!(
+            "[2024-05-01 10:00:00.250] [ingest] WARN: disk full, retrying, device: sda, free: 0\n",
+            "[2024-05-01 10:00:01.250] [ingest] ERRO: write \"a\" failed\nsecond line, path: /var/log/a b, retry: true\n",
+            "[2024-05-01 10:00:02.000] [main] INFO: started\n",
+        ));
+
+        // Queries select what is exported
+        let mut out: Vec<u8> = Vec::new();
+        let selected: ExportSummary = export_query(&store, "target:main", ExportFormat::TEXT, &mut out).unwrap();
+        assert_eq!(selected.entries_exported, 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "[2024-05-01 10:00:02.000] [main] INFO: started\n");
+        assert_eq!(export_query(&store, "(unclosed", ExportFormat::TEXT, &mut Vec::new()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
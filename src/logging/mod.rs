@@ -1 +1,7 @@
-pub mod logging;
\ No newline at end of file
+pub mod chunk_store_drain;
+pub mod json_log_import;
+pub mod log_export;
+#[allow(clippy::module_inception)]
+pub mod logging;
+pub mod syslog_listener;
+pub mod syslog_parser;
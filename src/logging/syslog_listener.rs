@@ -0,0 +1,373 @@
+use std::io::{Error, ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::configuration::config::Config;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store_writer::ChunkStoreWriter;
+use crate::data::representational::store::writer_maintenance::WriterMaintenance;
+use super::syslog_parser::parse_syslog;
+
+#[derive(Debug, Clone)]
+pub struct SyslogListenerConfig {
+    /// Local address the UDP and TCP listeners bind to, port 0 picks a free port for each
+    pub address: String,
+    pub udp: bool,
+    pub tcp: bool,
+    /// Longest message accepted, longer datagrams are truncated and longer TCP frames end the connection
+    pub max_message_length: usize,
+    /// Most TCP connections served at once, further connections are closed on accepting them
+    pub max_connections: usize,
+    /// How often idle listeners wake to check for shutdown, and the writer's maintenance runs
+    pub poll_interval: Duration,
+}
+
+impl Default for SyslogListenerConfig {
+    fn default() -> Self {
+        SyslogListenerConfig {
+            address: String::from("127.0.0.1:5514"),
+            udp: true,
+            tcp: true,
+            max_message_length: 64 * 1024,
+            max_connections: 256,
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl SyslogListenerConfig {
+    pub fn from_config(config: &mut Config) -> SyslogListenerConfig {
+        let defaults: SyslogListenerConfig = SyslogListenerConfig::default();
+        SyslogListenerConfig {
+            address: config.get_or_default("ingest.syslog.address", defaults.address),
+            udp: config.get_or_default("ingest.syslog.udp", defaults.udp),
+            tcp: config.get_or_default("ingest.syslog.tcp", defaults.tcp),
+            max_message_length: config.get_or_default("ingest.syslog.max_message_bytes", defaults.max_message_length),
+            max_connections: config.get_or_default("ingest.syslog.max_connections", defaults.max_connections),
+            poll_interval: Duration::from_millis(config.get_or_default(
+                "ingest.syslog.poll_interval_ms",
+                defaults.poll_interval.as_millis() as u64,
+            )),
+        }
+    }
+}
+
+///
+/// Take the next complete message from the bytes read on a TCP connection.
+/// Both RFC 6587 framings are accepted: octet counting, where a frame is its
+/// length in decimal, a space and the message, and newline delimited messages.
+/// A frame starting with a digit is octet counted, as syslog messages start
+/// with `<`.
+///
+/// # Arguments
+/// * pending: Bytes read and not yet framed, from which the frame is removed
+/// * max_length: Longest message accepted
+///
+/// # Returns
+/// `Result<Option<Vec<u8>>>`: Next message if a whole one has been read, or `InvalidData` if it is too long
+///
+pub fn next_tcp_frame(pending: &mut Vec<u8>, max_length: usize) -> Result<Option<Vec<u8>>, Error> {
+    if pending.first().is_some_and(|b: &u8| b.is_ascii_digit()) {
+        let space: usize = match pending.iter().position(|b: &u8| !b.is_ascii_digit()) {
+            Some(p) => p,
+            None if pending.len() < 10 => return Ok(None),
+            None => return Err(Error::new(ErrorKind::InvalidData, "Octet count is too long")),
+        };
+        if pending[space] != b' ' {
+            return Err(Error::new(ErrorKind::InvalidData, "Octet count is not followed by a space"));
+        }
+        let length: usize = match String::from_utf8_lossy(&pending[..space]).parse::<usize>() {
+            Ok(l) if l <= max_length => l,
+            _ => return Err(Error::new(ErrorKind::InvalidData, "Octet counted frame is too long")),
+        };
+        if pending.len() < space + 1 + length {
+            return Ok(None);
+        }
+        let frame: Vec<u8> = pending[space + 1..space + 1 + length].to_vec();
+        pending.drain(..space + 1 + length);
+        return Ok(Some(frame));
+    }
+    return match pending.iter().position(|b: &u8| *b == b'\n') {
+        Some(newline) if newline > max_length => Err(Error::new(ErrorKind::InvalidData, "Message is too long")),
+        Some(newline) => {
+            let frame: Vec<u8> = pending[..newline].to_vec();
+            pending.drain(..=newline);
+            Ok(Some(frame))
+        },
+        None if pending.len() > max_length => Err(Error::new(ErrorKind::InvalidData, "Message is too long")),
+        None => Ok(None),
+    };
+}
+
+fn lock(writer: &Mutex<ChunkStoreWriter>) -> Result<MutexGuard<'_, ChunkStoreWriter>, Error> {
+    return writer.lock()
+        .map_err(|_| Error::other("Chunk store writer lock poisoned"));
+}
+
+fn ingest(writer: &Mutex<ChunkStoreWriter>, message: &[u8]) {
+    let text: String = String::from_utf8_lossy(message).into_owned();
+    if text.trim().is_empty() {
+        return;
+    }
+    let received: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    let entry: ChunkEntry = parse_syslog(text.as_str(), received).to_entry(received);
+    if let Err(e) = lock(writer).and_then(|mut w: MutexGuard<ChunkStoreWriter>| w.append(entry)) {
+        warn!(crate::LOGGER, "Unable to store syslog message: {}", e);
+    }
+}
+
+fn is_timeout(e: &Error) -> bool {
+    return e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut;
+}
+
+///
+/// Listener ingesting syslog messages sent over UDP and TCP into a store.
+/// Each message is parsed as RFC 5424 or RFC 3164 and appended through the
+/// shared writer, so it is durable once received and sealed into a chunk by
+/// the writer's limits. A maintenance thread runs the writer's periodic work,
+/// sealing chunks that have aged out. Stopping the listener does not seal the
+/// open chunk.
+///
+pub struct SyslogListener {
+    udp_address: Option<SocketAddr>,
+    tcp_address: Option<SocketAddr>,
+    shutdown: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    maintenance: Option<WriterMaintenance>,
+}
+
+impl SyslogListener {
+    ///
+    /// Bind the enabled listeners and start receiving on background threads.
+    ///
+    /// # Arguments
+    /// * config: Address and limits to listen with
+    /// * writer: Writer of the store to ingest into
+    ///
+    /// # Returns
+    /// `Result<SyslogListener>`: Running listener, or the error binding its sockets
+    ///
+    pub fn start(config: SyslogListenerConfig, writer: Arc<Mutex<ChunkStoreWriter>>) -> Result<SyslogListener, Error> {
+        if !config.udp && !config.tcp {
+            return Err(Error::new(ErrorKind::InvalidInput, "Neither UDP nor TCP syslog is enabled"));
+        }
+        let mut listener: SyslogListener = SyslogListener {
+            udp_address: None,
+            tcp_address: None,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            threads: Vec::new(),
+            maintenance: None,
+        };
+        if config.udp {
+            let socket: UdpSocket = UdpSocket::bind(config.address.as_str())?;
+            socket.set_read_timeout(Some(config.poll_interval))?;
+            listener.udp_address = Some(socket.local_addr()?);
+            let (writer, shutdown, config) = (writer.clone(), listener.shutdown.clone(), config.clone());
+            listener.threads.push(thread::spawn(move || Self::receive_udp(socket, writer, shutdown, config)));
+        }
+        if config.tcp {
+            let tcp: TcpListener = TcpListener::bind(config.address.as_str())?;
+            tcp.set_nonblocking(true)?;
+            listener.tcp_address = Some(tcp.local_addr()?);
+            let (writer, shutdown, config) = (writer.clone(), listener.shutdown.clone(), config.clone());
+            listener.threads.push(thread::spawn(move || Self::accept_tcp(tcp, writer, shutdown, config)));
+        }
+        listener.maintenance = Some(WriterMaintenance::start(writer, config.poll_interval));
+        info!(
+            crate::LOGGER,
+            "Listening for syslog on UDP {:?} and TCP {:?}",
+            listener.udp_address,
+            listener.tcp_address
+        );
+        return Ok(listener);
+    }
+    pub fn udp_address(&self) -> Option<SocketAddr> {
+        self.udp_address
+    }
+    pub fn tcp_address(&self) -> Option<SocketAddr> {
+        self.tcp_address
+    }
+    ///
+    /// Stop listening and wait for every connection to close, each within the
+    /// poll interval.
+    ///
+    pub fn stop(mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        if let Some(maintenance) = self.maintenance.take() {
+            maintenance.stop();
+        }
+    }
+    fn receive_udp(socket: UdpSocket, writer: Arc<Mutex<ChunkStoreWriter>>, shutdown: Arc<AtomicBool>, config: SyslogListenerConfig) {
+        let mut buffer: Vec<u8> = vec![0; config.max_message_length];
+        while !shutdown.load(Ordering::SeqCst) {
+            match socket.recv_from(buffer.as_mut_slice()) {
+                Ok((length, _)) => ingest(&writer, &buffer[..length]),
+                Err(e) if is_timeout(&e) => {},
+                Err(e) => warn!(crate::LOGGER, "Error receiving syslog over UDP: {}", e),
+            }
+        }
+    }
+    fn accept_tcp(tcp: TcpListener, writer: Arc<Mutex<ChunkStoreWriter>>, shutdown: Arc<AtomicBool>, config: SyslogListenerConfig) {
+        let mut connections: Vec<JoinHandle<()>> = Vec::new();
+        while !shutdown.load(Ordering::SeqCst) {
+            connections.retain(|c: &JoinHandle<()>| !c.is_finished());
+            match tcp.accept() {
+                Ok((_, peer)) if connections.len() >= config.max_connections => {
+                    warn!(
+                        crate::LOGGER,
+                        "Refusing syslog connection from {}, {} connections are open",
+                        peer,
+                        connections.len()
+                    );
+                },
+                Ok((stream, peer)) => {
+                    let (writer, shutdown, config) = (writer.clone(), shutdown.clone(), config.clone());
+                    connections.push(thread::spawn(move || {
+                        if let Err(e) = Self::receive_tcp(stream, writer, shutdown, config) {
+                            warn!(crate::LOGGER, "Closing syslog connection from {}: {}", peer, e);
+                        }
+                    }));
+                },
+                Err(e) if is_timeout(&e) => thread::sleep(config.poll_interval),
+                Err(e) => warn!(crate::LOGGER, "Error accepting syslog connection: {}", e),
+            }
+        }
+        for connection in connections.into_iter() {
+            let _ = connection.join();
+        }
+    }
+    fn receive_tcp(mut stream: TcpStream, writer: Arc<Mutex<ChunkStoreWriter>>, shutdown: Arc<AtomicBool>, config: SyslogListenerConfig) -> Result<(), Error> {
+        // Accepted streams inherit non-blocking mode from the listener on some platforms
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(config.poll_interval))?;
+        let mut pending: Vec<u8> = Vec::new();
+        let mut buffer: [u8; 4096] = [0; 4096];
+        while !shutdown.load(Ordering::SeqCst) {
+            let length: usize = match stream.read(&mut buffer) {
+                Ok(l) => l,
+                Err(e) if is_timeout(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if length == 0 {
+                // The last newline delimited message need not end in a newline,
+                // but an octet counted frame cut short is incomplete
+                if pending.first().is_some_and(|b: &u8| b.is_ascii_digit()) {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "Connection closed within an octet counted frame"));
+                }
+                ingest(&writer, pending.as_slice());
+                return Ok(());
+            }
+            pending.extend_from_slice(&buffer[..length]);
+            while let Some(frame) = next_tcp_frame(&mut pending, config.max_message_length)? {
+                ingest(&writer, frame.as_slice());
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Drop for SyslogListener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::data::representational::store::subscription::{Subscription, SubscriptionStart};
+    use crate::utils::test_utils::{temp_store_path, test_writer_config};
+
+    fn start_listener(name: &str, max_connections: usize) -> (SyslogListener, Subscription) {
+        let mut writer: ChunkStoreWriter = ChunkStoreWriter::open(temp_store_path(name).as_str(), test_writer_config(4096)).unwrap();
+        let subscription: Subscription = writer.subscribe("", SubscriptionStart::NOW).unwrap();
+        let config: SyslogListenerConfig = SyslogListenerConfig {
+            address: String::from("127.0.0.1:0"),
+            max_connections,
+            poll_interval: Duration::from_millis(10),
+            ..SyslogListenerConfig::default()
+        };
+        let listener: SyslogListener = SyslogListener::start(config, Arc::new(Mutex::new(writer))).unwrap();
+        return (listener, subscription);
+    }
+
+    fn next_message(subscription: &Subscription) -> String {
+        let entry: ChunkEntry = subscription.receiver.recv_timeout(Duration::from_secs(5)).expect("No message received");
+        return String::from_utf8_lossy(entry.message.as_slice()).into_owned();
+    }
+
+    fn octet_counted(message: &str) -> String {
+        return format!("{} {}", message.len(), message);
+    }
+
+    #[test]
+    fn ingests_udp_and_both_tcp_framings() {
+        let (listener, subscription) = start_listener("ingests_udp_and_both_tcp_framings", 4);
+        let socket: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(b"<34>1 2003-10-11T22:14:15.003Z host app - ID47 - over udp", listener.udp_address().unwrap()).unwrap();
+        assert!(next_message(&subscription).starts_with("over udp"));
+
+        let mut stream: TcpStream = TcpStream::connect(listener.tcp_address().unwrap()).unwrap();
+        stream.write_all(b"<13>1 - host app - - - newline delimited\n").unwrap();
+        stream.write_all(octet_counted("<13>1 - host app - - - octet\ncounted").as_bytes()).unwrap();
+        stream.write_all(b"<13>1 - host app - - - unterminated").unwrap();
+        drop(stream);
+        assert!(next_message(&subscription).starts_with("newline delimited"));
+        assert!(next_message(&subscription).starts_with("octet\ncounted"));
+        assert!(next_message(&subscription).starts_with("unterminated"));
+        listener.stop();
+    }
+
+    #[test]
+    fn rejects_frames_over_max_length() {
+        let mut pending: Vec<u8> = b"<1>ab\n<1>abc\n".to_vec();
+        assert_eq!(next_tcp_frame(&mut pending, 5).unwrap(), Some(b"<1>ab".to_vec()));
+        // Over the limit whether or not its newline arrived with it
+        assert_eq!(next_tcp_frame(&mut pending, 5).unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut pending: Vec<u8> = b"<13>123".to_vec();
+        assert_eq!(next_tcp_frame(&mut pending, 5).unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut pending: Vec<u8> = b"6 <13>12".to_vec();
+        assert_eq!(next_tcp_frame(&mut pending, 5).unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut pending: Vec<u8> = b"5 <13>1".to_vec();
+        assert_eq!(next_tcp_frame(&mut pending, 5).unwrap(), Some(b"<13>1".to_vec()));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn drops_octet_counted_frame_cut_short() {
+        let (listener, subscription) = start_listener("drops_octet_counted_frame_cut_short", 4);
+        let mut stream: TcpStream = TcpStream::connect(listener.tcp_address().unwrap()).unwrap();
+        stream.write_all(b"200 <13>1 - host app - - - cut short").unwrap();
+        drop(stream);
+        let mut stream: TcpStream = TcpStream::connect(listener.tcp_address().unwrap()).unwrap();
+        stream.write_all(b"<13>1 - host app - - - complete\n").unwrap();
+        assert!(next_message(&subscription).starts_with("complete"));
+        drop(stream);
+        listener.stop();
+        assert!(subscription.receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn refuses_connections_beyond_limit() {
+        let (listener, subscription) = start_listener("refuses_connections_beyond_limit", 1);
+        let mut served: TcpStream = TcpStream::connect(listener.tcp_address().unwrap()).unwrap();
+        served.write_all(b"<13>1 - host app - - - first\n").unwrap();
+        assert!(next_message(&subscription).starts_with("first"));
+
+        let mut refused: TcpStream = TcpStream::connect(listener.tcp_address().unwrap()).unwrap();
+        refused.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut buffer: [u8; 16] = [0; 16];
+        assert!(refused.read(&mut buffer).map_or(true, |l: usize| l == 0));
+
+        served.write_all(b"<13>1 - host app - - - second\n").unwrap();
+        assert!(next_message(&subscription).starts_with("second"));
+        drop(served);
+        listener.stop();
+    }
+}
@@ -0,0 +1,345 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{escaped_transform, is_not, take_while1, take_while_m_n};
+use nom::character::complete::{char, digit1, space0, space1};
+use nom::combinator::{map, map_opt, map_res, opt, rest, value, verify};
+use nom::multi::{many0, many1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::utils::datetime_utils::epoch_to_datetime;
+use super::chunk_store_drain::format_message;
+
+///
+/// Syslog message in either the RFC 5424 or the legacy RFC 3164 format.
+/// Header fields sent as the nil value `-`, or absent from the legacy format,
+/// are `None`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogMessage {
+    pub priority: u8,
+    pub timestamp: Option<u64>,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub proc_id: Option<String>,
+    pub msg_id: Option<String>,
+    /// Structured data parameters as `sd-id.param-name` and value
+    pub structured_data: Vec<(String, String)>,
+    pub message: String,
+}
+
+impl SyslogMessage {
+    pub fn facility(&self) -> u8 {
+        self.priority >> 3
+    }
+    pub fn severity(&self) -> u8 {
+        self.priority & 0x07
+    }
+    ///
+    /// Entry for the message: its priority as the action, its app name, or its
+    /// hostname when it has none, as the target, and its message followed by
+    /// the hostname, process and message ids and structured data as fields.
+    ///
+    /// # Arguments
+    /// * received: Time the message was received, used when it carries no timestamp
+    ///
+    /// # Returns
+    /// `ChunkEntry`: Entry to append
+    ///
+    pub fn to_entry(&self, received: u64) -> ChunkEntry {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        if let Some(hostname) = &self.hostname {
+            fields.push((String::from("host"), hostname.clone()));
+        }
+        if let Some(proc_id) = &self.proc_id {
+            fields.push((String::from("procid"), proc_id.clone()));
+        }
+        if let Some(msg_id) = &self.msg_id {
+            fields.push((String::from("msgid"), msg_id.clone()));
+        }
+        fields.extend(self.structured_data.iter().cloned());
+        return ChunkEntry {
+            timestamp: self.timestamp.unwrap_or(received),
+            action: self.priority,
+            target: self.app_name.as_ref()
+                .or(self.hostname.as_ref())
+                .map_or(Vec::new(), |t: &String| t.as_bytes().to_vec()),
+            message: format_message(self.message.as_str(), fields.as_slice()),
+        };
+    }
+}
+
+///
+/// Parse a syslog message, trying RFC 5424 and then RFC 3164. Following RFC
+/// 3164, a message without a priority is given user.notice and one whose
+/// legacy header cannot be read is kept whole as the message, so parsing
+/// never fails.
+///
+/// # Arguments
+/// * input: Message text, without any TCP framing
+/// * received: Time the message was received in milliseconds since the epoch,
+///   supplying the year legacy timestamps omit
+///
+/// # Returns
+/// `SyslogMessage`: Parsed message
+///
+pub fn parse_syslog(input: &str, received: u64) -> SyslogMessage {
+    let input: &str = input.trim_end_matches(['\n', '\r', '\0']);
+    if let Ok((_, message)) = rfc5424_message(input) {
+        return message;
+    }
+    let (header, priority): (&str, u8) = match priority(input) {
+        Ok((rest, p)) => (rest, p),
+        Err(_) => (input, 13),
+    };
+    if let Ok((_, message)) = rfc3164_message(header, priority, received) {
+        return message;
+    }
+    return SyslogMessage {
+        priority,
+        timestamp: None,
+        hostname: None,
+        app_name: None,
+        proc_id: None,
+        msg_id: None,
+        structured_data: Vec::new(),
+        message: String::from(header),
+    };
+}
+
+fn priority(input: &str) -> IResult<&str, u8> {
+    return delimited(
+        char('<'),
+        map_res(
+            verify(take_while_m_n(1, 3, |c: char| c.is_ascii_digit()), |d: &str| d.parse::<u16>().is_ok_and(|p: u16| p <= 191)),
+            |d: &str| d.parse::<u8>(),
+        ),
+        char('>'),
+    )(input);
+}
+
+fn nil_or<'a, F>(field: F) -> impl FnMut(&'a str) -> IResult<&'a str, Option<String>>
+    where F: FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    return alt((
+        value(None, terminated(char('-'), char(' '))),
+        map(terminated(field, char(' ')), |f: &str| Some(String::from(f))),
+    ));
+}
+
+fn header_field(input: &str) -> IResult<&str, &str> {
+    return take_while1(|c: char| c.is_ascii_graphic())(input);
+}
+
+fn rfc5424_timestamp(input: &str) -> IResult<&str, &str> {
+    return verify(header_field, |t: &str| DateTime::parse_from_rfc3339(t).is_ok())(input);
+}
+
+fn sd_name(input: &str) -> IResult<&str, &str> {
+    return take_while1(|c: char| c.is_ascii_graphic() && c != '=' && c != ']' && c != '"')(input);
+}
+
+fn sd_value(input: &str) -> IResult<&str, String> {
+    return delimited(
+        char('"'),
+        map(
+            opt(escaped_transform(
+                is_not("\\\""),
+                '\\',
+                alt((value("\"", char('"')), value("\\", char('\\')), value("]", char(']')))),
+            )),
+            |v: Option<String>| v.unwrap_or_default(),
+        ),
+        char('"'),
+    )(input);
+}
+
+fn sd_element(input: &str) -> IResult<&str, Vec<(String, String)>> {
+    let (input, (id, parameters)) = delimited(
+        char('['),
+        pair(sd_name, many0(preceded(char(' '), tuple((sd_name, char('='), sd_value))))),
+        char(']'),
+    )(input)?;
+    return Ok((input, parameters.into_iter()
+        .map(|(name, _, v): (&str, char, String)| (format!("{}.{}", id, name), v))
+        .collect()));
+}
+
+fn structured_data(input: &str) -> IResult<&str, Vec<(String, String)>> {
+    return alt((
+        value(Vec::new(), char('-')),
+        map(many1(sd_element), |elements: Vec<Vec<(String, String)>>| elements.concat()),
+    ))(input);
+}
+
+///
+/// `<PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`
+///
+fn rfc5424_message(input: &str) -> IResult<&str, SyslogMessage> {
+    let (input, priority) = priority(input)?;
+    let (input, _) = terminated(verify(digit1, |v: &str| v != "0"), char(' '))(input)?;
+    let (input, timestamp) = nil_or(rfc5424_timestamp)(input)?;
+    let (input, hostname) = nil_or(header_field)(input)?;
+    let (input, app_name) = nil_or(header_field)(input)?;
+    let (input, proc_id) = nil_or(header_field)(input)?;
+    let (input, msg_id) = nil_or(header_field)(input)?;
+    let (input, structured_data) = structured_data(input)?;
+    let (input, message) = alt((preceded(char(' '), rest), verify(rest, |r: &str| r.is_empty())))(input)?;
+    return Ok((input, SyslogMessage {
+        priority,
+        timestamp: timestamp
+            .and_then(|t: String| DateTime::parse_from_rfc3339(t.as_str()).ok())
+            .map(|t| t.timestamp_millis().max(0) as u64),
+        hostname,
+        app_name,
+        proc_id,
+        msg_id,
+        structured_data,
+        message: String::from(message.trim_start_matches('\u{feff}')),
+    }));
+}
+
+///
+/// `Mmm dd hh:mm:ss`, with the day padded by a space rather than a zero.
+/// The year is taken to be the one the message was received in, or the one
+/// before for a time more than a day ahead of it.
+///
+fn rfc3164_timestamp(received: u64) -> impl FnMut(&str) -> IResult<&str, u64> {
+    return move |input: &str| {
+        let (input, (month, _, day, _, hour, _, minute, _, second)) = tuple((
+            map_opt(take_while_m_n(3, 3, |c: char| c.is_ascii_alphabetic()), |m: &str| {
+                ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+                    .iter()
+                    .position(|name: &&str| *name == m)
+                    .map(|p: usize| p as u32 + 1)
+            }),
+            space1,
+            map_res(digit1, |d: &str| d.parse::<u32>()),
+            char(' '),
+            map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), |d: &str| d.parse::<u32>()),
+            char(':'),
+            map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), |d: &str| d.parse::<u32>()),
+            char(':'),
+            map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_digit()), |d: &str| d.parse::<u32>()),
+        ))(input)?;
+        let received_year: i32 = epoch_to_datetime(received).year();
+        let timestamp = |year: i32| NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|d: NaiveDate| d.and_hms_opt(hour, minute, second))
+            .map(|t: NaiveDateTime| Utc.from_utc_datetime(&t).timestamp_millis().max(0) as u64);
+        let millis: u64 = match timestamp(received_year) {
+            Some(t) if t > received + 24 * 60 * 60 * 1000 => match timestamp(received_year - 1) {
+                Some(previous) => previous,
+                None => return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify))),
+            },
+            Some(t) => t,
+            None => return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify))),
+        };
+        return Ok((input, millis));
+    };
+}
+
+///
+/// `TIMESTAMP HOSTNAME TAG[PID]: MSG`, following the priority.
+///
+fn rfc3164_message(input: &str, priority: u8, received: u64) -> IResult<&str, SyslogMessage> {
+    let (input, timestamp) = terminated(rfc3164_timestamp(received), space1)(input)?;
+    let (input, hostname) = terminated(header_field, char(' '))(input)?;
+    let (input, app_name) = take_while1(|c: char| c.is_ascii_graphic() && c != '[' && c != ':')(input)?;
+    let (input, proc_id) = opt(delimited(char('['), is_not("]"), char(']')))(input)?;
+    let (input, _) = pair(char(':'), space0)(input)?;
+    let (input, message) = rest(input)?;
+    return Ok((input, SyslogMessage {
+        priority,
+        timestamp: Some(timestamp),
+        hostname: Some(String::from(hostname)),
+        app_name: Some(String::from(app_name)),
+        proc_id: proc_id.map(String::from),
+        msg_id: None,
+        structured_data: Vec::new(),
+        message: String::from(message),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(text: &str) -> u64 {
+        return DateTime::parse_from_rfc3339(text).unwrap().timestamp_millis() as u64;
+    }
+
+    #[test]
+    fn parses_rfc5424_with_structured_data() {
+        let message: SyslogMessage = parse_syslog(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+             [exampleSDID@32473 iut=\"3\" eventSource=\"Appl\\\"ication\"][examplePriority@32473 class=\"high\"] \u{feff}An application event\n",
+            0,
+        );
+        assert_eq!(message.priority, 165);
+        assert_eq!((message.facility(), message.severity()), (20, 5));
+        assert_eq!(message.timestamp, Some(millis("2003-10-11T22:14:15.003Z")));
+        assert_eq!(message.hostname.as_deref(), Some("mymachine.example.com"));
+        assert_eq!(message.app_name.as_deref(), Some("evntslog"));
+        assert_eq!(message.proc_id, None);
+        assert_eq!(message.msg_id.as_deref(), Some("ID47"));
+        assert_eq!(message.structured_data, vec![
+            (String::from("exampleSDID@32473.iut"), String::from("3")),
+            (String::from("exampleSDID@32473.eventSource"), String::from("Appl\"ication")),
+            (String::from("examplePriority@32473.class"), String::from("high")),
+        ]);
+        assert_eq!(message.message, "An application event");
+    }
+
+    #[test]
+    fn parses_rfc5424_without_message() {
+        let message: SyslogMessage = parse_syslog("<14>1 - - - - - -", 0);
+        assert_eq!(message.priority, 14);
+        assert_eq!(message.timestamp, None);
+        assert_eq!(message.hostname, None);
+        assert!(message.structured_data.is_empty());
+        assert_eq!(message.message, "");
+    }
+
+    #[test]
+    fn parses_rfc3164() {
+        let received: u64 = millis("2023-10-12T00:00:00Z");
+        let message: SyslogMessage = parse_syslog("<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed on /dev/pts/8", received);
+        assert_eq!(message.priority, 34);
+        assert_eq!(message.timestamp, Some(millis("2023-10-11T22:14:15Z")));
+        assert_eq!(message.hostname.as_deref(), Some("mymachine"));
+        assert_eq!(message.app_name.as_deref(), Some("su"));
+        assert_eq!(message.proc_id.as_deref(), Some("123"));
+        assert_eq!(message.message, "'su root' failed on /dev/pts/8");
+    }
+
+    #[test]
+    fn dates_rfc3164_ahead_of_receipt_in_previous_year() {
+        let received: u64 = millis("2024-01-01T00:00:10Z");
+        let message: SyslogMessage = parse_syslog("<13>Dec 31 23:59:59 host app: late", received);
+        assert_eq!(message.timestamp, Some(millis("2023-12-31T23:59:59Z")));
+        let message: SyslogMessage = parse_syslog("<13>Feb  3 04:05:06 host app: padded day", received);
+        assert_eq!(message.timestamp, Some(millis("2023-02-03T04:05:06Z")));
+    }
+
+    #[test]
+    fn keeps_invalid_rfc3164_dates_whole() {
+        let leap: SyslogMessage = parse_syslog("<13>Feb 29 12:00:00 host app: leap day", millis("2024-03-01T00:00:00Z"));
+        assert_eq!(leap.timestamp, Some(millis("2024-02-29T12:00:00Z")));
+        for text in ["<13>Feb 29 12:00:00 host app: leap day", "<13>Apr 31 12:00:00 host app: no such day"] {
+            let message: SyslogMessage = parse_syslog(text, millis("2023-06-01T00:00:00Z"));
+            assert_eq!(message.priority, 13);
+            assert_eq!(message.timestamp, None);
+            assert_eq!(message.app_name, None);
+            assert_eq!(message.message, &text[4..]);
+        }
+    }
+
+    #[test]
+    fn defaults_missing_priority_to_user_notice() {
+        let message: SyslogMessage = parse_syslog("plain text", 0);
+        assert_eq!(message.priority, 13);
+        assert_eq!(message.message, "plain text");
+        let message: SyslogMessage = parse_syslog("<192>1 - - - - - - out of range", 0);
+        assert_eq!(message.priority, 13);
+        assert_eq!(message.message, "<192>1 - - - - - - out of range");
+    }
+}
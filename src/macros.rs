@@ -41,7 +41,7 @@ macro_rules! try_except_return {
         match $connection_statement {
             Ok(value) => value,
             Err(e) => {
-                error!(crate::LOGGER, "{}: {}", $msg, e);
+                error!($crate::LOGGER, "{}: {}", $msg, e);
                 return;
             },
         }
@@ -54,7 +54,7 @@ macro_rules! try_except_return_default {
         match $connection_statement {
             Ok(value) => value,
             Err(e) => {
-                error!(crate::LOGGER, "{}: {}", $msg, e);
+                error!($crate::LOGGER, "{}: {}", $msg, e);
                 $default_value
             },
         }
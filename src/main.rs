@@ -1,153 +1,90 @@
-mod compression;
-mod data;
-mod cache;
-mod logging;
-mod configuration;
-mod macros;
-mod encoding;
-mod compiler;
-mod utils;
-
 #[macro_use]
 extern crate slog;
-extern crate slog_term;
-extern crate slog_async;
-extern crate slog_json;
-extern crate lazy_static;
-extern crate regex;
-extern crate chrono;
-extern crate core;
+extern crate chunky_logs;
 
 use std::fs::File;
 use std::io;
-use std::io::{Error, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use lazy_static::lazy_static;
-use slog::Logger;
-use crate::compiler::byte_unpack::ToVec;
 use lipsum::lipsum;
 use rand::Rng;
 
-use crate::compression::compressor::Compressor;
-use crate::logging::logging::initialize_logging;
-use crate::configuration::config::Config;
-use crate::data::representational::chunk::Chunk;
-use crate::data::representational::chunk_entry::ChunkEntry;
-use crate::data::representational::store::chunk_offsets::ChunkOffsets;
-use crate::data::representational::store::chunk_store::ChunkStore;
-use crate::data::representational::store::chunk_store_header::ChunkStoreHeader;
-
-type Byte = u8;
+use chunky_logs::LOGGER;
+use chunky_logs::try_except_return;
+use chunky_logs::cache::write_ahead_log::WriteAheadLog;
+use chunky_logs::configuration::config::Config;
+use chunky_logs::data::representational::chunk_entry::ChunkEntry;
+use chunky_logs::data::representational::store::chunk_store::ChunkStore;
+use chunky_logs::data::representational::store::chunk_store_writer::{ChunkStoreWriter, ChunkStoreWriterConfig};
+use chunky_logs::logging::syslog_listener::{SyslogListener, SyslogListenerConfig};
 
-lazy_static! {
-    static ref LOGGER: Logger = initialize_logging(String::from("chunky_logs_"));
-}
+const CONFIG_PATH: &str = "config/config.properties";
+const SAMPLE_STORE_PATH: &str = "data/scratch/chunk_store.bin";
 
-macro_rules! print_chunk_data {
-    ($c:expr) => {
-        info!(
-            &crate::LOGGER,
-            "Chunk data: [TS from: {}] [TS to: {}] [Length: {}] [State: {:?}] [Data: {:02X?}]",
-            $c.ts_from,
-            $c.ts_to,
-            $c.length,
-            $c.state,
-            $c.data.as_slice(),
-        )
-    }
-}
-
-fn create_test_bytes() {
+fn create_test_bytes(store_path: &str, writer_config: ChunkStoreWriterConfig) {
     let mut rng = rand::thread_rng();
-    let mut bytes: Vec<Byte> = Vec::new();
+    let _ = std::fs::remove_file(store_path);
+    let _ = std::fs::remove_file(WriteAheadLog::path_for_store(store_path));
 
-    let chunk_store_header: ChunkStoreHeader = ChunkStoreHeader{
-        length: 0,
-        sector_size: 30,
-        chunk_count: 2,
-        chunk_offsets_length: 0,
-        chunk_offsets: Vec::new(),
-    };
+    let mut writer: ChunkStoreWriter = try_except_return!(
+        ChunkStoreWriter::open(store_path, writer_config),
+        "Unable to open chunk store writer"
+    );
 
-    let mut chunk: Chunk = Chunk::default();
     let mut chunk_entry: ChunkEntry = ChunkEntry::default();
-    chunk_entry.target.push(0x00);
-    chunk_entry.message.push(0x00);
-    for i in 0..2 {
+    for i in 0..4 {
         chunk_entry.timestamp = i as u64;
         chunk_entry.action = i as u8;
-        chunk_entry.target = lipsum::lipsum(rng.gen_range(1..5)).as_bytes().to_vec();
+        chunk_entry.target = lipsum(rng.gen_range(1..5)).as_bytes().to_vec();
         chunk_entry.message = lipsum::lipsum_words(rng.gen_range(10..20)).as_bytes().to_vec();
-        chunk.entries.append(&mut chunk_entry.into_bytes());
-        chunk.entries_length += 1;
-    }
-    chunk.timestamp_from = 0;
-    chunk.timestamp_to = 1;
-
-    let mut compressor: Compressor = Compressor::new();
-    match compressor.compress_vec(&chunk.entries) {
-        Ok(b) => chunk.entries = b,
-        Err(e) => {
-            error!(crate::LOGGER, "An error occurred: {}", e.message);
-            return;
-        }
+        try_except_return!(writer.append(chunk_entry.clone()), "Error while writing to store");
     }
-    chunk.length = chunk.into_bytes().len() as u32;
+    try_except_return!(writer.flush(), "Error while flushing store");
+}
 
-    let mut chunk_store: ChunkStore = ChunkStore::default();
-    chunk_store.header = chunk_store_header;
-    let mut running_length: u32 = 0;
-    for _ in 0..chunk_store.header.chunk_count {
-        let chunk_offset: ChunkOffsets = ChunkOffsets{
-            sector_index: running_length / chunk_store.header.sector_size as u32,
-            sector_offset: running_length as u16 % chunk_store.header.sector_size as u16,
-        };
-        chunk_store.header.chunk_offsets.push(chunk_offset);
-        chunk_store.header.chunk_offsets_length += 1;
-        chunk_store.chunks.push(chunk.clone());
-        chunk_store.chunks_length += 1;
-        running_length += chunk.length;
-    }
-    chunk_store.header.length = chunk_store.header.into_bytes().len() as u64;
-
-    bytes.append(chunk_store.header.into_bytes().to_vec().as_mut());
-
-    let file: io::Result<File> = File::create("data/chunk_store.bin");
-    if file.is_ok() {
-        match file.unwrap().write_all(chunk_store.into_bytes().as_slice()) {
-            Ok(_) => {},
-            Err(e) => {
-                error!(crate::LOGGER, "Error while writing to file: {}", e.to_string());
-                return;
-            }
-        }
+fn listen_for_syslog(store_path: &str, writer_config: ChunkStoreWriterConfig, listener_config: SyslogListenerConfig) {
+    let writer: ChunkStoreWriter = try_except_return!(
+        ChunkStoreWriter::open(store_path, writer_config),
+        "Unable to open chunk store writer"
+    );
+    let _listener: SyslogListener = try_except_return!(
+        SyslogListener::start(listener_config, Arc::new(Mutex::new(writer))),
+        "Unable to start syslog listener"
+    );
+    loop {
+        std::thread::park();
     }
 }
 
 fn main() {
-    info!(&crate::LOGGER, "Configured logging");
-    // let mut properties: Config = Config::new("config/config.properties");
-    // properties.read();
+    info!(LOGGER, "Configured logging");
+    let mut properties: Config = Config::new(CONFIG_PATH);
+    properties.read();
+    let writer_config: ChunkStoreWriterConfig = ChunkStoreWriterConfig::from_config(&mut properties);
+    let store_path: String = properties.get_or_default("store.path", String::from(SAMPLE_STORE_PATH));
+    if let Some(directory) = Path::new(store_path.as_str()).parent() {
+        try_except_return!(std::fs::create_dir_all(directory), "Unable to create store directory");
+    }
 
-    create_test_bytes();
-    let mut chunk_store: ChunkStore = ChunkStore::default();
-    let mut file: io::Result<File> = File::open("data/chunk_store.bin");
+    if properties.get_or_default("ingest.syslog.enabled", false) {
+        listen_for_syslog(store_path.as_str(), writer_config, SyslogListenerConfig::from_config(&mut properties));
+        return;
+    }
+    create_test_bytes(store_path.as_str(), writer_config);
+    let file: io::Result<File> = File::open(store_path.as_str());
     if file.is_ok() {
-        match ChunkStore::read_from_file("data/chunk_store.bin") {
-            Ok(cs) => {
-                chunk_store = cs;
-                info!(crate::LOGGER, "Header: {:?}", chunk_store);
+        match ChunkStore::read_from_file(store_path.as_str()) {
+            Ok(chunk_store) => {
+                info!(LOGGER, "Header: {:?}", chunk_store.header);
+                match chunk_store.string_format_inspection() {
+                    Ok(report) => info!(LOGGER, "Chunks:\n{}", report),
+                    Err(e) => error!(LOGGER, "Unable to inspect chunks: {}", e),
+                }
             },
-            Err(e) => error!(crate::LOGGER, "An error occurred: {}", e.to_string()),
+            Err(e) => error!(LOGGER, "An error occurred: {}", e.to_string()),
         }
     }
-    // file = File::open("data/chunk_store.bin");
-    // if file.is_ok() {
-    //     match chunk_store.header.string_format_chunk_sector_ratio(&file.unwrap()) {
-    //         Ok(s) => info!(crate::LOGGER, "{}", s),
-    //         Err(e) => error!(crate::LOGGER, "An error occurred: {}", e.to_string()),
-    //     };
-    // }
 
     std::thread::sleep(Duration::from_millis(1000));
-}
\ No newline at end of file
+}
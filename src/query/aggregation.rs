@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_entry_index::{ChunkEntryIndex, ChunkEntryIndexEntry};
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::chunk_summary::ChunkSummary;
+use super::query::{Aggregation, AggregationGroup};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SeriesKey {
+    ALL,
+    ACTION(u8),
+    TARGET(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct AggregationSeries {
+    pub key: SeriesKey,
+    pub total: u64,
+    /// Bucket start time and count, in time order, omitting empty buckets. Empty without buckets.
+    pub points: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AggregationResult {
+    pub bucket: Option<u64>,
+    /// Series with the largest total first
+    pub series: Vec<AggregationSeries>,
+}
+
+///
+/// Running counts of an aggregation, fed one entry or one chunk summary at a
+/// time. Memory grows with the number of series and non-empty buckets, never
+/// with the number of entries.
+///
+pub struct Aggregator {
+    aggregation: Aggregation,
+    counts: HashMap<SeriesKey, BTreeMap<u64, u64>>,
+    matched: u64,
+}
+
+impl Aggregator {
+    pub fn new(aggregation: Aggregation) -> Aggregator {
+        return Aggregator {
+            aggregation,
+            counts: HashMap::new(),
+            matched: 0,
+        };
+    }
+    pub fn matched(&self) -> u64 {
+        self.matched
+    }
+    fn bucket_of(&self, timestamp: u64) -> u64 {
+        return match self.aggregation.bucket {
+            Some(width) if width > 0 => timestamp - timestamp % width,
+            _ => 0,
+        };
+    }
+    pub fn add(&mut self, key: SeriesKey, timestamp: u64, count: u64) {
+        let bucket: u64 = self.bucket_of(timestamp);
+        *self.counts.entry(key)
+            .or_default()
+            .entry(bucket)
+            .or_insert(0) += count;
+        self.matched += count;
+    }
+    pub fn add_entry(&mut self, entry: &ChunkEntry) {
+        let key: SeriesKey = match self.aggregation.group {
+            AggregationGroup::NONE => SeriesKey::ALL,
+            AggregationGroup::ACTION => SeriesKey::ACTION(entry.action),
+            AggregationGroup::TARGET => SeriesKey::TARGET(entry.target.clone()),
+        };
+        self.add(key, entry.timestamp, 1);
+    }
+    ///
+    /// Count a chunk from its metadata sections alone, for when every entry of
+    /// the chunk within the time range matches. A chunk lying wholly inside the
+    /// range and a single bucket is counted from its summary, and otherwise
+    /// ungrouped counts fall back to the timestamps in its entry index.
+    ///
+    /// # Arguments
+    /// * timestamp_from: Earliest entry timestamp of the chunk
+    /// * timestamp_to: Latest entry timestamp of the chunk
+    /// * sections: Metadata sections of the chunk
+    /// * from: Start of the time range, inclusive
+    /// * to: End of the time range, inclusive
+    ///
+    /// # Returns
+    /// `bool`: Whether the chunk was counted, `false` if its entries must be read instead
+    ///
+    pub fn add_summarised(&mut self, timestamp_from: u64, timestamp_to: u64, sections: &[ChunkMetadataSection], from: u64, to: u64) -> bool {
+        if timestamp_from >= from && timestamp_to <= to && self.bucket_of(timestamp_from) == self.bucket_of(timestamp_to) {
+            if let Some(summary) = sections.iter().find_map(ChunkSummary::from_section) {
+                match self.aggregation.group {
+                    AggregationGroup::NONE => {
+                        self.add(SeriesKey::ALL, timestamp_from, summary.entry_count as u64);
+                        return true;
+                    },
+                    AggregationGroup::ACTION => {
+                        for action in summary.actions.iter() {
+                            self.add(SeriesKey::ACTION(action.action), timestamp_from, action.count as u64);
+                        }
+                        return true;
+                    },
+                    // The summary only estimates how many targets there are
+                    AggregationGroup::TARGET => {},
+                }
+            }
+        }
+        if self.aggregation.group != AggregationGroup::NONE {
+            return false;
+        }
+        let index: ChunkEntryIndex = match sections.iter().find_map(ChunkEntryIndex::from_section) {
+            Some(i) => i,
+            None => return false,
+        };
+        for entry in index.entries.iter().filter(|e: &&ChunkEntryIndexEntry| e.timestamp >= from && e.timestamp <= to) {
+            self.add(SeriesKey::ALL, entry.timestamp, 1);
+        }
+        return true;
+    }
+    pub fn finish(self) -> AggregationResult {
+        let bucketed: bool = self.aggregation.bucket.is_some();
+        let mut counts: HashMap<SeriesKey, BTreeMap<u64, u64>> = self.counts;
+        // A plain count is still a count when nothing matched
+        if self.aggregation.group == AggregationGroup::NONE {
+            counts.entry(SeriesKey::ALL).or_default();
+        }
+        let mut series: Vec<AggregationSeries> = counts.into_iter()
+            .map(|(key, buckets): (SeriesKey, BTreeMap<u64, u64>)| AggregationSeries {
+                key,
+                total: buckets.values().sum(),
+                points: if bucketed { buckets.into_iter().collect() } else { Vec::new() },
+            })
+            .collect();
+        series.sort_by(|a: &AggregationSeries, b: &AggregationSeries| b.total.cmp(&a.total).then_with(|| a.key.cmp(&b.key)));
+        return AggregationResult {
+            bucket: self.aggregation.bucket,
+            series,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk::Chunk;
+    use crate::utils::test_utils::entry_at;
+
+    ///
+    /// Sealed chunk whose entries cycle through three actions and two targets.
+    ///
+    fn varied_chunk_of(timestamps: &[u64]) -> Chunk {
+        let entries: Vec<ChunkEntry> = timestamps.iter()
+            .map(|timestamp: &u64| ChunkEntry {
+                action: (*timestamp % 3) as u8,
+                target: format!("target-{}", timestamp % 2).into_bytes(),
+                ..entry_at(*timestamp)
+            })
+            .collect();
+        return Chunk::seal(entries.as_slice()).ok().expect("Could not seal chunk");
+    }
+
+    /// Key, total and points of a series
+    type Series = (SeriesKey, u64, Vec<(u64, u64)>);
+
+    fn series(result: AggregationResult) -> Vec<Series> {
+        return result.series.into_iter()
+            .map(|s: AggregationSeries| (s.key, s.total, s.points))
+            .collect();
+    }
+
+    ///
+    /// Counts of the chunk from its metadata, if it could be counted that way,
+    /// beside the counts from decoding every entry.
+    ///
+    fn summarised_and_decoded(chunk: &Chunk, aggregation: Aggregation, from: u64, to: u64) -> (Option<AggregationResult>, AggregationResult) {
+        let mut summarised: Aggregator = Aggregator::new(aggregation);
+        let counted: bool = summarised.add_summarised(chunk.timestamp_from, chunk.timestamp_to, chunk.sections.as_slice(), from, to);
+        let mut decoded: Aggregator = Aggregator::new(aggregation);
+        for entry in chunk.decode_entries().ok().expect("Could not decode entries").iter() {
+            if entry.timestamp >= from && entry.timestamp <= to {
+                decoded.add_entry(entry);
+            }
+        }
+        return (if counted { Some(summarised.finish()) } else { None }, decoded.finish());
+    }
+
+    fn assert_summarised_matches_decoded(chunk: &Chunk, aggregation: Aggregation, from: u64, to: u64) {
+        let (summarised, decoded): (Option<AggregationResult>, AggregationResult) = summarised_and_decoded(chunk, aggregation, from, to);
+        assert_eq!(series(summarised.expect("Chunk was not counted from its metadata")), series(decoded));
+    }
+
+    #[test]
+    fn counts_chunk_inside_range_from_summary() {
+        let chunk: Chunk = varied_chunk_of(&[100, 110, 120, 130, 140, 150, 160]);
+        let count: Aggregation = Aggregation { group: AggregationGroup::NONE, bucket: None };
+        assert_summarised_matches_decoded(&chunk, count, 0, 1000);
+        assert_summarised_matches_decoded(&chunk, count, 100, 160);
+        // A single bucket holding the whole chunk is answered from the summary too
+        assert_summarised_matches_decoded(&chunk, Aggregation { group: AggregationGroup::NONE, bucket: Some(1000) }, 0, 1000);
+    }
+
+    #[test]
+    fn counts_chunk_straddling_range_from_entry_index() {
+        let chunk: Chunk = varied_chunk_of(&[100, 110, 120, 130, 140, 150, 160]);
+        let count: Aggregation = Aggregation { group: AggregationGroup::NONE, bucket: None };
+        assert_summarised_matches_decoded(&chunk, count, 125, 1000);
+        assert_summarised_matches_decoded(&chunk, count, 0, 130);
+        assert_summarised_matches_decoded(&chunk, count, 111, 119);
+        let (summarised, _): (Option<AggregationResult>, AggregationResult) = summarised_and_decoded(&chunk, count, 125, 1000);
+        assert_eq!(summarised.unwrap().series[0].total, 4);
+    }
+
+    #[test]
+    fn counts_chunk_spanning_buckets_from_entry_index() {
+        let chunk: Chunk = varied_chunk_of(&[95, 100, 105, 150, 199, 200, 250, 310]);
+        let histogram: Aggregation = Aggregation { group: AggregationGroup::NONE, bucket: Some(100) };
+        assert_summarised_matches_decoded(&chunk, histogram, 0, 1000);
+        assert_summarised_matches_decoded(&chunk, histogram, 100, 250);
+        let (summarised, _): (Option<AggregationResult>, AggregationResult) = summarised_and_decoded(&chunk, histogram, 0, 1000);
+        assert_eq!(summarised.unwrap().series[0].points, vec![(0, 1), (100, 4), (200, 2), (300, 1)]);
+    }
+
+    #[test]
+    fn counts_by_action_from_summary_only_inside_range() {
+        let chunk: Chunk = varied_chunk_of(&[100, 101, 102, 103, 104, 105, 106, 107, 108, 109]);
+        let by_action: Aggregation = Aggregation { group: AggregationGroup::ACTION, bucket: None };
+        assert_summarised_matches_decoded(&chunk, by_action, 0, 1000);
+        let (summarised, _): (Option<AggregationResult>, AggregationResult) = summarised_and_decoded(&chunk, by_action, 0, 1000);
+        assert_eq!(
+            series(summarised.unwrap()),
+            vec![
+                (SeriesKey::ACTION(1), 4, Vec::new()),
+                (SeriesKey::ACTION(0), 3, Vec::new()),
+                (SeriesKey::ACTION(2), 3, Vec::new()),
+            ],
+        );
+        // Grouped counts of a chunk straddling the range or spanning buckets need its entries
+        assert!(summarised_and_decoded(&chunk, by_action, 105, 1000).0.is_none());
+        assert!(summarised_and_decoded(&chunk, Aggregation { group: AggregationGroup::ACTION, bucket: Some(5) }, 0, 1000).0.is_none());
+        assert!(summarised_and_decoded(&chunk, Aggregation { group: AggregationGroup::TARGET, bucket: None }, 0, 1000).0.is_none());
+    }
+
+    #[test]
+    fn reads_entries_of_chunk_without_metadata() {
+        let mut chunk: Chunk = varied_chunk_of(&[100, 110, 120]);
+        chunk.sections.clear();
+        let count: Aggregation = Aggregation { group: AggregationGroup::NONE, bucket: None };
+        assert!(summarised_and_decoded(&chunk, count, 0, 1000).0.is_none());
+        assert!(summarised_and_decoded(&chunk, count, 105, 1000).0.is_none());
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::Value;
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+///
+/// Resolve a named field of an entry for comparison. `timestamp`, `action`,
+/// `target` and `message` are the entry's own fields. Any other name is looked
+/// up in the message: as a dotted path into a JSON object message, or
+/// otherwise among the `name=value` pairs trailing it, as `split_message`
+/// reads them.
+///
+/// # Arguments
+/// * entry: Entry to read the field from
+/// * name: Field name
+///
+/// # Returns
+/// `Option<String>`: Field value as text, `None` if the entry has no such field
+///
+pub fn field_value(entry: &ChunkEntry, name: &str) -> Option<String> {
+    return match name {
+        "timestamp" => Some(entry.timestamp.to_string()),
+        "action" => Some(entry.action.to_string()),
+        "target" => Some(String::from_utf8_lossy(entry.target.as_slice()).into_owned()),
+        "message" => Some(String::from_utf8_lossy(entry.message.as_slice()).into_owned()),
+        _ => structured_field(entry.message.as_slice(), name),
+    };
+}
+
+pub fn structured_field(message: &[u8], name: &str) -> Option<String> {
+    let text: String = String::from_utf8_lossy(message).into_owned();
+    if text.trim_start().starts_with('{') {
+        if let Ok(json) = serde_json::from_str::<Value>(text.as_str()) {
+            return json_field(&json, name);
+        }
+    }
+    return key_value_field(text.as_str(), name);
+}
+
+fn json_field(json: &Value, name: &str) -> Option<String> {
+    let mut current: &Value = json;
+    for part in name.split('.') {
+        current = current.get(part)?;
+    }
+    return match current {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    };
+}
+
+fn key_value_field(text: &str, name: &str) -> Option<String> {
+    return split_message(text).1.into_iter()
+        .find(|(key, _): &(String, String)| key == name)
+        .map(|(_, value): (String, String)| value);
+}
+
+///
+/// Inverse of `format_message`: split an entry message into the record message
+/// and the `key=value` pairs trailing it. A message without trailing pairs is
+/// returned whole with no fields.
+///
+/// # Arguments
+/// * message: Entry message text
+///
+/// # Returns
+/// `(String, Vec<(String, String)>)`: Record message and fields in the order written
+///
+pub fn split_message(message: &str) -> (String, Vec<(String, String)>) {
+    let mut start: usize = 0;
+    loop {
+        if let Some(fields) = parse_fields(&message[start..]) {
+            return (String::from(message[..start].trim_end()), fields);
+        }
+        match message[start..].find(' ') {
+            Some(space) => start += space + 1,
+            None => return (String::from(message), Vec::new()),
+        }
+    }
+}
+
+///
+/// Parse text made up entirely of space separated `key=value` pairs.
+///
+fn parse_fields(text: &str) -> Option<Vec<(String, String)>> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut rest: &str = text;
+    while !rest.is_empty() {
+        let equals: usize = rest.find('=')?;
+        let key: &str = &rest[..equals];
+        if key.is_empty() || key.chars().any(|c: char| c.is_whitespace() || c == '"') {
+            return None;
+        }
+        let mut value: String = String::new();
+        let mut chars = rest[equals + 1..].char_indices();
+        let consumed: usize = if rest[equals + 1..].starts_with('"') {
+            chars.next();
+            let mut end: Option<usize> = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        end = Some(i + 1);
+                        break;
+                    },
+                    '\\' => value.push(chars.next()?.1),
+                    _ => value.push(c),
+                }
+            }
+            end?
+        } else {
+            let unquoted: &str = &rest[equals + 1..];
+            let end: usize = unquoted.find(char::is_whitespace).unwrap_or(unquoted.len());
+            value.push_str(&unquoted[..end]);
+            end
+        };
+        fields.push((String::from(key), value));
+        rest = &rest[equals + 1 + consumed..];
+        if !rest.is_empty() {
+            rest = rest.strip_prefix(' ')?;
+        }
+    }
+    if fields.is_empty() {
+        return None;
+    }
+    return Some(fields);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::chunk_store_drain::format_message;
+    use crate::utils::test_utils::entry_at;
+
+    #[test]
+    fn field_lookup_reads_the_pairs_export_splits() {
+        let fields: Vec<(String, String)> = vec![
+            (String::from("path"), String::from("/var/log/a b")),
+            (String::from("quote"), String::from("say \"hi\" \\ bye")),
+            (String::from("status"), String::from("500")),
+        ];
+        let entry: ChunkEntry = ChunkEntry {
+            message: format_message("request user=bob failed", fields.as_slice()),
+            ..entry_at(1)
+        };
+        let text: String = String::from_utf8(entry.message.clone()).unwrap();
+        let (message, split): (String, Vec<(String, String)>) = split_message(text.as_str());
+        assert_eq!(message, "request user=bob failed");
+        assert_eq!(split, fields);
+        for (key, value) in fields.iter() {
+            assert_eq!(field_value(&entry, key.as_str()), Some(value.clone()));
+        }
+        // Pairs inside the record message are not fields
+        assert_eq!(field_value(&entry, "user"), None);
+        assert_eq!(field_value(&entry, "target"), Some(String::from("target")));
+        assert_eq!(structured_field(br#"{"http":{"status":404}}"#, "http.status"), Some(String::from("404")));
+    }
+}
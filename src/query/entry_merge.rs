@@ -0,0 +1,119 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::io::Error;
+use crate::data::representational::chunk_entry::ChunkEntry;
+
+///
+/// Entries of one opened chunk, sorted, consumed from the front.
+///
+struct Run {
+    rank: usize,
+    sequence: usize,
+    entries: std::vec::IntoIter<ChunkEntry>,
+    head: ChunkEntry,
+}
+
+impl Run {
+    fn key(&self) -> (u64, usize, usize) {
+        (self.head.timestamp, self.rank, self.sequence)
+    }
+}
+
+impl PartialEq for Run {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Run {}
+
+impl PartialOrd for Run {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Run {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+///
+/// Lazy k-way merge by timestamp over chunks that may overlap in time. A chunk
+/// is only opened once the merge reaches its first timestamp, so the chunks
+/// held at once are those overlapping in time, not every chunk given. Entries
+/// with equal timestamps come out by the rank of their chunk, then in the
+/// order their chunks were opened and the entries were written.
+///
+pub struct EntryMerge<P> {
+    /// Stack of unopened chunks, with the one starting earliest on top
+    pending: Vec<(u64, usize, P)>,
+    runs: BinaryHeap<Reverse<Run>>,
+    next_run: usize,
+}
+
+impl<P> EntryMerge<P> {
+    ///
+    /// # Arguments
+    /// * chunks: First timestamp, rank and location of each chunk to merge
+    ///
+    pub fn new(mut chunks: Vec<(u64, usize, P)>) -> EntryMerge<P> {
+        chunks.sort_by_key(|(timestamp_from, _, _): &(u64, usize, P)| Reverse(*timestamp_from));
+        return EntryMerge {
+            pending: chunks,
+            runs: BinaryHeap::new(),
+            next_run: 0,
+        };
+    }
+    ///
+    /// Next entry in timestamp order, opening the chunks the merge has reached.
+    ///
+    /// # Arguments
+    /// * open: Reads the entries of a chunk to merge, in any order
+    ///
+    /// # Returns
+    /// `Result<Option<(usize, ChunkEntry)>>`: Rank of the entry's chunk and the entry, `None` once every chunk is drained
+    ///
+    pub fn next_entry(&mut self, open: &mut dyn FnMut(P) -> Result<Vec<ChunkEntry>, Error>) -> Result<Option<(usize, ChunkEntry)>, Error> {
+        loop {
+            let due: bool = match (self.pending.last(), self.runs.peek()) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some((timestamp_from, _, _)), Some(Reverse(run))) => *timestamp_from <= run.head.timestamp,
+            };
+            if !due {
+                break;
+            }
+            if let Some((_, rank, chunk)) = self.pending.pop() {
+                let mut entries: Vec<ChunkEntry> = open(chunk)?;
+                // Chunks sealed before entries were sorted on seal may be out of order
+                entries.sort_by_key(|e: &ChunkEntry| e.timestamp);
+                let mut entries: std::vec::IntoIter<ChunkEntry> = entries.into_iter();
+                if let Some(head) = entries.next() {
+                    self.runs.push(Reverse(Run {
+                        rank,
+                        sequence: self.next_run,
+                        entries,
+                        head,
+                    }));
+                    self.next_run += 1;
+                }
+            }
+        }
+        let Reverse(mut run) = match self.runs.pop() {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        let rank: usize = run.rank;
+        let entry: ChunkEntry = match run.entries.next() {
+            Some(next) => {
+                let entry: ChunkEntry = std::mem::replace(&mut run.head, next);
+                self.runs.push(Reverse(run));
+                entry
+            },
+            None => run.head,
+        };
+        return Ok(Some((rank, entry)));
+    }
+}
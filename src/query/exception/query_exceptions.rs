@@ -0,0 +1,36 @@
+use std::fmt;
+
+pub enum QueryError {
+    QueryParseError(QueryParseError),
+    QueryPlanError(QueryPlanError)
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::QueryParseError(e) => e.fmt(f),
+            QueryError::QueryPlanError(e) => e.fmt(f),
+        }
+    }
+}
+
+pub struct QueryParseError {
+    pub position: usize,
+    pub message: String
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid query at position {}: {}", self.position, self.message)
+    }
+}
+
+pub struct QueryPlanError {
+    pub message: String
+}
+
+impl fmt::Display for QueryPlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unable to plan query: {}", self.message)
+    }
+}
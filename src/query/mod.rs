@@ -0,0 +1,9 @@
+pub mod aggregation;
+pub mod entry_fields;
+pub mod entry_merge;
+pub mod exception;
+#[allow(clippy::module_inception)]
+pub mod query;
+pub mod query_executor;
+pub mod query_parser;
+pub mod query_planner;
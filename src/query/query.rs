@@ -0,0 +1,86 @@
+///
+/// One end of a query time range, resolved against the current time when the
+/// query is planned.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeBound {
+    /// Milliseconds since the epoch
+    ABSOLUTE(u64),
+    /// Milliseconds before the time the query is planned
+    RELATIVE(u64),
+    /// No bound on this end of the range
+    OPEN,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComparisonOperator {
+    EQ,
+    NE,
+    LT,
+    LE,
+    GT,
+    GE,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpression {
+    /// `time:[from TO to]`, entries with a timestamp in the inclusive range
+    TIME_RANGE(TimeBound, TimeBound),
+    /// `target:name`, entries with exactly this target
+    TARGET(String),
+    /// `action:n`, entries with this action
+    ACTION(u8),
+    /// `/pattern/`, entries whose message matches the regex
+    REGEX(String),
+    /// Bare or quoted text, entries whose message contains it
+    TEXT(String),
+    /// `term:word` or `term:"some words"`, entries whose message holds the words in order, ignoring case
+    TERM(String),
+    /// `field op value` or `field:value`, over an entry or structured message field
+    COMPARISON(String, ComparisonOperator, String),
+    AND(Box<QueryExpression>, Box<QueryExpression>),
+    OR(Box<QueryExpression>, Box<QueryExpression>),
+    NOT(Box<QueryExpression>),
+}
+
+///
+/// Processing applied to the matching entries, in the order written.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryStage {
+    /// `| limit n`, keep the first n results
+    LIMIT(usize),
+    /// `| count` or `| histogram(bucket = 1m)`, optionally `by action` or `by target`
+    AGGREGATE(Aggregation),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregationGroup {
+    /// One series over every matching entry
+    NONE,
+    /// One series per action
+    ACTION,
+    /// One series per target
+    TARGET,
+}
+
+///
+/// Count of matching entries, split into a series per group and into time
+/// buckets within each series.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregation {
+    pub group: AggregationGroup,
+    /// Bucket width in milliseconds, `None` to count over the whole range
+    pub bucket: Option<u64>,
+}
+
+///
+/// Parsed log search query: an optional filter expression, matching every
+/// entry when absent, followed by pipeline stages.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub expression: Option<QueryExpression>,
+    pub stages: Vec<QueryStage>,
+}
@@ -0,0 +1,515 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::store::archive::archive_store::ArchiveStore;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::data::representational::store::index::index_posting::IndexPosting;
+use crate::data::representational::store::index::index_query::IndexQuery;
+use crate::data::representational::store::index::inverted_index::InvertedIndex;
+use super::aggregation::{AggregationResult, Aggregator};
+use super::entry_merge::EntryMerge;
+use super::query::{Aggregation, QueryStage};
+use super::query_planner::{QueryPlan, QueryPlanner};
+
+#[derive(Debug, Clone)]
+pub enum QueryResult {
+    /// Matching entries in timestamp order
+    ENTRIES(Vec<ChunkEntry>),
+    /// Counts of matching entries from an aggregation stage
+    AGGREGATION(AggregationResult),
+}
+
+///
+/// Chunk that may hold matches, with what is known of it before its entries
+/// are decompressed.
+///
+struct CandidateChunk {
+    timestamp_from: u64,
+    timestamp_to: u64,
+    /// Archived chunks are read whole, chunks in the store only once their entries are needed
+    chunk: Option<Chunk>,
+    index: usize,
+    sections: Vec<ChunkMetadataSection>,
+}
+
+///
+/// Location of a chunk that may hold matches, with its bounds, so candidates
+/// can be ordered before any of them is read.
+///
+pub struct PendingChunk {
+    pub timestamp_from: u64,
+    pub timestamp_to: u64,
+    /// Index of the archive holding the chunk, `None` for chunks in the store
+    pub archive: Option<usize>,
+    pub index: usize,
+    sections: Vec<ChunkMetadataSection>,
+}
+
+///
+/// Archive read last, kept open by its path while chunks from it are loaded.
+///
+pub type OpenArchive = Option<(String, ArchiveStore)>;
+
+impl CandidateChunk {
+    fn sections(&self) -> &[ChunkMetadataSection] {
+        return match &self.chunk {
+            Some(chunk) => chunk.sections.as_slice(),
+            None => self.sections.as_slice(),
+        };
+    }
+    fn entries(&self, store: &ChunkStore) -> Result<Vec<ChunkEntry>, Error> {
+        let decoded = match &self.chunk {
+            Some(chunk) => chunk.decode_entries(),
+            None => store.read_chunk(self.index)?.decode_entries(),
+        };
+        return decoded.map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()));
+    }
+}
+
+impl QueryPlan {
+    ///
+    /// Chunks in the store holding entries that satisfy the plan's index query,
+    /// or `None` if it has none. The store's inverted index is opened in memory
+    /// for the lookup, so a missing or stale sidecar is rebuilt without writing
+    /// over the one the store's writer maintains.
+    ///
+    fn indexed_chunks(&self, store: &ChunkStore) -> Result<Option<BTreeSet<usize>>, Error> {
+        let query: &IndexQuery = match &self.index_query {
+            Some(q) => q,
+            None => return Ok(None),
+        };
+        let postings: Vec<IndexPosting> = InvertedIndex::open_in_memory(store)?.search(store, query)?;
+        return Ok(Some(postings.iter().map(|p: &IndexPosting| p.chunk as usize).collect()));
+    }
+    ///
+    /// Indices of the chunks in the store that may hold matching entries. Only
+    /// chunks overlapping the plan's time range are considered, and each is
+    /// checked against the chunk condition from its bounds and metadata
+    /// sections alone, then against the inverted index if the plan queries it.
+    ///
+    pub fn candidate_chunks(&self, store: &ChunkStore) -> Result<Vec<usize>, Error> {
+        return Ok(self.stored_chunks(store)?.iter().map(|p: &PendingChunk| p.index).collect());
+    }
+    fn stored_chunks(&self, store: &ChunkStore) -> Result<Vec<PendingChunk>, Error> {
+        let indexed: Option<BTreeSet<usize>> = self.indexed_chunks(store)?;
+        let mut file: File = File::open(store.path.as_str())?;
+        let mut candidates: Vec<PendingChunk> = Vec::new();
+        for index in store.chunks_overlapping_range(self.from, self.to)? {
+            if indexed.as_ref().is_some_and(|i: &BTreeSet<usize>| !i.contains(&index)) {
+                continue;
+            }
+            let (timestamp_from, timestamp_to): (u64, u64) = store.read_chunk_bounds(&mut file, index)?;
+            let sections: Vec<ChunkMetadataSection> = store.read_chunk_sections(&mut file, index)?;
+            if self.chunk_condition.may_match(timestamp_from, timestamp_to, sections.as_slice()) {
+                candidates.push(PendingChunk {
+                    timestamp_from,
+                    timestamp_to,
+                    archive: None,
+                    index,
+                    sections,
+                });
+            }
+        }
+        return Ok(candidates);
+    }
+    ///
+    /// Chunks of a store and its archives that may hold matches, archived
+    /// chunks first, then in store order. Chunks in the store are checked
+    /// against the chunk condition from their bounds and sections and against
+    /// the inverted index; archived chunks, which the index does not cover,
+    /// only once they are read.
+    ///
+    /// # Arguments
+    /// * store: Store to search
+    ///
+    /// # Returns
+    /// `Result<Vec<PendingChunk>>`: Candidate chunks, none of them read yet
+    ///
+    pub fn pending_chunks(&self, store: &ChunkStore) -> Result<Vec<PendingChunk>, Error> {
+        let mut pending: Vec<PendingChunk> = Vec::new();
+        for (archive_index, reference) in store.header.archives.iter().enumerate() {
+            if !reference.overlaps(self.from, self.to) {
+                continue;
+            }
+            let archive: ArchiveStore = ArchiveStore::open(store.archive_path(reference).as_str())?;
+            for index in archive.chunks_overlapping(self.from, self.to) {
+                pending.push(PendingChunk {
+                    timestamp_from: archive.index[index].timestamp_from,
+                    timestamp_to: archive.index[index].timestamp_to,
+                    archive: Some(archive_index),
+                    index,
+                    sections: Vec::new(),
+                });
+            }
+        }
+        pending.append(&mut self.stored_chunks(store)?);
+        return Ok(pending);
+    }
+    ///
+    /// Read a pending chunk, keeping the archive it came from open for the next
+    /// one. Archived chunks failing the chunk condition are skipped.
+    ///
+    fn load_chunk(&self, store: &ChunkStore, pending: PendingChunk, archive: &mut OpenArchive) -> Result<Option<CandidateChunk>, Error> {
+        let archive_index: usize = match pending.archive {
+            Some(i) => i,
+            None => return Ok(Some(CandidateChunk {
+                timestamp_from: pending.timestamp_from,
+                timestamp_to: pending.timestamp_to,
+                chunk: None,
+                index: pending.index,
+                sections: pending.sections,
+            })),
+        };
+        let path: String = store.archive_path(&store.header.archives[archive_index]);
+        if archive.as_ref().is_none_or(|(p, _): &(String, ArchiveStore)| *p != path) {
+            let opened: ArchiveStore = ArchiveStore::open(path.as_str())?;
+            *archive = Some((path, opened));
+        }
+        let chunk: Chunk = match archive {
+            Some((_, a)) => a.read_chunk(pending.index)?,
+            None => return Ok(None),
+        };
+        if !self.chunk_condition.may_match(chunk.timestamp_from, chunk.timestamp_to, chunk.sections.as_slice()) {
+            return Ok(None);
+        }
+        return Ok(Some(CandidateChunk {
+            timestamp_from: chunk.timestamp_from,
+            timestamp_to: chunk.timestamp_to,
+            chunk: Some(chunk),
+            index: pending.index,
+            sections: Vec::new(),
+        }));
+    }
+    ///
+    /// Entries of a pending chunk that match the plan, in the order they are
+    /// stored.
+    ///
+    /// # Arguments
+    /// * store: Store the chunk was listed from by `pending_chunks`
+    /// * pending: Chunk to read
+    /// * archive: Archive kept open between calls
+    ///
+    /// # Returns
+    /// `Result<Vec<ChunkEntry>>`: Matching entries, empty if the chunk cannot match
+    ///
+    pub fn chunk_matches(&self, store: &ChunkStore, pending: PendingChunk, archive: &mut OpenArchive) -> Result<Vec<ChunkEntry>, Error> {
+        let candidate: CandidateChunk = match self.load_chunk(store, pending, archive)? {
+            Some(c) => c,
+            None => return Ok(Vec::new()),
+        };
+        let mut entries: Vec<ChunkEntry> = candidate.entries(store)?;
+        entries.retain(|e: &ChunkEntry| self.predicate.matches(e));
+        return Ok(entries);
+    }
+    ///
+    /// Visit the chunks that may hold matches one at a time, archived chunks
+    /// first, until the visitor returns `false`.
+    ///
+    fn scan_chunks(&self, store: &ChunkStore, visit: &mut dyn FnMut(&CandidateChunk) -> Result<bool, Error>) -> Result<(), Error> {
+        let mut archive: OpenArchive = None;
+        for pending in self.pending_chunks(store)?.into_iter() {
+            let candidate: CandidateChunk = match self.load_chunk(store, pending, &mut archive)? {
+                Some(c) => c,
+                None => continue,
+            };
+            if !visit(&candidate)? {
+                return Ok(());
+            }
+        }
+        return Ok(());
+    }
+    ///
+    /// Visit matching entries in timestamp order until the visitor returns
+    /// `false`. While the candidate chunks follow one another in time they are
+    /// read in turn; if the store has overlapping chunks, or its archives and
+    /// chunks overlap, the chunks are merged by timestamp with `EntryMerge`, so
+    /// a limit keeps the earliest matches.
+    ///
+    fn scan_matches(&self, store: &ChunkStore, visit: &mut dyn FnMut(ChunkEntry) -> Result<bool, Error>) -> Result<(), Error> {
+        let pending: Vec<PendingChunk> = self.pending_chunks(store)?;
+        let ordered: bool = !store.has_overlapping_chunks()
+            && pending.windows(2).all(|w: &[PendingChunk]| w[1].timestamp_from >= w[0].timestamp_to);
+        let mut archive: OpenArchive = None;
+        if ordered {
+            for chunk in pending.into_iter() {
+                for entry in self.chunk_matches(store, chunk, &mut archive)?.into_iter() {
+                    if !visit(entry)? {
+                        return Ok(());
+                    }
+                }
+            }
+            return Ok(());
+        }
+        let mut merge: EntryMerge<PendingChunk> = EntryMerge::new(
+            pending.into_iter().map(|p: PendingChunk| (p.timestamp_from, 0, p)).collect(),
+        );
+        while let Some((_, entry)) = merge.next_entry(&mut |p: PendingChunk| self.chunk_matches(store, p, &mut archive))? {
+            if !visit(entry)? {
+                return Ok(());
+            }
+        }
+        return Ok(());
+    }
+    ///
+    /// Run the plan against a store and its archives, then apply the stages.
+    ///
+    /// # Arguments
+    /// * store: Store to search
+    ///
+    /// # Returns
+    /// `Result<QueryResult>`: Matching entries, or counts if the query aggregates them
+    ///
+    pub fn execute(&self, store: &ChunkStore) -> Result<QueryResult, Error> {
+        let aggregate_at: usize = self.stages.iter()
+            .position(|s: &QueryStage| matches!(s, QueryStage::AGGREGATE(_)))
+            .unwrap_or(self.stages.len());
+        // Limits ahead of any aggregation bound how many matches are read
+        let read_limit: usize = self.stages[..aggregate_at].iter()
+            .filter_map(|s: &QueryStage| match s {
+                QueryStage::LIMIT(n) => Some(*n),
+                _ => None,
+            })
+            .min()
+            .unwrap_or(usize::MAX);
+        let (mut result, stages): (QueryResult, &[QueryStage]) = match self.stages.get(aggregate_at) {
+            Some(QueryStage::AGGREGATE(aggregation)) => (
+                QueryResult::AGGREGATION(self.aggregate_matches(store, aggregation, read_limit)?),
+                &self.stages[aggregate_at + 1..],
+            ),
+            _ => (QueryResult::ENTRIES(self.collect_matches(store, read_limit)?), self.stages.as_slice()),
+        };
+        for stage in stages.iter() {
+            result = Self::apply_stage(stage, result);
+        }
+        return Ok(result);
+    }
+    ///
+    /// Count the plan's matches. Chunks are streamed one at a time, and those
+    /// whose metadata already answers the aggregation are not decompressed.
+    ///
+    /// # Arguments
+    /// * store: Store to search
+    /// * aggregation: Grouping and bucketing of the counts
+    ///
+    /// # Returns
+    /// `Result<AggregationResult>`: Count series of the matching entries
+    ///
+    pub fn aggregate(&self, store: &ChunkStore, aggregation: &Aggregation) -> Result<AggregationResult, Error> {
+        return self.aggregate_matches(store, aggregation, usize::MAX);
+    }
+    fn aggregate_matches(&self, store: &ChunkStore, aggregation: &Aggregation, limit: usize) -> Result<AggregationResult, Error> {
+        let mut aggregator: Aggregator = Aggregator::new(*aggregation);
+        let summarise: bool = self.predicate.is_time_only();
+        if limit == usize::MAX {
+            self.scan_chunks(store, &mut |candidate: &CandidateChunk| {
+                if summarise && aggregator.add_summarised(
+                    candidate.timestamp_from,
+                    candidate.timestamp_to,
+                    candidate.sections(),
+                    self.from,
+                    self.to,
+                ) {
+                    return Ok(true);
+                }
+                for entry in candidate.entries(store)?.iter() {
+                    if self.predicate.matches(entry) {
+                        aggregator.add_entry(entry);
+                    }
+                }
+                return Ok(true);
+            })?;
+        } else if limit > 0 {
+            // A limit counts the earliest matches, so they are read in timestamp order
+            self.scan_matches(store, &mut |entry: ChunkEntry| {
+                aggregator.add_entry(&entry);
+                return Ok(aggregator.matched() < limit as u64);
+            })?;
+        }
+        return Ok(aggregator.finish());
+    }
+    ///
+    /// Pass the plan's matches to a visitor as they are read, in timestamp
+    /// order, holding only the chunks overlapping in time at once. Limit stages
+    /// are honoured; aggregation stages cannot be streamed.
+    ///
+    /// # Arguments
+    /// * store: Store to search
+    /// * visit: Called with each matching entry, stopping the scan on error
+    ///
+    /// # Returns
+    /// `Result<u64>`: Number of entries visited, or `InvalidInput` if the plan aggregates
+    ///
+    pub fn for_each_match(&self, store: &ChunkStore, visit: &mut dyn FnMut(&ChunkEntry) -> Result<(), Error>) -> Result<u64, Error> {
+        let mut limit: u64 = u64::MAX;
+        for stage in self.stages.iter() {
+            match stage {
+                QueryStage::LIMIT(n) => limit = limit.min(*n as u64),
+                QueryStage::AGGREGATE(_) => return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Streamed queries cannot aggregate",
+                )),
+            }
+        }
+        let mut visited: u64 = 0;
+        if limit > 0 {
+            self.scan_matches(store, &mut |entry: ChunkEntry| {
+                visit(&entry)?;
+                visited += 1;
+                return Ok(visited < limit);
+            })?;
+        }
+        return Ok(visited);
+    }
+    fn collect_matches(&self, store: &ChunkStore, limit: usize) -> Result<Vec<ChunkEntry>, Error> {
+        let mut entries: Vec<ChunkEntry> = Vec::new();
+        if limit > 0 {
+            self.scan_matches(store, &mut |entry: ChunkEntry| {
+                entries.push(entry);
+                return Ok(entries.len() < limit);
+            })?;
+        }
+        return Ok(entries);
+    }
+    fn apply_stage(stage: &QueryStage, result: QueryResult) -> QueryResult {
+        return match (stage, result) {
+            (QueryStage::LIMIT(n), QueryResult::ENTRIES(mut entries)) => {
+                entries.truncate(*n);
+                QueryResult::ENTRIES(entries)
+            },
+            (QueryStage::LIMIT(n), QueryResult::AGGREGATION(mut aggregation)) => {
+                aggregation.series.truncate(*n);
+                QueryResult::AGGREGATION(aggregation)
+            },
+            // Planning allows a single aggregation, which execution has already run
+            (QueryStage::AGGREGATE(_), result) => result,
+        };
+    }
+}
+
+impl ChunkStore {
+    ///
+    /// Parse, plan and run a query against this store, resolving relative time
+    /// bounds against the current time.
+    ///
+    /// # Arguments
+    /// * text: Query text
+    ///
+    /// # Returns
+    /// `Result<QueryResult>`: Query results, or `InvalidInput` if the query is invalid
+    ///
+    pub fn query(&self, text: &str) -> Result<QueryResult, Error> {
+        let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        let plan: QueryPlan = match QueryPlanner::plan_text(text, now) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+        return plan.execute(self);
+    }
+    ///
+    /// Count the entries matching a query, grouped and bucketed as requested.
+    /// Stages in the query text are ignored.
+    ///
+    /// # Arguments
+    /// * text: Query text selecting the entries to count
+    /// * aggregation: Grouping and bucketing of the counts
+    ///
+    /// # Returns
+    /// `Result<AggregationResult>`: Count series, or `InvalidInput` if the query is invalid
+    ///
+    pub fn aggregate(&self, text: &str, aggregation: &Aggregation) -> Result<AggregationResult, Error> {
+        let now: u64 = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        let plan: QueryPlan = match QueryPlanner::plan_text(text, now) {
+            Ok(p) => p,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+        return plan.aggregate(self, aggregation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::store::compaction::CompactionConfig;
+    use crate::utils::test_utils::{chunk_of, chunk_with, temp_store_path};
+
+    fn messages_store(name: &str) -> ChunkStore {
+        let path: String = temp_store_path(name);
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        store.append_chunk(chunk_with(&[(1, "Disk full on sda"), (2, "user login ok")])).unwrap();
+        store.append_chunk(chunk_with(&[(3, "disk check passed"), (4, "login failed for user")])).unwrap();
+        store.append_chunk(chunk_with(&[(5, "nothing to report")])).unwrap();
+        return store;
+    }
+
+    fn timestamps(result: QueryResult) -> Vec<u64> {
+        return match result {
+            QueryResult::ENTRIES(entries) => entries.iter().map(|e: &ChunkEntry| e.timestamp).collect(),
+            QueryResult::AGGREGATION(_) => panic!("Expected entries"),
+        };
+    }
+
+    #[test]
+    fn limits_overlapping_chunks_to_earliest_matches() {
+        let path: String = temp_store_path("limits_overlapping_chunks_to_earliest_matches");
+        let mut store: ChunkStore = ChunkStore::create(path.as_str(), 64, false).unwrap();
+        store.append_chunk(chunk_of(&[10, 20, 30])).unwrap();
+        store.append_chunk(chunk_of(&[5, 15, 25])).unwrap();
+        assert!(store.has_overlapping_chunks());
+        assert_eq!(timestamps(store.query("| limit 3").unwrap()), vec![5, 10, 15]);
+        assert_eq!(timestamps(store.query("").unwrap()), vec![5, 10, 15, 20, 25, 30]);
+    }
+
+    #[test]
+    fn answers_term_and_phrase_queries_from_the_index() {
+        let store: ChunkStore = messages_store("answers_term_and_phrase_queries_from_the_index");
+        assert_eq!(timestamps(store.query("term:DISK").unwrap()), vec![1, 3]);
+        assert_eq!(timestamps(store.query("term:disk AND term:full").unwrap()), vec![1]);
+        assert_eq!(timestamps(store.query("term:full OR term:failed").unwrap()), vec![1, 4]);
+        assert_eq!(timestamps(store.query("term:\"login failed\"").unwrap()), vec![4]);
+        assert!(timestamps(store.query("term:\"failed login\"").unwrap()).is_empty());
+        assert_eq!(timestamps(store.query("term:user NOT term:failed").unwrap()), vec![2]);
+        // Terms ORed with a condition the index cannot answer are checked entry by entry
+        assert_eq!(timestamps(store.query("term:passed OR action:1").unwrap()), vec![3]);
+        assert_eq!(store.query("term:\"--\"").unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        // Chunks without a posting are never read
+        let plan: QueryPlan = QueryPlanner::plan_text("term:login", 0).ok().expect("Could not plan query");
+        assert_eq!(plan.candidate_chunks(&store).unwrap(), vec![0, 1]);
+        let plan: QueryPlan = QueryPlanner::plan_text("term:full OR term:report", 0).ok().expect("Could not plan query");
+        assert_eq!(plan.candidate_chunks(&store).unwrap(), vec![0, 2]);
+        let plan: QueryPlan = QueryPlanner::plan_text("NOT term:login", 0).ok().expect("Could not plan query");
+        assert_eq!(plan.candidate_chunks(&store).unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn rebuilds_missing_or_stale_index_for_term_queries() {
+        let mut store: ChunkStore = messages_store("rebuilds_missing_or_stale_index_for_term_queries");
+        let index_path: String = InvertedIndex::path_for_store(store.path.as_str());
+        assert!(!std::path::Path::new(index_path.as_str()).exists());
+        assert_eq!(timestamps(store.query("term:disk").unwrap()), vec![1, 3]);
+        // Queries rebuild the index in memory and leave the sidecar to the writer
+        assert!(!std::path::Path::new(index_path.as_str()).exists());
+        InvertedIndex::open(&store).unwrap();
+        let saved: Vec<u8> = std::fs::read(index_path.as_str()).unwrap();
+
+        // Chunks appended since the index was saved are caught up
+        store.append_chunk(chunk_with(&[(6, "disk replaced")])).unwrap();
+        assert_eq!(timestamps(store.query("term:disk").unwrap()), vec![1, 3, 6]);
+        assert_eq!(std::fs::read(index_path.as_str()).unwrap(), saved);
+
+        // Compaction renumbers the chunks, so the saved postings are rebuilt
+        assert!(store.compact(&CompactionConfig::default()).unwrap().chunks_merged > 0);
+        assert_eq!(timestamps(store.query("term:disk").unwrap()), vec![1, 3, 6]);
+        assert_eq!(timestamps(store.query("term:report").unwrap()), vec![5]);
+        let index: InvertedIndex = InvertedIndex::open(&store).unwrap();
+        assert_eq!(index.chunk_epoch, store.header.chunk_epoch);
+        assert_eq!(index.chunk_count, store.header.chunk_offsets.len());
+
+        // An unreadable sidecar is rebuilt too
+        std::fs::write(index_path.as_str(), b"not an index").unwrap();
+        assert_eq!(timestamps(store.query("term:\"disk full\"").unwrap()), vec![1]);
+    }
+}
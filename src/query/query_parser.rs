@@ -0,0 +1,470 @@
+use nom::IResult;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, digit1, multispace0, multispace1, satisfy};
+use nom::combinator::{cut, map, map_res, not, opt, peek, recognize, value, verify};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use super::exception::query_exceptions::QueryParseError;
+use super::query::{Aggregation, AggregationGroup, ComparisonOperator, Query, QueryExpression, QueryStage, TimeBound};
+
+///
+/// Parse a log search query.
+///
+/// ```text
+/// query      := expression? stage*
+/// expression := and ("OR" and)*
+/// and        := not ("AND"? not)*
+/// not        := "NOT" not | primary
+/// primary    := "(" expression ")" | "time:[" bound "TO" bound "]"
+///             | "target:" value | "action:" integer | "term:" value | "/" regex "/"
+///             | field operator value | field ":" value | value
+/// stage      := "|" "limit" integer | "|" aggregate ("by" ("action" | "target"))?
+/// aggregate  := "count" | "histogram" "(" "bucket" "=" duration ")"
+/// bound      := "*" | "now" | "now-" duration | integer | RFC 3339 time
+/// duration   := integer ("ms" | "s" | "m" | "h" | "d")
+/// ```
+///
+/// Terms written next to each other are implicitly joined with AND. Values
+/// are bare words or double quoted strings with `\` escapes. `term:` queries
+/// match whole words and are answered from the store's inverted index.
+///
+/// # Arguments
+/// * input: Query text
+///
+/// # Returns
+/// `Result<Query>`: Parsed query, or the position and reason it is invalid
+///
+pub fn parse_query(input: &str) -> Result<Query, QueryParseError> {
+    let parsed: IResult<&str, Query> = map(
+        pair(
+            delimited(multispace0, opt(expression), multispace0),
+            many0(stage),
+        ),
+        |(expression, stages): (Option<QueryExpression>, Vec<QueryStage>)| Query { expression, stages },
+    )(input);
+    return match parsed {
+        Ok((rest, query)) => {
+            let rest: &str = rest.trim_start();
+            if rest.is_empty() {
+                Ok(query)
+            } else {
+                Err(QueryParseError {
+                    position: input.len() - rest.len(),
+                    message: format!("unexpected '{}'", rest.chars().take(16).collect::<String>()),
+                })
+            }
+        },
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(QueryParseError {
+            position: input.len() - e.input.len(),
+            message: if e.input.is_empty() {
+                String::from("unexpected end of query")
+            } else {
+                format!("unexpected '{}'", e.input.chars().take(16).collect::<String>())
+            },
+        }),
+        Err(nom::Err::Incomplete(_)) => Err(QueryParseError {
+            position: input.len(),
+            message: String::from("unexpected end of query"),
+        }),
+    };
+}
+
+fn expression(input: &str) -> IResult<&str, QueryExpression> {
+    let (input, first) = and_expression(input)?;
+    let (input, rest) = many0(preceded(
+        delimited(multispace0, keyword("OR"), multispace0),
+        cut(and_expression),
+    ))(input)?;
+    return Ok((input, rest.into_iter().fold(first, |l: QueryExpression, r: QueryExpression| {
+        QueryExpression::OR(Box::new(l), Box::new(r))
+    })));
+}
+
+fn and_expression(input: &str) -> IResult<&str, QueryExpression> {
+    let (input, first) = not_expression(input)?;
+    let (input, rest) = many0(preceded(
+        pair(multispace0, opt(terminated(keyword("AND"), multispace0))),
+        not_expression,
+    ))(input)?;
+    return Ok((input, rest.into_iter().fold(first, |l: QueryExpression, r: QueryExpression| {
+        QueryExpression::AND(Box::new(l), Box::new(r))
+    })));
+}
+
+fn not_expression(input: &str) -> IResult<&str, QueryExpression> {
+    alt((
+        map(
+            preceded(terminated(keyword("NOT"), multispace0), cut(not_expression)),
+            |e: QueryExpression| QueryExpression::NOT(Box::new(e)),
+        ),
+        primary,
+    ))(input)
+}
+
+fn primary(input: &str) -> IResult<&str, QueryExpression> {
+    alt((
+        delimited(
+            terminated(char('('), multispace0),
+            cut(expression),
+            cut(preceded(multispace0, char(')'))),
+        ),
+        time_range,
+        map(
+            preceded(tag("target:"), cut(value_token)),
+            QueryExpression::TARGET,
+        ),
+        map(
+            preceded(tag("action:"), cut(map_res(digit1, |d: &str| d.parse::<u8>()))),
+            QueryExpression::ACTION,
+        ),
+        map(
+            preceded(tag("term:"), cut(value_token)),
+            QueryExpression::TERM,
+        ),
+        map(regex_literal, QueryExpression::REGEX),
+        map(
+            tuple((identifier, delimited(multispace0, operator, multispace0), value_token)),
+            |(f, o, v): (&str, ComparisonOperator, String)| QueryExpression::COMPARISON(String::from(f), o, v),
+        ),
+        map(
+            tuple((identifier, char(':'), value_token)),
+            |(f, _, v): (&str, char, String)| QueryExpression::COMPARISON(String::from(f), ComparisonOperator::EQ, v),
+        ),
+        map(text_token, QueryExpression::TEXT),
+    ))(input)
+}
+
+fn time_range(input: &str) -> IResult<&str, QueryExpression> {
+    let (input, _) = tag("time:[")(input)?;
+    let (input, (from, _, to, _)) = cut(tuple((
+        delimited(multispace0, time_bound, multispace1),
+        tag("TO"),
+        delimited(multispace1, time_bound, multispace0),
+        char(']'),
+    )))(input)?;
+    return Ok((input, QueryExpression::TIME_RANGE(from, to)));
+}
+
+fn time_bound(input: &str) -> IResult<&str, TimeBound> {
+    map_res(
+        take_while1(|c: char| !c.is_whitespace() && c != ']'),
+        parse_time_bound,
+    )(input)
+}
+
+pub fn parse_time_bound(text: &str) -> Result<TimeBound, String> {
+    if text == "*" {
+        return Ok(TimeBound::OPEN);
+    }
+    if text == "now" {
+        return Ok(TimeBound::RELATIVE(0));
+    }
+    if let Some(offset) = text.strip_prefix("now-") {
+        return parse_duration(offset).map(TimeBound::RELATIVE);
+    }
+    if text.chars().all(|c: char| c.is_ascii_digit()) {
+        return text.parse::<u64>().map(TimeBound::ABSOLUTE).map_err(|e| e.to_string());
+    }
+    return match chrono::DateTime::parse_from_rfc3339(text) {
+        Ok(t) => Ok(TimeBound::ABSOLUTE(t.timestamp_millis().max(0) as u64)),
+        Err(e) => Err(e.to_string()),
+    };
+}
+
+///
+/// Parse a duration such as `15m` into milliseconds.
+///
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let split: usize = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let amount: u64 = text[..split].parse::<u64>().map_err(|e| e.to_string())?;
+    let unit: u64 = match &text[split..] {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        other => return Err(format!("unknown time unit '{}'", other)),
+    };
+    return Ok(amount.saturating_mul(unit));
+}
+
+fn stage(input: &str) -> IResult<&str, QueryStage> {
+    preceded(
+        delimited(multispace0, char('|'), multispace0),
+        cut(terminated(
+            alt((
+                map(
+                    preceded(pair(tag("limit"), multispace1), map_res(digit1, |d: &str| d.parse::<usize>())),
+                    QueryStage::LIMIT,
+                ),
+                map(aggregation, QueryStage::AGGREGATE),
+            )),
+            multispace0,
+        )),
+    )(input)
+}
+
+fn aggregation(input: &str) -> IResult<&str, Aggregation> {
+    let (input, bucket) = alt((
+        value(None, keyword("count")),
+        map(
+            delimited(
+                tuple((keyword("histogram"), multispace0, char('('), multispace0, keyword("bucket"), multispace0, char('='), multispace0)),
+                bucket_width,
+                pair(multispace0, char(')')),
+            ),
+            Some,
+        ),
+    ))(input)?;
+    let (input, group) = opt(preceded(
+        tuple((multispace1, keyword("by"), multispace1)),
+        alt((
+            value(AggregationGroup::ACTION, keyword("action")),
+            value(AggregationGroup::TARGET, keyword("target")),
+        )),
+    ))(input)?;
+    return Ok((input, Aggregation {
+        group: group.unwrap_or(AggregationGroup::NONE),
+        bucket,
+    }));
+}
+
+fn bucket_width(input: &str) -> IResult<&str, u64> {
+    verify(
+        map_res(take_while1(|c: char| c.is_ascii_alphanumeric()), parse_duration),
+        |width: &u64| *width > 0,
+    )(input)
+}
+
+fn operator(input: &str) -> IResult<&str, ComparisonOperator> {
+    alt((
+        value(ComparisonOperator::GE, tag(">=")),
+        value(ComparisonOperator::LE, tag("<=")),
+        value(ComparisonOperator::NE, tag("!=")),
+        value(ComparisonOperator::EQ, tag("==")),
+        value(ComparisonOperator::EQ, tag("=")),
+        value(ComparisonOperator::LT, tag("<")),
+        value(ComparisonOperator::GT, tag(">")),
+    ))(input)
+}
+
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(word), not(peek(satisfy(is_identifier_char))))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn identifier(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        satisfy(|c: char| c.is_alphabetic() || c == '_'),
+        take_while(is_identifier_char),
+    ))(input)
+}
+
+fn value_token(input: &str) -> IResult<&str, String> {
+    alt((
+        quoted_string,
+        map(bare_word, String::from),
+    ))(input)
+}
+
+fn text_token(input: &str) -> IResult<&str, String> {
+    alt((
+        quoted_string,
+        map(
+            verify(bare_word, |w: &str| w != "AND" && w != "OR" && w != "NOT"),
+            String::from,
+        ),
+    ))(input)
+}
+
+fn bare_word(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| !c.is_whitespace() && !"()|\"".contains(c))(input)
+}
+
+fn quoted_string(input: &str) -> IResult<&str, String> {
+    return delimited_literal(input, '"', false);
+}
+
+fn regex_literal(input: &str) -> IResult<&str, String> {
+    return delimited_literal(input, '/', true);
+}
+
+///
+/// Text between two delimiters, where `\` escapes the delimiter. Other escapes
+/// are resolved in strings but kept in regexes, where the regex interprets them.
+///
+fn delimited_literal(input: &str, delimiter: char, keep_escapes: bool) -> IResult<&str, String> {
+    let (rest, _) = char(delimiter)(input)?;
+    let mut literal: String = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == delimiter {
+            return Ok((&rest[i + c.len_utf8()..], literal));
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some((_, escaped)) => {
+                    if keep_escapes && escaped != delimiter {
+                        literal.push('\\');
+                    }
+                    literal.push(escaped);
+                },
+                None => break,
+            }
+            continue;
+        }
+        literal.push(c);
+    }
+    return Err(nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Char)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Query {
+        return parse_query(text).ok().unwrap_or_else(|| panic!("Could not parse {}", text));
+    }
+
+    fn expression_of(text: &str) -> QueryExpression {
+        return parse(text).expression.unwrap_or_else(|| panic!("No expression in {}", text));
+    }
+
+    fn text(word: &str) -> Box<QueryExpression> {
+        return Box::new(QueryExpression::TEXT(String::from(word)));
+    }
+
+    fn error_position(text: &str) -> usize {
+        return parse_query(text).err().unwrap_or_else(|| panic!("Parsed {}", text)).position;
+    }
+
+    #[test]
+    fn parses_field_comparisons() {
+        for (query, field, operator, value) in [
+            ("status>=500", "status", ComparisonOperator::GE, "500"),
+            ("status <= 499", "status", ComparisonOperator::LE, "499"),
+            ("latency<1.5", "latency", ComparisonOperator::LT, "1.5"),
+            ("latency > 2", "latency", ComparisonOperator::GT, "2"),
+            ("user != \"bob smith\"", "user", ComparisonOperator::NE, "bob smith"),
+            ("user == bob", "user", ComparisonOperator::EQ, "bob"),
+            ("user=bob", "user", ComparisonOperator::EQ, "bob"),
+            ("http.status:404", "http.status", ComparisonOperator::EQ, "404"),
+        ] {
+            assert_eq!(
+                expression_of(query),
+                QueryExpression::COMPARISON(String::from(field), operator, String::from(value)),
+                "{}",
+                query,
+            );
+        }
+        assert_eq!(expression_of("target:api"), QueryExpression::TARGET(String::from("api")));
+        assert_eq!(expression_of("target:\"web api\""), QueryExpression::TARGET(String::from("web api")));
+        assert_eq!(expression_of("action:3"), QueryExpression::ACTION(3));
+        assert_eq!(expression_of("term:\"disk full\""), QueryExpression::TERM(String::from("disk full")));
+    }
+
+    #[test]
+    fn parses_time_ranges() {
+        assert_eq!(
+            expression_of("time:[now-1h TO now]"),
+            QueryExpression::TIME_RANGE(TimeBound::RELATIVE(60 * 60 * 1000), TimeBound::RELATIVE(0)),
+        );
+        assert_eq!(
+            expression_of("time:[ 1000 TO * ]"),
+            QueryExpression::TIME_RANGE(TimeBound::ABSOLUTE(1000), TimeBound::OPEN),
+        );
+        // RFC 3339 bounds are converted to milliseconds, honouring their offsets
+        assert_eq!(
+            expression_of("time:[2024-05-01T12:00:00Z TO 2024-05-01T14:00:00.250+02:00]"),
+            QueryExpression::TIME_RANGE(TimeBound::ABSOLUTE(1_714_564_800_000), TimeBound::ABSOLUTE(1_714_564_800_250)),
+        );
+        assert_eq!(parse_time_bound("now-15m"), Ok(TimeBound::RELATIVE(15 * 60 * 1000)));
+        assert_eq!(parse_duration("250ms"), Ok(250));
+        assert_eq!(parse_duration("2d"), Ok(2 * 24 * 60 * 60 * 1000));
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_time_bound("yesterday").is_err());
+    }
+
+    #[test]
+    fn parses_regex_and_quoted_literals() {
+        // An escaped delimiter is unescaped, other escapes are left for the regex
+        assert_eq!(expression_of(r"/disk\/full \d+/"), QueryExpression::REGEX(String::from(r"disk/full \d+")));
+        assert_eq!(expression_of(r"/a\\b/"), QueryExpression::REGEX(String::from(r"a\\b")));
+        // Strings resolve every escape
+        assert_eq!(expression_of(r#""say \"hi\" \\ bye""#), QueryExpression::TEXT(String::from(r#"say "hi" \ bye"#)));
+        assert_eq!(expression_of("timeout"), QueryExpression::TEXT(String::from("timeout")));
+    }
+
+    #[test]
+    fn binds_not_then_and_then_or() {
+        // Adjacent terms are joined with AND, which binds tighter than OR
+        assert_eq!(
+            expression_of("a OR b c"),
+            QueryExpression::OR(text("a"), Box::new(QueryExpression::AND(text("b"), text("c")))),
+        );
+        assert_eq!(
+            expression_of("a AND b OR NOT c"),
+            QueryExpression::OR(
+                Box::new(QueryExpression::AND(text("a"), text("b"))),
+                Box::new(QueryExpression::NOT(text("c"))),
+            ),
+        );
+        assert_eq!(
+            expression_of("NOT a b"),
+            QueryExpression::AND(Box::new(QueryExpression::NOT(text("a"))), text("b")),
+        );
+        assert_eq!(
+            expression_of("NOT (a OR b)"),
+            QueryExpression::NOT(Box::new(QueryExpression::OR(text("a"), text("b")))),
+        );
+        // Chains associate to the left
+        assert_eq!(
+            expression_of("a b c"),
+            QueryExpression::AND(Box::new(QueryExpression::AND(text("a"), text("b"))), text("c")),
+        );
+        // Keywords only count as whole words
+        assert_eq!(expression_of("ORACLE"), QueryExpression::TEXT(String::from("ORACLE")));
+    }
+
+    #[test]
+    fn parses_stages() {
+        assert_eq!(parse(""), Query { expression: None, stages: Vec::new() });
+        assert_eq!(
+            parse("target:api | count by target"),
+            Query {
+                expression: Some(QueryExpression::TARGET(String::from("api"))),
+                stages: vec![QueryStage::AGGREGATE(Aggregation { group: AggregationGroup::TARGET, bucket: None })],
+            },
+        );
+        assert_eq!(
+            parse("| histogram(bucket = 1m) by action | limit 5").stages,
+            vec![
+                QueryStage::AGGREGATE(Aggregation { group: AggregationGroup::ACTION, bucket: Some(60 * 1000) }),
+                QueryStage::LIMIT(5),
+            ],
+        );
+        assert_eq!(
+            parse("error |count").stages,
+            vec![QueryStage::AGGREGATE(Aggregation { group: AggregationGroup::NONE, bucket: None })],
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error_position("target:api )"), 11);
+        assert_eq!(error_position("time:[now TO"), 12);
+        assert_eq!(error_position("time:[now TO then]"), 13);
+        assert_eq!(error_position("a AND (b OR"), 11);
+        assert_eq!(error_position("a AND (b"), 8);
+        assert_eq!(error_position("action:300"), 7);
+        assert_eq!(error_position("x /unterminated"), 2);
+        assert_eq!(error_position("x | histogram(bucket = 0s)"), 23);
+        let error: QueryParseError = parse_query("target:api )").err().unwrap();
+        assert_eq!(error.to_string(), "invalid query at position 11: unexpected ')'");
+        assert_eq!(parse_query("time:[now TO").err().unwrap().message, "unexpected end of query");
+    }
+}
@@ -0,0 +1,417 @@
+use std::cmp::Ordering;
+use regex::bytes::Regex;
+use crate::data::representational::chunk_bloom_filter::ChunkBloomFilter;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::chunk_metadata_section::ChunkMetadataSection;
+use crate::data::representational::chunk_summary::ChunkSummary;
+use crate::data::representational::store::index::index_query::IndexQuery;
+use crate::data::representational::store::index::tokenizer;
+use crate::data::representational::store::index::trigram::TrigramQuery;
+use super::entry_fields;
+use super::exception::query_exceptions::{QueryError, QueryPlanError};
+use super::query::{ComparisonOperator, Query, QueryExpression, QueryStage, TimeBound};
+use super::query_parser;
+
+///
+/// Condition on a chunk's time range and metadata sections that every chunk
+/// holding a matching entry satisfies. Evaluating it never decompresses the
+/// chunk, and it only ever errs towards keeping a chunk.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkCondition {
+    ALL,
+    /// Chunk time range overlaps the inclusive range
+    TIME(u64, u64),
+    /// Chunk target bloom filter may contain the target
+    TARGET(Vec<u8>),
+    /// Chunk summary counts entries with the action
+    ACTION(u8),
+    /// Chunk trigram filter may satisfy the query
+    TRIGRAMS(TrigramQuery),
+    AND(Vec<ChunkCondition>),
+    OR(Vec<ChunkCondition>),
+}
+
+impl ChunkCondition {
+    pub fn may_match(&self, timestamp_from: u64, timestamp_to: u64, sections: &[ChunkMetadataSection]) -> bool {
+        return match self {
+            ChunkCondition::ALL => true,
+            ChunkCondition::TIME(from, to) => timestamp_from <= *to && timestamp_to >= *from,
+            ChunkCondition::TARGET(target) => Self::filter(sections, ChunkMetadataSection::BLOOM_FILTER)
+                .is_none_or(|f: ChunkBloomFilter| f.might_contain(target.as_slice())),
+            ChunkCondition::ACTION(action) => sections.iter()
+                .find_map(ChunkSummary::from_section)
+                .is_none_or(|s: ChunkSummary| s.action_count(*action) > 0),
+            ChunkCondition::TRIGRAMS(query) => Self::filter(sections, ChunkMetadataSection::TRIGRAM_FILTER)
+                .is_none_or(|f: ChunkBloomFilter| query.may_match(&f)),
+            ChunkCondition::AND(conditions) => conditions.iter()
+                .all(|c: &ChunkCondition| c.may_match(timestamp_from, timestamp_to, sections)),
+            ChunkCondition::OR(conditions) => conditions.iter()
+                .any(|c: &ChunkCondition| c.may_match(timestamp_from, timestamp_to, sections)),
+        };
+    }
+    fn filter(sections: &[ChunkMetadataSection], kind: u8) -> Option<ChunkBloomFilter> {
+        sections.iter()
+            .find(|s: &&ChunkMetadataSection| s.kind == kind)
+            .and_then(ChunkBloomFilter::from_section)
+    }
+    ///
+    /// Smallest time range containing every chunk the condition can match.
+    ///
+    pub fn time_bounds(&self) -> (u64, u64) {
+        return match self {
+            ChunkCondition::TIME(from, to) => (*from, *to),
+            ChunkCondition::AND(conditions) => conditions.iter()
+                .map(ChunkCondition::time_bounds)
+                .fold((u64::MIN, u64::MAX), |(f, t): (u64, u64), (cf, ct): (u64, u64)| (f.max(cf), t.min(ct))),
+            ChunkCondition::OR(conditions) => conditions.iter()
+                .map(ChunkCondition::time_bounds)
+                .reduce(|(f, t): (u64, u64), (cf, ct): (u64, u64)| (f.min(cf), t.max(ct)))
+                .unwrap_or((u64::MIN, u64::MAX)),
+            _ => (u64::MIN, u64::MAX),
+        };
+    }
+}
+
+///
+/// Per-entry predicate compiled from a query expression, with regexes built
+/// and time bounds resolved.
+///
+#[derive(Debug, Clone)]
+pub enum EntryPredicate {
+    ALL,
+    TIME(u64, u64),
+    TARGET(Vec<u8>),
+    ACTION(u8),
+    REGEX(Regex),
+    TEXT(Vec<u8>),
+    /// Message tokens, consecutive and in order
+    WORDS(Vec<String>),
+    COMPARISON(String, ComparisonOperator, String),
+    AND(Box<EntryPredicate>, Box<EntryPredicate>),
+    OR(Box<EntryPredicate>, Box<EntryPredicate>),
+    NOT(Box<EntryPredicate>),
+}
+
+impl EntryPredicate {
+    pub fn matches(&self, entry: &ChunkEntry) -> bool {
+        return match self {
+            EntryPredicate::ALL => true,
+            EntryPredicate::TIME(from, to) => entry.timestamp >= *from && entry.timestamp <= *to,
+            EntryPredicate::TARGET(target) => entry.target == *target,
+            EntryPredicate::ACTION(action) => entry.action == *action,
+            EntryPredicate::REGEX(regex) => regex.is_match(entry.message.as_slice()),
+            EntryPredicate::TEXT(text) => text.is_empty()
+                || entry.message.windows(text.len()).any(|w: &[u8]| w == text.as_slice()),
+            EntryPredicate::WORDS(words) => tokenizer::tokenize(entry.message.as_slice())
+                .windows(words.len())
+                .any(|w: &[String]| w == words.as_slice()),
+            EntryPredicate::COMPARISON(field, operator, value) => match entry_fields::field_value(entry, field.as_str()) {
+                Some(actual) => Self::compare(actual.as_str(), *operator, value.as_str()),
+                None => *operator == ComparisonOperator::NE,
+            },
+            EntryPredicate::AND(left, right) => left.matches(entry) && right.matches(entry),
+            EntryPredicate::OR(left, right) => left.matches(entry) || right.matches(entry),
+            EntryPredicate::NOT(inner) => !inner.matches(entry),
+        };
+    }
+    ///
+    /// Whether the predicate only restricts timestamps, and so matches every
+    /// entry within the plan's time range.
+    ///
+    pub fn is_time_only(&self) -> bool {
+        return match self {
+            EntryPredicate::ALL | EntryPredicate::TIME(_, _) => true,
+            EntryPredicate::AND(left, right) => left.is_time_only() && right.is_time_only(),
+            _ => false,
+        };
+    }
+    ///
+    /// Compare numerically when both sides are numbers, otherwise as text.
+    ///
+    fn compare(actual: &str, operator: ComparisonOperator, expected: &str) -> bool {
+        let ordering: Option<Ordering> = match (actual.trim().parse::<f64>(), expected.parse::<f64>()) {
+            (Ok(a), Ok(e)) => a.partial_cmp(&e),
+            _ => Some(actual.cmp(expected)),
+        };
+        return match ordering {
+            Some(o) => match operator {
+                ComparisonOperator::EQ => o == Ordering::Equal,
+                ComparisonOperator::NE => o != Ordering::Equal,
+                ComparisonOperator::LT => o == Ordering::Less,
+                ComparisonOperator::LE => o != Ordering::Greater,
+                ComparisonOperator::GT => o == Ordering::Greater,
+                ComparisonOperator::GE => o != Ordering::Less,
+            },
+            None => operator == ComparisonOperator::NE,
+        };
+    }
+}
+
+///
+/// Executable form of a query: the time range of chunks to consider, the
+/// condition those chunks must meet, the predicate entries must satisfy and
+/// the stages applied to the matches.
+///
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub from: u64,
+    pub to: u64,
+    pub chunk_condition: ChunkCondition,
+    /// Query over the store's inverted index every match satisfies, if the expression has one
+    pub index_query: Option<IndexQuery>,
+    pub predicate: EntryPredicate,
+    pub stages: Vec<QueryStage>,
+}
+
+pub struct QueryPlanner {}
+
+impl QueryPlanner {
+    ///
+    /// Parse and plan a query in one step.
+    ///
+    /// # Arguments
+    /// * text: Query text
+    /// * now: Time relative bounds are resolved against, in milliseconds since the epoch
+    ///
+    /// # Returns
+    /// `Result<QueryPlan>`: Plan ready to execute against a store
+    ///
+    pub fn plan_text(text: &str, now: u64) -> Result<QueryPlan, QueryError> {
+        let query: Query = query_parser::parse_query(text).map_err(QueryError::QueryParseError)?;
+        return Self::plan(&query, now).map_err(QueryError::QueryPlanError);
+    }
+    pub fn plan(query: &Query, now: u64) -> Result<QueryPlan, QueryPlanError> {
+        if query.stages.iter().filter(|s: &&QueryStage| matches!(s, QueryStage::AGGREGATE(_))).count() > 1 {
+            return Err(QueryPlanError { message: String::from("a query can aggregate only once") });
+        }
+        let (chunk_condition, index_query, predicate): (ChunkCondition, Option<IndexQuery>, EntryPredicate) = match &query.expression {
+            Some(expression) => (
+                Self::chunk_condition(expression, now)?,
+                Self::index_query(expression),
+                Self::predicate(expression, now)?,
+            ),
+            None => (ChunkCondition::ALL, None, EntryPredicate::ALL),
+        };
+        let (from, to): (u64, u64) = chunk_condition.time_bounds();
+        return Ok(QueryPlan {
+            from,
+            to,
+            chunk_condition,
+            index_query,
+            predicate,
+            stages: query.stages.clone(),
+        });
+    }
+    fn resolve_bound(bound: &TimeBound, now: u64, open: u64) -> u64 {
+        return match bound {
+            TimeBound::ABSOLUTE(t) => *t,
+            TimeBound::RELATIVE(offset) => now.saturating_sub(*offset),
+            TimeBound::OPEN => open,
+        };
+    }
+    fn chunk_condition(expression: &QueryExpression, now: u64) -> Result<ChunkCondition, QueryPlanError> {
+        return Ok(match expression {
+            QueryExpression::TIME_RANGE(from, to) => ChunkCondition::TIME(
+                Self::resolve_bound(from, now, u64::MIN),
+                Self::resolve_bound(to, now, u64::MAX),
+            ),
+            QueryExpression::TARGET(target) => ChunkCondition::TARGET(target.as_bytes().to_vec()),
+            QueryExpression::ACTION(action) => ChunkCondition::ACTION(*action),
+            QueryExpression::REGEX(pattern) => match TrigramQuery::from_regex(pattern.as_str()) {
+                Ok(TrigramQuery::ALL) => ChunkCondition::ALL,
+                Ok(q) => ChunkCondition::TRIGRAMS(q),
+                Err(e) => return Err(QueryPlanError { message: e.to_string() }),
+            },
+            QueryExpression::TEXT(text) => match TrigramQuery::from_substring(text.as_bytes()) {
+                TrigramQuery::ALL => ChunkCondition::ALL,
+                q => ChunkCondition::TRIGRAMS(q),
+            },
+            QueryExpression::AND(left, right) => ChunkCondition::AND(vec![
+                Self::chunk_condition(left, now)?,
+                Self::chunk_condition(right, now)?,
+            ]),
+            QueryExpression::OR(left, right) => {
+                let (left, right): (ChunkCondition, ChunkCondition) = (Self::chunk_condition(left, now)?, Self::chunk_condition(right, now)?);
+                if left == ChunkCondition::ALL || right == ChunkCondition::ALL {
+                    ChunkCondition::ALL
+                } else {
+                    ChunkCondition::OR(vec![left, right])
+                }
+            },
+            // A negation, a field comparison or a term answered by the index can match in any chunk
+            _ => ChunkCondition::ALL,
+        });
+    }
+    ///
+    /// Index query every match of the expression satisfies. Terms under a
+    /// negation, or ORed with anything the index cannot answer, give none.
+    ///
+    fn index_query(expression: &QueryExpression) -> Option<IndexQuery> {
+        return match expression {
+            QueryExpression::TERM(text) => Some(IndexQuery::parse_text(text.as_str())),
+            QueryExpression::AND(left, right) => match (Self::index_query(left), Self::index_query(right)) {
+                (Some(l), Some(r)) => Some(IndexQuery::AND(vec![l, r])),
+                (l, r) => l.or(r),
+            },
+            QueryExpression::OR(left, right) => match (Self::index_query(left), Self::index_query(right)) {
+                (Some(l), Some(r)) => Some(IndexQuery::OR(vec![l, r])),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+    fn predicate(expression: &QueryExpression, now: u64) -> Result<EntryPredicate, QueryPlanError> {
+        return Ok(match expression {
+            QueryExpression::TIME_RANGE(from, to) => EntryPredicate::TIME(
+                Self::resolve_bound(from, now, u64::MIN),
+                Self::resolve_bound(to, now, u64::MAX),
+            ),
+            QueryExpression::TARGET(target) => EntryPredicate::TARGET(target.as_bytes().to_vec()),
+            QueryExpression::ACTION(action) => EntryPredicate::ACTION(*action),
+            QueryExpression::REGEX(pattern) => match Regex::new(pattern.as_str()) {
+                Ok(r) => EntryPredicate::REGEX(r),
+                Err(e) => return Err(QueryPlanError { message: e.to_string() }),
+            },
+            QueryExpression::TEXT(text) => EntryPredicate::TEXT(text.as_bytes().to_vec()),
+            QueryExpression::TERM(text) => match tokenizer::tokenize(text.as_bytes()) {
+                words if words.is_empty() => return Err(QueryPlanError { message: format!("term '{}' has no words", text) }),
+                words => EntryPredicate::WORDS(words),
+            },
+            QueryExpression::COMPARISON(field, operator, value) => EntryPredicate::COMPARISON(field.clone(), *operator, value.clone()),
+            QueryExpression::AND(left, right) => EntryPredicate::AND(
+                Box::new(Self::predicate(left, now)?),
+                Box::new(Self::predicate(right, now)?),
+            ),
+            QueryExpression::OR(left, right) => EntryPredicate::OR(
+                Box::new(Self::predicate(left, now)?),
+                Box::new(Self::predicate(right, now)?),
+            ),
+            QueryExpression::NOT(inner) => EntryPredicate::NOT(Box::new(Self::predicate(inner, now)?)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::representational::chunk::Chunk;
+    use crate::logging::chunk_store_drain::format_message;
+    use crate::utils::test_utils::entry_at;
+
+    const NOW: u64 = 10 * 60 * 60 * 1000;
+
+    fn plan_of(text: &str) -> QueryPlan {
+        return QueryPlanner::plan_text(text, NOW).ok().unwrap_or_else(|| panic!("Could not plan {}", text));
+    }
+
+    fn entry_with(target: &str, action: u8, fields: &[(&str, &str)]) -> ChunkEntry {
+        let fields: Vec<(String, String)> = fields.iter()
+            .map(|(k, v): &(&str, &str)| (String::from(*k), String::from(*v)))
+            .collect();
+        return ChunkEntry {
+            target: target.as_bytes().to_vec(),
+            action,
+            message: format_message("request handled", fields.as_slice()),
+            ..entry_at(100)
+        };
+    }
+
+    #[test]
+    fn and_conditions_intersect_time_bounds() {
+        let plan: QueryPlan = plan_of("time:[100 TO 500] AND time:[300 TO 900] target:api");
+        assert_eq!(
+            plan.chunk_condition,
+            ChunkCondition::AND(vec![
+                ChunkCondition::AND(vec![ChunkCondition::TIME(100, 500), ChunkCondition::TIME(300, 900)]),
+                ChunkCondition::TARGET(b"api".to_vec()),
+            ]),
+        );
+        assert_eq!((plan.from, plan.to), (300, 500));
+        assert_eq!(plan.chunk_condition.time_bounds(), (300, 500));
+    }
+
+    #[test]
+    fn or_conditions_take_the_union_of_time_bounds() {
+        let plan: QueryPlan = plan_of("time:[100 TO 200] OR time:[now-1h TO *]");
+        assert_eq!(
+            plan.chunk_condition,
+            ChunkCondition::OR(vec![ChunkCondition::TIME(100, 200), ChunkCondition::TIME(NOW - 60 * 60 * 1000, u64::MAX)]),
+        );
+        assert_eq!((plan.from, plan.to), (100, u64::MAX));
+        // Either side matching anywhere lets the whole disjunction match anywhere
+        let plan: QueryPlan = plan_of("time:[100 TO 200] OR status>=500");
+        assert_eq!(plan.chunk_condition, ChunkCondition::ALL);
+        assert_eq!((plan.from, plan.to), (u64::MIN, u64::MAX));
+    }
+
+    #[test]
+    fn negations_match_in_any_chunk() {
+        let plan: QueryPlan = plan_of("NOT time:[100 TO 200]");
+        assert_eq!(plan.chunk_condition, ChunkCondition::ALL);
+        assert_eq!((plan.from, plan.to), (u64::MIN, u64::MAX));
+        assert!(!plan.predicate.matches(&entry_at(150)));
+        assert!(plan.predicate.matches(&entry_at(250)));
+        assert!(plan.index_query.is_none());
+        // A negation only widens its own side of a conjunction
+        let plan: QueryPlan = plan_of("time:[100 TO 200] NOT action:3");
+        assert_eq!(plan.chunk_condition, ChunkCondition::AND(vec![ChunkCondition::TIME(100, 200), ChunkCondition::ALL]));
+        assert_eq!((plan.from, plan.to), (100, 200));
+    }
+
+    #[test]
+    fn index_queries_follow_and_or_and_not() {
+        assert_eq!(
+            plan_of("term:disk term:full").index_query,
+            Some(IndexQuery::AND(vec![IndexQuery::TERM(String::from("disk")), IndexQuery::TERM(String::from("full"))])),
+        );
+        assert_eq!(plan_of("term:disk action:3").index_query, Some(IndexQuery::TERM(String::from("disk"))));
+        assert_eq!(plan_of("term:disk OR action:3").index_query, None);
+        assert_eq!(plan_of("NOT term:disk").index_query, None);
+    }
+
+    #[test]
+    fn compares_numbers_numerically_and_other_values_as_text() {
+        let errors: EntryPredicate = plan_of("status>=500").predicate;
+        assert!(errors.matches(&entry_with("api", 0, &[("status", "503")])));
+        assert!(!errors.matches(&entry_with("api", 0, &[("status", "404")])));
+        // As text "1000" would sort before "500"
+        assert!(errors.matches(&entry_with("api", 0, &[("status", "1000")])));
+        assert!(errors.matches(&entry_with("api", 0, &[("status", "abc")])));
+        assert!(!errors.matches(&entry_with("api", 0, &[("status", "40x")])));
+        let users: EntryPredicate = plan_of("user>bob").predicate;
+        assert!(users.matches(&entry_with("api", 0, &[("user", "carol")])));
+        assert!(!users.matches(&entry_with("api", 0, &[("user", "alice")])));
+        assert!(plan_of("user=\"bob smith\"").predicate.matches(&entry_with("api", 0, &[("user", "bob smith")])));
+        // A missing field is only ever unequal
+        assert!(!errors.matches(&entry_with("api", 0, &[])));
+        assert!(plan_of("status!=500").predicate.matches(&entry_with("api", 0, &[])));
+    }
+
+    #[test]
+    fn prunes_chunks_by_target_and_action_sections() {
+        let chunk: Chunk = Chunk::seal(&[
+            entry_with("api", 3, &[]),
+            entry_with("db", 3, &[]),
+            entry_with("db", 5, &[]),
+        ]).ok().expect("Could not seal chunk");
+        let sections: &[ChunkMetadataSection] = chunk.sections.as_slice();
+        let may_match = |text: &str| -> bool {
+            return plan_of(text).chunk_condition.may_match(chunk.timestamp_from, chunk.timestamp_to, sections);
+        };
+        assert!(may_match("target:api"));
+        assert!(may_match("target:db"));
+        assert!(!may_match("target:web"));
+        assert!(may_match("action:3"));
+        assert!(may_match("action:5"));
+        assert!(!may_match("action:4"));
+        assert!(!may_match("target:api action:4"));
+        assert!(may_match("target:web OR action:5"));
+        assert!(!may_match("target:web OR action:4"));
+        assert!(may_match("NOT target:api"));
+        assert!(!may_match("target:api time:[200 TO *]"));
+        // Without sections a chunk is always kept
+        assert!(ChunkCondition::TARGET(b"web".to_vec()).may_match(0, u64::MAX, &[]));
+        assert!(ChunkCondition::ACTION(4).may_match(0, u64::MAX, &[]));
+    }
+}
@@ -0,0 +1,21 @@
+use std::fs::File;
+use std::io::Error;
+use std::path::Path;
+
+///
+/// Sync the directory containing a path, so a file just renamed or created
+/// there survives a crash along with its contents.
+///
+/// # Arguments
+/// * path: File whose directory entry must be made durable
+///
+/// # Returns
+/// `Result<()>`: Empty result once the directory is synced
+///
+pub fn sync_parent_directory(path: &str) -> Result<(), Error> {
+    let parent: &Path = match Path::new(path).parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    return File::open(parent)?.sync_all();
+}
@@ -0,0 +1,23 @@
+pub const FNV_OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+const FNV_PRIME: u64 = 0x00000100000001B3;
+
+///
+/// 64-bit FNV-1a hash of a byte string from the given offset basis. Bloom
+/// filters and sketches are persisted with their chunks, so they hash with
+/// this rather than `std`'s hasher, whose output may change between builds.
+///
+/// # Arguments
+/// * basis: Starting state, `FNV_OFFSET_BASIS` for standard FNV-1a
+/// * bytes: Bytes to hash
+///
+/// # Returns
+/// `u64`: Hash of the bytes
+///
+pub fn fnv1a(basis: u64, bytes: &[u8]) -> u64 {
+    let mut hash: u64 = basis;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    return hash;
+}
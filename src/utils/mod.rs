@@ -1 +1,5 @@
-pub mod datetime_utils;
\ No newline at end of file
+pub mod datetime_utils;
+pub mod file_utils;
+pub mod hash_utils;
+#[cfg(test)]
+pub mod test_utils;
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+use crate::data::representational::chunk::Chunk;
+use crate::data::representational::chunk_entry::ChunkEntry;
+use crate::data::representational::store::chunk_store::ChunkStore;
+use crate::data::representational::store::chunk_store_writer::ChunkStoreWriterConfig;
+
+///
+/// Path for a test's store file in a fresh directory under the system temp
+/// directory, so tests can run in parallel and leave nothing in the repo.
+///
+pub fn temp_store_path(name: &str) -> String {
+    let directory: PathBuf = std::env::temp_dir().join(format!("chunky_logs_test_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).expect("Could not create test directory");
+    return directory.join("store.bin").to_string_lossy().into_owned();
+}
+
+///
+/// Writer settings for tests: small sectors so stores stay small, no
+/// background recompression, and chunks sealed after the given entry count.
+///
+pub fn test_writer_config(max_chunk_entries: usize) -> ChunkStoreWriterConfig {
+    return ChunkStoreWriterConfig {
+        sector_size: 64,
+        max_chunk_entries,
+        recompression: None,
+        ..ChunkStoreWriterConfig::default()
+    };
+}
+
+///
+/// Entry at the given timestamp with a fixed target and a message naming
+/// the timestamp.
+///
+pub fn entry_at(timestamp: u64) -> ChunkEntry {
+    return ChunkEntry {
+        timestamp,
+        action: 0,
+        target: b"target".to_vec(),
+        message: format!("message {}", timestamp).into_bytes(),
+    };
+}
+
+///
+/// Sealed chunk holding one `entry_at` for each timestamp.
+///
+pub fn chunk_of(timestamps: &[u64]) -> Chunk {
+    let entries: Vec<ChunkEntry> = timestamps.iter().map(|t: &u64| entry_at(*t)).collect();
+    return Chunk::seal(entries.as_slice()).ok().expect("Could not seal chunk");
+}
+
+///
+/// Sealed chunk holding an entry for each timestamp and message pair.
+///
+pub fn chunk_with(messages: &[(u64, &str)]) -> Chunk {
+    let entries: Vec<ChunkEntry> = messages.iter()
+        .map(|(timestamp, message): &(u64, &str)| ChunkEntry {
+            message: message.as_bytes().to_vec(),
+            ..entry_at(*timestamp)
+        })
+        .collect();
+    return Chunk::seal(entries.as_slice()).ok().expect("Could not seal chunk");
+}
+
+///
+/// Timestamps of every entry sealed into a store, in chunk order.
+///
+pub fn stored_timestamps(store: &ChunkStore) -> Vec<u64> {
+    return (0..store.header.chunk_offsets.len())
+        .flat_map(|i: usize| store.read_chunk(i).unwrap().decode_entries().ok().expect("Could not decode entries"))
+        .map(|e: ChunkEntry| e.timestamp)
+        .collect();
+}